@@ -1,5 +1,11 @@
-use crate::components::MenuBar;
+use std::path::PathBuf;
+
+use crate::components::{
+    BackupRestorer, ErrorDialog, JournalRecovery, MenuBar, PassphraseChanger, PassphrasePrompt,
+    StatementImporter,
+};
 use crate::data::SaveFile;
+use crate::error::Error;
 use crate::ui_state::UiState;
 
 pub const APP_NAME: &str = "Ebbify";
@@ -8,6 +14,13 @@ pub struct App {
     cached_title: String,
     save_file: Option<SaveFile>,
     ui_state: UiState,
+    backup_restorer: Option<BackupRestorer>,
+    journal_recovery: Option<JournalRecovery>,
+    passphrase_prompt: Option<PassphrasePrompt>,
+    passphrase_changer: Option<PassphraseChanger>,
+    error_dialog: Option<ErrorDialog>,
+    /// The last error writing the journal, so that it is only shown once while it keeps failing.
+    journal_error: Option<String>,
 }
 
 impl Default for App {
@@ -16,6 +29,12 @@ impl Default for App {
             cached_title: APP_NAME.into(),
             save_file: None,
             ui_state: Default::default(),
+            backup_restorer: None,
+            journal_recovery: None,
+            passphrase_prompt: None,
+            passphrase_changer: None,
+            error_dialog: None,
+            journal_error: None,
         }
     }
 }
@@ -24,6 +43,60 @@ impl App {
     pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
         Self::default()
     }
+
+    fn save_file_changed(&mut self) {
+        self.ui_state = Default::default();
+        self.passphrase_changer = None;
+        self.journal_error = None;
+        self.journal_recovery = None;
+        let save_file = match &self.save_file {
+            Some(save_file) => save_file,
+            None => return,
+        };
+        match save_file.recoverable_updates() {
+            Ok(recoverable_updates) => {
+                self.journal_recovery = JournalRecovery::new(&recoverable_updates);
+            }
+            Err(err) => {
+                println!("Failed to read journal: {:?}", err);
+                self.error_dialog = Some(ErrorDialog::new(
+                    "Could Not Recover Unsaved Changes",
+                    format!(
+                        "The edits made after {} was last saved can't be recovered: {}",
+                        save_file.path.display(),
+                        err
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// `open_file` loads the save file at `path`, asking for a passphrase if it is encrypted and
+    /// offering to restore a backup if it is corrupt.
+    fn open_file(&mut self, path: PathBuf, passphrase: Option<String>) {
+        match SaveFile::load(path.clone(), passphrase.as_deref()) {
+            Ok(save_file) => {
+                self.save_file = Some(save_file);
+                self.save_file_changed();
+            }
+            Err(Error::PassphraseRequired) => {
+                self.passphrase_prompt = Some(PassphrasePrompt::new(path, None));
+            }
+            Err(err @ Error::WrongPassphrase) => {
+                self.passphrase_prompt = Some(PassphrasePrompt::new(path, Some(err.to_string())));
+            }
+            Err(err @ (Error::Corrupt(_) | Error::Tampered)) => {
+                self.backup_restorer = Some(BackupRestorer::new(path, err.to_string(), passphrase));
+            }
+            Err(err) => {
+                println!("Failed to load budget file: {:?}", err);
+                self.error_dialog = Some(ErrorDialog::new(
+                    "Could Not Open File",
+                    format!("{} could not be loaded: {}", path.display(), err),
+                ));
+            }
+        }
+    }
 }
 
 impl eframe::App for App {
@@ -47,22 +120,97 @@ impl eframe::App for App {
             self.cached_title = target_title;
         }
 
-        egui::TopBottomPanel::top("top-panel").show(ctx, |ui| {
-            let menu_bar_response = MenuBar::add(ui, ctx, &mut self.save_file);
-            if menu_bar_response.save_file_changed {
-                self.ui_state = Default::default();
-            }
-        });
+        // Until the old journal has been replayed or discarded, saving would discard it, and edits
+        // would be made to the file without the edits it holds.
+        let editable = self.journal_recovery.is_none();
+        let menu_bar_response = egui::TopBottomPanel::top("top-panel")
+            .show(ctx, |ui| {
+                MenuBar::add(ui, ctx, &mut self.save_file, editable)
+            })
+            .inner;
+        if menu_bar_response.save_file_changed {
+            self.save_file_changed();
+        }
+        if let Some(parsed_statement) = menu_bar_response.parsed_statement {
+            self.ui_state.statement_importer = Some(StatementImporter::new(parsed_statement));
+        }
+        if let Some(path) = menu_bar_response.open_path {
+            self.open_file(path, None);
+        }
+        if menu_bar_response.change_passphrase_pressed {
+            self.passphrase_changer = Some(Default::default());
+        }
+        if let Some(save_error) = menu_bar_response.save_error {
+            self.error_dialog = Some(ErrorDialog::new("Could Not Save File", save_error));
+        }
         egui::CentralPanel::default().show(ctx, |ui| match &mut self.save_file {
             Some(save_file) => {
                 self.ui_state.add_tab_selector(ui);
                 ui.separator();
+                ui.set_enabled(editable);
                 self.ui_state
                     .add_current_tab(ui, ctx, &mut save_file.app_data);
+                self.ui_state.add_windows(ctx, &mut save_file.app_data);
             }
             None => {
                 ui.heading("Load a budget");
             }
         });
+        if let Some(backup_restorer) = &mut self.backup_restorer {
+            let response = backup_restorer.add(ctx);
+            if let Some(restored) = response.restored {
+                self.save_file = Some(restored);
+                self.save_file_changed();
+            }
+            if !response.is_open {
+                self.backup_restorer = None;
+            }
+        }
+        if let Some(passphrase_prompt) = &mut self.passphrase_prompt {
+            let response = passphrase_prompt.add(ctx);
+            let path = passphrase_prompt.path().clone();
+            if !response.is_open {
+                self.passphrase_prompt = None;
+            }
+            if let Some(passphrase) = response.passphrase {
+                self.open_file(path, Some(passphrase));
+            }
+        }
+
+        if let Some(save_file) = &mut self.save_file {
+            if let Some(passphrase_changer) = &mut self.passphrase_changer {
+                if !passphrase_changer.add(ctx, save_file) {
+                    self.passphrase_changer = None;
+                }
+            }
+            match &mut self.journal_recovery {
+                Some(journal_recovery) => {
+                    if !journal_recovery.add(ctx, save_file) {
+                        self.journal_recovery = None;
+                    }
+                }
+                // Hold off on journaling until the old journal has been replayed or discarded.
+                None => match save_file.write_journal() {
+                    Ok(()) => self.journal_error = None,
+                    Err(err) => {
+                        println!("Failed to write journal: {:?}", err);
+                        let error = format!(
+                            "Unsaved changes can't be recovered if the app quits: {}",
+                            err
+                        );
+                        if self.journal_error.as_ref() != Some(&error) {
+                            self.error_dialog =
+                                Some(ErrorDialog::new("Could Not Write Journal", error.clone()));
+                            self.journal_error = Some(error);
+                        }
+                    }
+                },
+            }
+        }
+        if let Some(error_dialog) = &mut self.error_dialog {
+            if !error_dialog.add(ctx) {
+                self.error_dialog = None;
+            }
+        }
     }
 }
@@ -2,8 +2,11 @@ use std::collections::HashMap;
 
 use egui::{Button, Context, Grid, Ui, Window};
 
-use super::BalanceManager;
-use crate::data::{next_id, Account, AppData, Balance, CachedValue, Price, Update};
+use super::{BalanceManager, DeleteDialog};
+use crate::data::{
+    next_id, Account, AppData, Balance, CachedValue, Converter, Currency, Dependency, EntityRef,
+    Money, Price, Update,
+};
 use crate::widgets::CurrencyPicker;
 
 struct AccountEditor {
@@ -41,11 +44,33 @@ impl AccountEditor {
     }
 }
 
-#[derive(Default)]
 pub struct AccountManager {
     account_editor: Option<AccountEditor>,
-    latest_balances: CachedValue<HashMap<u32, i32>>,
+    latest_balances: CachedValue<HashMap<u32, Option<Money>>>,
+    /// The total of the balances in the base currency, or `None` if it overflowed.
+    total: CachedValue<Option<Money>>,
     balance_manager: Option<BalanceManager>,
+    delete_dialog: Option<DeleteDialog>,
+}
+
+impl Default for AccountManager {
+    fn default() -> Self {
+        Self {
+            account_editor: None,
+            latest_balances: CachedValue::depending_on(&[
+                Dependency::Accounts,
+                Dependency::Transactions,
+            ]),
+            total: CachedValue::depending_on(&[
+                Dependency::Accounts,
+                Dependency::Currencies,
+                Dependency::Settings,
+                Dependency::Transactions,
+            ]),
+            balance_manager: None,
+            delete_dialog: None,
+        }
+    }
 }
 
 impl AccountManager {
@@ -57,6 +82,29 @@ impl AccountManager {
         }
     }
 
+    /// `total` converts the current balances into `currency` at today's rates. The balances of
+    /// credit accounts are liabilities, so they are subtracted.
+    fn total(app_data: &AppData, currency: &Currency) -> Option<Money> {
+        let today = chrono::offset::Local::now().date_naive();
+        let mut total = Money::ZERO;
+        let mut converter = Converter::new(app_data);
+        for account in app_data.accounts().values() {
+            let account_currency = app_data.currencies().get(&account.currency_id)?;
+            let amount = converter.convert(
+                account.current_amount(app_data)?,
+                account_currency,
+                currency,
+                today,
+            )?;
+            total = if account.debit_account {
+                total.checked_add(amount)?
+            } else {
+                total.checked_sub(amount)?
+            };
+        }
+        Some(total)
+    }
+
     pub fn add(&mut self, ui: &mut Ui, ctx: &Context, app_data: &mut AppData) {
         let latest_balances = self.latest_balances.get(app_data, |app_data: &AppData| {
             app_data
@@ -65,8 +113,9 @@ impl AccountManager {
                 .map(|(account_id, account)| (*account_id, account.current_amount(app_data)))
                 .collect()
         });
+        let mut account_to_remove = None;
         Grid::new("account-manager-grid")
-            .num_columns(5)
+            .num_columns(6)
             .spacing([40.0, 4.0])
             .striped(true)
             .show(ui, |ui| {
@@ -75,32 +124,56 @@ impl AccountManager {
                 ui.strong("Credit/Debit");
                 ui.strong("Balance");
                 ui.strong("Edit");
+                ui.strong("Delete");
                 ui.end_row();
                 for account in app_data.accounts().values() {
                     let currency = app_data.currencies().get(&account.currency_id).unwrap();
                     ui.label(&account.name);
                     ui.label(&currency.code);
                     ui.label(Self::credit_or_debit(account.debit_account));
-                    if ui
-                        .link(format!(
-                            "{}",
-                            Price::new(*latest_balances.get(&account.id).unwrap_or(&0), currency),
-                        ))
-                        .clicked()
-                    {
-                        if self.balance_manager.is_none() {
-                            self.balance_manager = Some(BalanceManager::new(account.id));
-                        }
+                    let balance_text = match latest_balances.get(&account.id).copied().flatten() {
+                        Some(balance) => format!("{}", Price::new(balance, currency)),
+                        None => "Overflow".into(),
+                    };
+                    if ui.link(balance_text).clicked() && self.balance_manager.is_none() {
+                        self.balance_manager = Some(BalanceManager::new(account.id));
+                    }
+                    if ui.button("Edit").clicked() && self.account_editor.is_none() {
+                        self.account_editor = Some(AccountEditor::of_account(account));
                     }
-                    if ui.button("Edit").clicked() {
-                        if self.account_editor.is_none() {
-                            self.account_editor = Some(AccountEditor::of_account(account));
-                        }
+                    if ui.button("Delete").clicked() {
+                        account_to_remove = Some(account.id);
                     }
                     ui.end_row();
                 }
+
+                if let Some(currency) = app_data.base_currency() {
+                    ui.strong("Total");
+                    ui.label(&currency.code);
+                    ui.label("");
+                    let total = *self.total.get(app_data, |app_data: &AppData| {
+                        Self::total(app_data, currency)
+                    });
+                    ui.label(match total {
+                        Some(total) => format!("{}", Price::new(total, currency)),
+                        None => "Overflow".into(),
+                    });
+                }
+                ui.end_row();
             });
 
+        if let Some(account_to_remove) = account_to_remove {
+            if self.delete_dialog.is_none() {
+                self.delete_dialog =
+                    DeleteDialog::delete(app_data, EntityRef::Account(account_to_remove));
+            }
+        }
+        if let Some(delete_dialog) = &mut self.delete_dialog {
+            if !delete_dialog.add(ctx, app_data) {
+                self.delete_dialog = None;
+            }
+        }
+
         if ui.button("New Account").clicked() {
             self.account_editor = Some(Default::default());
         }
@@ -146,7 +219,7 @@ impl AccountManager {
                             ui.end_row();
                         });
                     let is_ok =
-                        account_editor.name.len() > 0 && account_editor.currency_id.is_some();
+                        !account_editor.name.is_empty() && account_editor.currency_id.is_some();
                     if ui.add_enabled(is_ok, Button::new(button_text)).clicked() {
                         clicked_create = true;
                     }
@@ -174,17 +247,25 @@ impl AccountManager {
                 balances,
                 autofocus: _,
             } = self.account_editor.take().unwrap();
-            let id = match id {
-                Some(id) => id,
-                None => next_id(app_data.accounts()),
+            let (id, label) = match id {
+                Some(id) => (id, format!("Edit account '{}'", name)),
+                None => (
+                    next_id(app_data.accounts()),
+                    format!("Create account '{}'", name),
+                ),
             };
-            app_data.perform_update(vec![Update::SetAccount(Account {
-                id,
-                name,
-                currency_id: currency_id.unwrap(),
-                debit_account,
-                balances,
-            })]);
+            if let Err(err) = app_data.perform_update(
+                label,
+                vec![Update::SetAccount(Account {
+                    id,
+                    name,
+                    currency_id: currency_id.unwrap(),
+                    debit_account,
+                    balances,
+                })],
+            ) {
+                println!("Failed to save account: {:?}", err);
+            }
         }
 
         if !is_open || clicked_create {
@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use egui::{Context, Grid, Window};
+
+use crate::data::{backups, SaveFile};
+
+pub struct BackupRestorerResponse {
+    pub is_open: bool,
+    pub restored: Option<SaveFile>,
+}
+
+/// Offers to restore a save file that couldn't be loaded from one of its backups.
+pub struct BackupRestorer {
+    path: PathBuf,
+    error: String,
+    /// The passphrase the file was opened with, if it is encrypted.
+    passphrase: Option<String>,
+    backups: Vec<PathBuf>,
+}
+
+impl BackupRestorer {
+    pub fn new(path: PathBuf, error: String, passphrase: Option<String>) -> Self {
+        let backups = backups(&path);
+        Self {
+            path,
+            error,
+            passphrase,
+            backups,
+        }
+    }
+
+    pub fn add(&mut self, ctx: &Context) -> BackupRestorerResponse {
+        let mut is_open = true;
+        let mut restore_from = None;
+        Window::new("Restore Backup")
+            .open(&mut is_open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} could not be loaded: {}",
+                    self.path.display(),
+                    self.error
+                ));
+                if self.backups.is_empty() {
+                    ui.label("There are no backups of this file.");
+                    return;
+                }
+                ui.label("Restoring a backup replaces the file with it.");
+                Grid::new("backup-restorer-grid")
+                    .num_columns(2)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for backup in self.backups.iter() {
+                            ui.label(backup.file_name().unwrap_or_default().to_string_lossy());
+                            if ui.button("Restore").clicked() {
+                                restore_from = Some(backup.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        let mut restored = None;
+        if let Some(backup) = restore_from {
+            match SaveFile::restore_backup(self.path.clone(), &backup, self.passphrase.as_deref()) {
+                Ok(save_file) => {
+                    restored = Some(save_file);
+                    is_open = false;
+                }
+                Err(err) => {
+                    self.error = format!(
+                        "{}; restoring {} failed: {}",
+                        self.error,
+                        backup.display(),
+                        err
+                    );
+                }
+            }
+        }
+        BackupRestorerResponse { is_open, restored }
+    }
+}
@@ -1,14 +1,14 @@
 use chrono::naive::NaiveDate as Date;
 use egui::{Button, Color32, Context, Grid, RichText, Ui, Window};
 
-use crate::data::{AppData, Balance, CachedValue, Price, Update};
+use crate::data::{AppData, Balance, CachedValue, Dependency, Money, Price, Update};
 use crate::widgets::{date_input, price_input};
 
 struct BalanceEditor {
     new_balance: bool,
     date: Date,
-    amount: Option<i32>,
-    computed_amount: CachedValue<i32>,
+    amount: Option<Money>,
+    computed_amount: CachedValue<Option<Money>>,
 }
 
 impl Default for BalanceEditor {
@@ -35,7 +35,7 @@ impl BalanceEditor {
 
 pub struct BalanceManager {
     account_id: u32,
-    balance_table: CachedValue<Vec<(Balance, i32)>>,
+    balance_table: CachedValue<Vec<(Balance, Option<Money>)>>,
     balance_editor: Option<BalanceEditor>,
 }
 
@@ -43,7 +43,7 @@ impl BalanceManager {
     pub fn new(account_id: u32) -> Self {
         Self {
             account_id,
-            balance_table: Default::default(),
+            balance_table: CachedValue::depending_on(&[Dependency::Account(account_id)]),
             balance_editor: None,
         }
     }
@@ -54,28 +54,26 @@ impl BalanceManager {
                 Some(account) => account,
                 None => return Vec::new(),
             };
-            let mut balance_table = Vec::new();
-            for balance in &account.balances {
-                balance_table.push((balance.clone(), 0));
-            }
-            balance_table.push((
-                Balance {
-                    date: Date::MAX,
-                    amount: 0,
-                },
-                0,
-            ));
-            let mut i = 0;
-            for transactions_on_date in app_data.transactions_by_date().values() {
-                for transaction_id in transactions_on_date {
-                    let transaction = app_data.transactions().get(&transaction_id).unwrap();
-                    if transaction.account_id == account.id {
-                        if transaction.date > balance_table[i].0.date {
-                            i += 1;
-                        }
-                        balance_table[i].1 += transaction.amount;
-                    }
+            // Each balance is paired with the total of the transactions since the one before, or
+            // `None` if that total is too large to represent.
+            let total_since = |previous_date: Option<Date>, date: Date| match (
+                app_data.account_index(account.id),
+                previous_date,
+            ) {
+                (Some(account_index), Some(previous_date)) => {
+                    account_index.total_between(previous_date, date)
                 }
+                (Some(account_index), None) => account_index.total_through(date),
+                (None, _) => Some(Money::ZERO),
+            };
+            let mut balance_table = Vec::new();
+            let mut previous_date = None;
+            for balance in account.balances.iter().chain([&Balance {
+                date: Date::MAX,
+                amount: Money::ZERO,
+            }]) {
+                balance_table.push((balance.clone(), total_since(previous_date, balance.date)));
+                previous_date = Some(balance.date);
             }
             balance_table
         });
@@ -96,36 +94,35 @@ impl BalanceManager {
                 ui.strong("Delete");
                 ui.end_row();
 
-                let mut last_balance_amount = 0;
+                let format_amount = |amount: Option<Money>| match amount {
+                    Some(amount) => format!("{}", Price::new(amount, currency)),
+                    None => "Overflow".into(),
+                };
+                let mut last_balance_amount = Money::ZERO;
                 for (index, (balance, delta)) in balance_table.iter().enumerate() {
                     if balance.date < Date::MAX {
-                        ui.label(&balance.date.to_string());
+                        ui.label(balance.date.to_string());
                         ui.label(format!("{}", Price::new(balance.amount, currency)));
-                        let expected_delta = balance.amount - last_balance_amount;
-                        let color = if *delta == expected_delta {
+                        let expected_delta = balance.amount.checked_sub(last_balance_amount);
+                        let color = if delta.is_some() && *delta == expected_delta {
                             Color32::GREEN
                         } else {
                             Color32::RED
                         };
                         last_balance_amount = balance.amount;
-                        ui.label(
-                            RichText::new(format!("{}", Price::new(*delta, currency))).color(color),
-                        );
-                        if ui.button("Edit").clicked() {
-                            if self.balance_editor.is_none() {
-                                self.balance_editor = Some(BalanceEditor::of_balance(balance));
-                            }
+                        ui.label(RichText::new(format_amount(*delta)).color(color));
+                        if ui.button("Edit").clicked() && self.balance_editor.is_none() {
+                            self.balance_editor = Some(BalanceEditor::of_balance(balance));
                         }
                         if ui.button("Delete").clicked() {
                             delete_index = Some(index);
                         }
                     } else {
                         ui.label("Latest");
-                        ui.label(format!(
-                            "{}",
-                            Price::new(last_balance_amount + delta, currency)
+                        ui.label(format_amount(
+                            delta.and_then(|delta| last_balance_amount.checked_add(delta)),
                         ));
-                        ui.label(format!("{}", Price::new(*delta, currency)));
+                        ui.label(format_amount(*delta));
                     }
                     ui.end_row();
                 }
@@ -157,13 +154,13 @@ impl BalanceManager {
                         .show(ui, |ui| {
                             ui.label("Date");
                             if balance_editor.new_balance {
-                                let old_date = balance_editor.date.clone();
+                                let old_date = balance_editor.date;
                                 ui.add(date_input(&mut balance_editor.date));
                                 if old_date != balance_editor.date {
                                     balance_editor.computed_amount.invalidate();
                                 }
                             } else {
-                                ui.label(&balance_editor.date.to_string());
+                                ui.label(balance_editor.date.to_string());
                             }
                             ui.end_row();
 
@@ -176,23 +173,25 @@ impl BalanceManager {
 
                             ui.label("Amount");
                             ui.horizontal(|ui| {
-                                let mut edit_amount = balance_editor.amount.unwrap_or(computed);
+                                let mut edit_amount =
+                                    balance_editor.amount.or(computed).unwrap_or(Money::ZERO);
                                 ui.add(price_input(&mut edit_amount, currency));
-                                balance_editor.amount = if edit_amount != computed {
+                                balance_editor.amount = if Some(edit_amount) != computed {
                                     Some(edit_amount)
                                 } else {
                                     None
                                 };
-                                if balance_editor.amount.is_some() {
-                                    if ui.button("⟲").clicked() {
-                                        balance_editor.amount = None;
-                                    }
+                                if balance_editor.amount.is_some() && ui.button("⟲").clicked() {
+                                    balance_editor.amount = None;
                                 }
                             });
                             ui.end_row();
 
                             ui.label("Computed balance on date");
-                            ui.label(format!("{}", Price::new(computed, currency)));
+                            ui.label(match computed {
+                                Some(computed) => format!("{}", Price::new(computed, currency)),
+                                None => "Overflow".into(),
+                            });
                             ui.end_row();
                         });
 
@@ -221,11 +220,16 @@ impl BalanceManager {
                 amount,
                 computed_amount: _,
             } = self.balance_editor.take().unwrap();
+            let amount = amount.or_else(|| account.balance_on_date(app_data, date));
             let amount = match amount {
                 Some(amount) => amount,
-                None => account.balance_on_date(app_data, date),
+                None => {
+                    println!("Failed to save balance: the computed balance overflowed");
+                    return;
+                }
             };
             let balance = Balance { date, amount };
+            let label = format!("Set balance of '{}' on {}", account.name, date);
             if new_balance {
                 let index = account.balances.partition_point(|b| b.date < date);
                 account.balances.insert(index, balance);
@@ -236,11 +240,19 @@ impl BalanceManager {
                     .unwrap();
                 account.balances[index] = balance;
             }
-            app_data.perform_update(vec![Update::SetAccount(account)]);
+            if let Err(err) = app_data.perform_update(label, vec![Update::SetAccount(account)]) {
+                println!("Failed to save balance: {:?}", err);
+            }
         } else if let Some(delete_index) = delete_index {
             let mut account = account.clone();
+            let label = format!(
+                "Delete balance of '{}' on {}",
+                account.name, account.balances[delete_index].date
+            );
             account.balances.remove(delete_index);
-            app_data.perform_update(vec![Update::SetAccount(account)]);
+            if let Err(err) = app_data.perform_update(label, vec![Update::SetAccount(account)]) {
+                println!("Failed to delete balance: {:?}", err);
+            }
         }
 
         if !is_open || clicked_create {
@@ -0,0 +1,239 @@
+use std::collections::HashSet;
+
+use chrono::naive::NaiveDate as Date;
+use chrono::Months;
+use egui::{Color32, ComboBox, Context, Grid, RichText, ScrollArea, Ui};
+
+use crate::data::{
+    month_start, AppData, Budget, BudgetReport, CachedValue, CategoryNode, Dependency, Money,
+    Price, ReportBasis, Rollover, Update,
+};
+use crate::widgets::price_input;
+
+pub struct BudgetManager {
+    month: Date,
+    currency_id: Option<u32>,
+    basis: ReportBasis,
+    /// `None` if there is no base currency, and `Some(None)` if it couldn't be computed.
+    report: CachedValue<Option<Option<BudgetReport>>>,
+    expanded: HashSet<u32>,
+}
+
+impl Default for BudgetManager {
+    fn default() -> Self {
+        Self {
+            month: month_start(chrono::offset::Local::now().date_naive()),
+            currency_id: None,
+            basis: ReportBasis::Cash,
+            report: CachedValue::depending_on(&[
+                Dependency::Budgets,
+                Dependency::Categories,
+                Dependency::Currencies,
+                Dependency::Flows,
+            ]),
+            expanded: HashSet::new(),
+        }
+    }
+}
+
+fn rollover_text(rollover: Option<Rollover>) -> &'static str {
+    match rollover {
+        None => "No budget",
+        Some(Rollover::CarrySurplus) => "Carry surplus",
+        Some(Rollover::CarryDeficit) => "Carry deficit",
+        Some(Rollover::Reset) => "Reset monthly",
+    }
+}
+
+fn colored_price(amount: Money, price: Price) -> RichText {
+    let text = RichText::new(format!("{}", price));
+    if amount.is_negative() {
+        text.color(Color32::RED)
+    } else {
+        text
+    }
+}
+
+impl BudgetManager {
+    fn add_options(&mut self, ui: &mut Ui, app_data: &AppData) {
+        ui.horizontal(|ui| {
+            if ui.button("◀").clicked() {
+                if let Some(month) = self.month.checked_sub_months(Months::new(1)) {
+                    self.month = month;
+                }
+            }
+            ui.strong(self.month.format("%B %Y").to_string());
+            if ui.button("▶").clicked() {
+                if let Some(month) = self.month.checked_add_months(Months::new(1)) {
+                    self.month = month;
+                }
+            }
+            if let Some(currency) = app_data.base_currency() {
+                ui.label(format!("In {}", currency.code));
+            }
+            ui.selectable_value(&mut self.basis, ReportBasis::Cash, "Cash basis");
+            ui.selectable_value(&mut self.basis, ReportBasis::Accrual, "Accrual basis");
+        });
+    }
+
+    /// Adds the row of `node` and, if it is expanded, its children. Edits to the budgets are added
+    /// to `updates`.
+    fn add_row(
+        &mut self,
+        ui: &mut Ui,
+        app_data: &AppData,
+        report: &BudgetReport,
+        node: &CategoryNode,
+        depth: usize,
+        updates: &mut Vec<Update>,
+    ) {
+        let name = &app_data.categories().get(&node.id).unwrap().name;
+        let currency = app_data.currencies().get(&report.currency_id).unwrap();
+        let budget = app_data.budgets().get(&node.id);
+        let totals = report.totals(node.id);
+        let is_expanded = self.expanded.contains(&node.id);
+        ui.horizontal(|ui| {
+            ui.add_space(depth as f32 * 16.0);
+            if !node.children.is_empty() && ui.link(if is_expanded { "▾" } else { "▸" }).clicked()
+            {
+                if is_expanded {
+                    self.expanded.remove(&node.id);
+                } else {
+                    self.expanded.insert(node.id);
+                }
+            }
+            ui.label(name);
+        });
+
+        let old_rollover = budget.map(|budget| budget.rollover);
+        let mut rollover = old_rollover;
+        ComboBox::from_id_source(("budget-rollover", node.id))
+            .selected_text(rollover_text(rollover))
+            .show_ui(ui, |ui| {
+                for option in [
+                    None,
+                    Some(Rollover::CarrySurplus),
+                    Some(Rollover::CarryDeficit),
+                    Some(Rollover::Reset),
+                ] {
+                    ui.selectable_value(&mut rollover, option, rollover_text(option));
+                }
+            });
+        if rollover != old_rollover {
+            updates.push(match (budget, rollover) {
+                (_, None) => Update::DeleteBudget(node.id),
+                (Some(budget), Some(rollover)) => Update::SetBudget(Budget {
+                    rollover,
+                    ..budget.clone()
+                }),
+                (None, Some(rollover)) => Update::SetBudget(Budget {
+                    category_id: node.id,
+                    rollover,
+                    allocations: Vec::new(),
+                }),
+            });
+        }
+
+        match budget {
+            Some(budget) => {
+                ui.horizontal(|ui| {
+                    let old_allocation = budget.allocation(report.month);
+                    let mut allocation = old_allocation;
+                    ui.add(price_input(&mut allocation, currency));
+                    if allocation != old_allocation {
+                        let mut budget = budget.clone();
+                        budget.set_allocation(report.month, allocation);
+                        updates.push(Update::SetBudget(budget));
+                    }
+                    // The budgets of descendants add to the total.
+                    if totals.allocated != old_allocation {
+                        ui.weak(format!("{}", Price::new(totals.allocated, currency)));
+                    }
+                });
+            }
+            None => {
+                ui.label(format!("{}", Price::new(totals.allocated, currency)));
+            }
+        }
+        ui.label(format!("{}", Price::new(totals.actual, currency)));
+        ui.label(colored_price(
+            totals.available,
+            Price::new(totals.available, currency),
+        ));
+        ui.end_row();
+
+        if is_expanded {
+            for child in node.children.iter() {
+                self.add_row(ui, app_data, report, child, depth + 1, updates);
+            }
+        }
+    }
+
+    pub fn add(&mut self, ui: &mut Ui, _ctx: &Context, app_data: &mut AppData) {
+        self.currency_id = app_data.base_currency().map(|currency| currency.id);
+        let old_options = (self.month, self.currency_id, self.basis);
+        self.add_options(ui, app_data);
+        if (self.month, self.currency_id, self.basis) != old_options {
+            self.report.invalidate();
+        }
+        ui.separator();
+
+        let (month, currency_id, basis) = (self.month, self.currency_id, self.basis);
+        let mut updates = Vec::new();
+        // Take the cached report so that rows can update `self` while reading it.
+        let mut report = std::mem::take(&mut self.report);
+        match report.get(app_data, |app_data: &AppData| {
+            currency_id.map(|currency_id| BudgetReport::new(app_data, month, currency_id, basis))
+        }) {
+            Some(Some(report)) => {
+                let currency = app_data.currencies().get(&report.currency_id).unwrap();
+                ui.horizontal(|ui| {
+                    ui.label("To be budgeted");
+                    ui.strong(colored_price(
+                        report.to_be_budgeted,
+                        Price::new(report.to_be_budgeted, currency),
+                    ));
+                    ui.separator();
+                    ui.label("Income to date");
+                    ui.label(format!("{}", Price::new(report.income, currency)));
+                });
+                ui.separator();
+
+                ScrollArea::both().show(ui, |ui| {
+                    Grid::new("budget-grid")
+                        .num_columns(5)
+                        .spacing([20.0, 4.0])
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Category");
+                            ui.strong("Rollover");
+                            ui.strong("Allocated");
+                            ui.strong("Actual");
+                            ui.strong("Available");
+                            ui.end_row();
+                            for node in app_data.category_trees().iter() {
+                                self.add_row(ui, app_data, report, node, 0, &mut updates);
+                            }
+                        });
+                });
+            }
+            Some(None) => {
+                ui.label(
+                    RichText::new("Some amounts can't be converted or are too large to represent.")
+                        .color(Color32::RED),
+                );
+            }
+            None => (),
+        }
+        self.report = report;
+
+        if !updates.is_empty() {
+            if let Err(err) = app_data.perform_update(
+                format!("Edit budget for {}", month.format("%B %Y")),
+                updates,
+            ) {
+                println!("Failed to save budget: {:?}", err);
+            }
+        }
+    }
+}
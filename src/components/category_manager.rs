@@ -1,7 +1,8 @@
 use egui::{Button, Context, Grid, Ui, Window};
 
-use crate::data::{next_id, AmortizationType, AppData, Category, CategoryNode, Update};
-use crate::widgets::CategoryPicker;
+use super::DeleteDialog;
+use crate::data::{next_id, AmortizationType, AppData, Category, CategoryNode, EntityRef, Update};
+use crate::widgets::{months_input, AmortizationPicker, CategoryPicker};
 
 #[derive(Default)]
 struct CategoryEditor {
@@ -29,6 +30,7 @@ impl CategoryEditor {
 #[derive(Default)]
 pub struct CategoryManager {
     category_editor: Option<CategoryEditor>,
+    delete_dialog: Option<DeleteDialog>,
 }
 
 impl CategoryManager {
@@ -37,29 +39,23 @@ impl CategoryManager {
         let mut node_to_remove = None;
         ui.horizontal(|ui| {
             ui.label(&app_data.categories().get(&node.id).unwrap().name);
-            if ui.link("+").clicked() {
-                if self.category_editor.is_none() {
-                    self.category_editor = Some(CategoryEditor {
-                        parent_id: Some(node.id),
-                        autofocus: true,
-                        ..Default::default()
-                    });
-                }
+            if ui.link("+").clicked() && self.category_editor.is_none() {
+                self.category_editor = Some(CategoryEditor {
+                    parent_id: Some(node.id),
+                    autofocus: true,
+                    ..Default::default()
+                });
             }
-            if node.children.len() == 0 {
-                if ui.link("-").clicked() {
-                    node_to_remove = Some(node.id);
-                }
+            if ui.link("-").clicked() {
+                node_to_remove = Some(node.id);
             }
-            if ui.link("Edit").clicked() {
-                if self.category_editor.is_none() {
-                    self.category_editor = Some(CategoryEditor::of_category(
-                        app_data.categories().get(&node.id).unwrap(),
-                    ));
-                }
+            if ui.link("Edit").clicked() && self.category_editor.is_none() {
+                self.category_editor = Some(CategoryEditor::of_category(
+                    app_data.categories().get(&node.id).unwrap(),
+                ));
             }
         });
-        if node.children.len() > 0 {
+        if !node.children.is_empty() {
             ui.indent(node.id, |ui| {
                 for child in node.children.iter() {
                     if let Some(id) = self.show_node(child, ui, app_data) {
@@ -79,7 +75,15 @@ impl CategoryManager {
             }
         }
         if let Some(node_to_remove) = node_to_remove {
-            app_data.perform_update(vec![Update::DeleteCategory(node_to_remove)]);
+            if self.delete_dialog.is_none() {
+                self.delete_dialog =
+                    DeleteDialog::delete(app_data, EntityRef::Category(node_to_remove));
+            }
+        }
+        if let Some(delete_dialog) = &mut self.delete_dialog {
+            if !delete_dialog.add(ctx, app_data) {
+                self.delete_dialog = None;
+            }
         }
 
         if ui.button("New Category").clicked() {
@@ -123,9 +127,27 @@ impl CategoryManager {
                                 app_data,
                             ));
                             ui.end_row();
+
+                            ui.label("Default amortization");
+                            ui.add(AmortizationPicker::new(
+                                "category-editor-amortization-picker",
+                                &mut category_editor.default_amortization_type,
+                            ));
+                            ui.end_row();
+
+                            if category_editor.default_amortization_type.is_some() {
+                                ui.label("Months");
+                                let mut length =
+                                    category_editor.default_amortization_length.unwrap_or(1);
+                                ui.add(months_input(&mut length));
+                                category_editor.default_amortization_length = Some(length);
+                                ui.end_row();
+                            } else {
+                                category_editor.default_amortization_length = None;
+                            }
                         });
                     if ui
-                        .add_enabled(category_editor.name.len() > 0, Button::new(button_text))
+                        .add_enabled(!category_editor.name.is_empty(), Button::new(button_text))
                         .clicked()
                     {
                         clicked_create = true;
@@ -142,17 +164,25 @@ impl CategoryManager {
                 default_amortization_length,
                 autofocus: _,
             } = self.category_editor.take().unwrap();
-            let id = match id {
-                Some(id) => id,
-                None => next_id(app_data.categories()),
+            let (id, label) = match id {
+                Some(id) => (id, format!("Edit category '{}'", name)),
+                None => (
+                    next_id(app_data.categories()),
+                    format!("Create category '{}'", name),
+                ),
             };
-            app_data.perform_update(vec![Update::SetCategory(Category {
-                id,
-                name,
-                parent_id,
-                default_amortization_type,
-                default_amortization_length,
-            })]);
+            if let Err(err) = app_data.perform_update(
+                label,
+                vec![Update::SetCategory(Category {
+                    id,
+                    name,
+                    parent_id,
+                    default_amortization_type,
+                    default_amortization_length,
+                })],
+            ) {
+                println!("Failed to save category: {:?}", err);
+            }
         }
 
         if !is_open || clicked_create {
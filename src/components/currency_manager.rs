@@ -1,14 +1,22 @@
-use egui::{Button, Context, Grid, Ui, Window};
+use std::collections::BTreeMap;
 
-use crate::data::{next_id, AppData, Currency, Update};
-use crate::widgets::stringable_input;
+use egui::{Button, ComboBox, Context, Grid, Ui, Window};
+
+use super::{DeleteDialog, ExchangeRateManager};
+use crate::data::{
+    base_currency_updates, next_id, AppData, Currency, EntityRef, Money, NumberFormat, Price,
+    SymbolPosition, Update,
+};
+use crate::widgets::{stringable_input, CurrencyPicker};
 
 struct CurrencyEditor {
     id: Option<u32>,
     code: String,
     major: i32,
-    equivalent_usd: f32,
+    equivalent_base: f32,
     symbol: String,
+    number_format: NumberFormat,
+    symbol_position: SymbolPosition,
     autofocus: bool,
 }
 
@@ -18,8 +26,10 @@ impl Default for CurrencyEditor {
             id: None,
             code: Default::default(),
             major: 100,
-            equivalent_usd: 1.0,
+            equivalent_base: 1.0,
             symbol: "$".into(),
+            number_format: Default::default(),
+            symbol_position: Default::default(),
             autofocus: true,
         }
     }
@@ -31,50 +41,146 @@ impl CurrencyEditor {
             id,
             code,
             major,
-            equivalent_usd,
+            equivalent_base,
             symbol,
+            number_format,
+            symbol_position,
+            exchange_rates: _,
         } = currency;
         Self {
             id: Some(*id),
             code: code.clone(),
             major: *major,
-            equivalent_usd: *equivalent_usd,
+            equivalent_base: *equivalent_base,
             symbol: symbol.clone(),
+            number_format: *number_format,
+            symbol_position: *symbol_position,
             autofocus: true,
         }
     }
+
+    fn to_currency(&self, id: u32) -> Currency {
+        Currency {
+            id,
+            code: self.code.clone(),
+            major: self.major,
+            equivalent_base: self.equivalent_base,
+            symbol: self.symbol.clone(),
+            number_format: self.number_format,
+            symbol_position: self.symbol_position,
+            exchange_rates: BTreeMap::new(),
+        }
+    }
+}
+
+fn number_format_text(number_format: NumberFormat) -> &'static str {
+    match number_format {
+        NumberFormat::CommaPeriod => "1,234.56",
+        NumberFormat::PeriodComma => "1.234,56",
+        NumberFormat::SpaceComma => "1 234,56",
+        NumberFormat::ApostrophePeriod => "1'234.56",
+    }
+}
+
+fn symbol_position_text(symbol_position: SymbolPosition) -> &'static str {
+    match symbol_position {
+        SymbolPosition::Prefix => "Before",
+        SymbolPosition::PrefixSpaced => "Before, spaced",
+        SymbolPosition::Suffix => "After",
+        SymbolPosition::SuffixSpaced => "After, spaced",
+    }
 }
 
 #[derive(Default)]
 pub struct CurrencyManager {
     currency_editor: Option<CurrencyEditor>,
+    exchange_rate_manager: Option<ExchangeRateManager>,
+    delete_dialog: Option<DeleteDialog>,
 }
 
 impl CurrencyManager {
     pub fn add(&mut self, ui: &mut Ui, ctx: &Context, app_data: &mut AppData) {
+        let old_base_currency_id = app_data.base_currency().map(|currency| currency.id);
+        let mut base_currency_id = old_base_currency_id;
+        ui.horizontal(|ui| {
+            ui.label("Base currency");
+            ui.add(CurrencyPicker::new(
+                "base-currency-picker",
+                &mut base_currency_id,
+                false,
+                app_data,
+            ));
+        });
+        if let Some(base_currency_id) = base_currency_id {
+            if Some(base_currency_id) != old_base_currency_id {
+                let label = format!(
+                    "Set base currency to {}",
+                    app_data.currencies()[&base_currency_id].code
+                );
+                let updates = base_currency_updates(app_data, base_currency_id);
+                if let Err(err) = app_data.perform_update(label, updates) {
+                    println!("Failed to set base currency: {:?}", err);
+                }
+            }
+        }
+        let base_code = app_data
+            .base_currency()
+            .map_or(String::new(), |currency| currency.code.clone());
+
+        let mut currency_to_remove = None;
         Grid::new("currency-manager-grid")
-            .num_columns(4)
+            .num_columns(6)
             .spacing([40.0, 4.0])
             .striped(true)
             .show(ui, |ui| {
                 ui.strong("Currency");
-                ui.strong("Equivalent USD");
+                ui.strong(format!("Equivalent {}", base_code));
                 ui.strong("Minor to Major");
+                ui.strong("Rates");
                 ui.strong("Edit");
+                ui.strong("Delete");
                 ui.end_row();
                 for currency in app_data.currencies().values() {
                     ui.label(&currency.code);
-                    ui.label(format!("{}", &currency.equivalent_usd));
+                    ui.label(format!("{}", &currency.equivalent_base));
                     ui.label(format!("{}", &currency.major));
-                    if ui.button("Edit").clicked() {
-                        if self.currency_editor.is_none() {
-                            self.currency_editor = Some(CurrencyEditor::of_currency(currency));
-                        }
+                    if ui.button("Rates").clicked() && self.exchange_rate_manager.is_none() {
+                        self.exchange_rate_manager = Some(ExchangeRateManager::new(currency.id));
+                    }
+                    if ui.button("Edit").clicked() && self.currency_editor.is_none() {
+                        self.currency_editor = Some(CurrencyEditor::of_currency(currency));
+                    }
+                    if ui.button("Delete").clicked() {
+                        currency_to_remove = Some(currency.id);
                     }
                     ui.end_row();
                 }
             });
 
+        if let Some(currency_to_remove) = currency_to_remove {
+            if self.delete_dialog.is_none() {
+                self.delete_dialog =
+                    DeleteDialog::delete(app_data, EntityRef::Currency(currency_to_remove));
+            }
+        }
+        if let Some(delete_dialog) = &mut self.delete_dialog {
+            if !delete_dialog.add(ctx, app_data) {
+                self.delete_dialog = None;
+            }
+        }
+
+        let mut exchange_rates_is_open = true;
+        if let Some(exchange_rate_manager) = &mut self.exchange_rate_manager {
+            Window::new("Exchange Rates")
+                .open(&mut exchange_rates_is_open)
+                .show(ctx, |ui| {
+                    exchange_rate_manager.add(ui, ctx, app_data);
+                });
+        }
+        if !exchange_rates_is_open {
+            self.exchange_rate_manager = None;
+        }
+
         if ui.button("New Currency").clicked() {
             self.currency_editor = Some(Default::default());
         }
@@ -104,8 +210,8 @@ impl CurrencyManager {
                             }
                             ui.end_row();
 
-                            ui.label("Equivalent USD");
-                            ui.add(stringable_input(&mut currency_editor.equivalent_usd));
+                            ui.label(format!("Equivalent {}", base_code));
+                            ui.add(stringable_input(&mut currency_editor.equivalent_base));
                             ui.end_row();
 
                             ui.label("Minor to Major");
@@ -115,8 +221,60 @@ impl CurrencyManager {
                             ui.label("Symbol");
                             ui.text_edit_singleline(&mut currency_editor.symbol);
                             ui.end_row();
+
+                            ui.label("Symbol position");
+                            ComboBox::from_id_source("currency-symbol-position")
+                                .selected_text(symbol_position_text(
+                                    currency_editor.symbol_position,
+                                ))
+                                .show_ui(ui, |ui| {
+                                    for symbol_position in [
+                                        SymbolPosition::Prefix,
+                                        SymbolPosition::PrefixSpaced,
+                                        SymbolPosition::Suffix,
+                                        SymbolPosition::SuffixSpaced,
+                                    ] {
+                                        ui.selectable_value(
+                                            &mut currency_editor.symbol_position,
+                                            symbol_position,
+                                            symbol_position_text(symbol_position),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label("Number format");
+                            ComboBox::from_id_source("currency-number-format")
+                                .selected_text(number_format_text(currency_editor.number_format))
+                                .show_ui(ui, |ui| {
+                                    for number_format in [
+                                        NumberFormat::CommaPeriod,
+                                        NumberFormat::PeriodComma,
+                                        NumberFormat::SpaceComma,
+                                        NumberFormat::ApostrophePeriod,
+                                    ] {
+                                        ui.selectable_value(
+                                            &mut currency_editor.number_format,
+                                            number_format,
+                                            number_format_text(number_format),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label("Example");
+                            let example = currency_editor.to_currency(0);
+                            ui.label(format!(
+                                "{}",
+                                Price::new(Money::from_minor(-123456789), &example)
+                            ));
+                            ui.end_row();
                         });
-                    let is_ok = currency_editor.code.len() > 0;
+                    // Conversions divide by these, so they must be positive.
+                    let is_ok = !currency_editor.code.is_empty()
+                        && currency_editor.equivalent_base.is_finite()
+                        && currency_editor.equivalent_base > 0.0
+                        && currency_editor.major > 0;
                     if ui.add_enabled(is_ok, Button::new(button_text)).clicked() {
                         clicked_create = true;
                     }
@@ -124,25 +282,22 @@ impl CurrencyManager {
         }
 
         if clicked_create {
-            let CurrencyEditor {
-                id,
-                code,
-                major,
-                equivalent_usd,
-                symbol,
-                autofocus: _,
-            } = self.currency_editor.take().unwrap();
-            let id = match id {
-                Some(id) => id,
-                None => next_id(app_data.currencies()),
+            let currency_editor = self.currency_editor.take().unwrap();
+            let (id, label) = match currency_editor.id {
+                Some(id) => (id, format!("Edit currency {}", currency_editor.code)),
+                None => (
+                    next_id(app_data.currencies()),
+                    format!("Create currency {}", currency_editor.code),
+                ),
             };
-            app_data.perform_update(vec![Update::SetCurrency(Currency {
-                id,
-                code,
-                major,
-                equivalent_usd,
-                symbol,
-            })]);
+            // The rates are edited separately, so keep whatever they are now.
+            let mut currency = currency_editor.to_currency(id);
+            if let Some(existing) = app_data.currencies().get(&id) {
+                currency.exchange_rates = existing.exchange_rates.clone();
+            }
+            if let Err(err) = app_data.perform_update(label, vec![Update::SetCurrency(currency)]) {
+                println!("Failed to save currency: {:?}", err);
+            }
         }
 
         if !is_open || clicked_create {
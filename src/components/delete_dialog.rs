@@ -0,0 +1,198 @@
+use egui::{Button, Context, Window};
+
+use crate::data::{reassign_updates, AppData, Deletion, Dependents, EntityRef, Update};
+use crate::error::Error;
+use crate::widgets::{AccountPicker, CategoryPicker, CurrencyPicker};
+
+/// Offers to reassign or delete the dependents of an entity that couldn't be deleted because
+/// other entities still refer to it.
+pub struct DeleteDialog {
+    entity: EntityRef,
+    dependents: Dependents,
+    reassign_to: Option<u32>,
+    error: Option<String>,
+}
+
+impl DeleteDialog {
+    /// Deletes `entity` if nothing refers to it. Otherwise, returns a dialog for resolving its
+    /// dependents.
+    pub fn delete(app_data: &mut AppData, entity: EntityRef) -> Option<Self> {
+        let update = match entity {
+            EntityRef::Account(id) => Update::DeleteAccount(id),
+            EntityRef::Category(id) => Update::DeleteCategory(id),
+            EntityRef::Currency(id) => Update::DeleteCurrency(id),
+        };
+        let label = format!("Delete {}", Self::describe(app_data, entity));
+        match app_data.perform_update(label, vec![update]) {
+            Ok(()) => None,
+            Err(Error::HasDependents(entity, dependents)) => Some(Self {
+                entity,
+                dependents: *dependents,
+                reassign_to: None,
+                error: None,
+            }),
+            Err(err) => {
+                println!("Failed to delete: {:?}", err);
+                None
+            }
+        }
+    }
+
+    /// A description of `entity` for the undo history, such as "account 'Checking'".
+    fn describe(app_data: &AppData, entity: EntityRef) -> String {
+        match entity {
+            EntityRef::Account(id) => format!(
+                "account '{}'",
+                app_data.accounts().get(&id).map_or("", |a| a.name.as_str())
+            ),
+            EntityRef::Category(id) => format!(
+                "category '{}'",
+                app_data
+                    .categories()
+                    .get(&id)
+                    .map_or("", |c| c.name.as_str())
+            ),
+            EntityRef::Currency(id) => format!(
+                "currency {}",
+                app_data
+                    .currencies()
+                    .get(&id)
+                    .map_or("", |c| c.code.as_str())
+            ),
+        }
+    }
+
+    fn name<'a>(&self, app_data: &'a AppData) -> &'a str {
+        match self.entity {
+            EntityRef::Account(id) => app_data.accounts().get(&id).map(|a| a.name.as_str()),
+            EntityRef::Category(id) => app_data.categories().get(&id).map(|c| c.name.as_str()),
+            EntityRef::Currency(id) => app_data.currencies().get(&id).map(|c| c.code.as_str()),
+        }
+        .unwrap_or("")
+    }
+
+    /// What reassigning does to the dependents of the entity.
+    fn reassign_explanation(&self) -> &'static str {
+        match self.entity {
+            EntityRef::Account(_) => {
+                "Reassigning moves its transactions, statements and rules to an account in the same \
+                currency. Transfers with that account are unlinked."
+            }
+            EntityRef::Category(_) => {
+                "Reassigning moves its subcategories, flows and rules to the other category, and \
+                adds its budget allocations to those of the other category."
+            }
+            EntityRef::Currency(_) => {
+                "Reassigning moves its exchange rates to the other currency. Amounts aren't \
+                converted, so a currency that accounts, flows or original amounts are in can't be \
+                reassigned."
+            }
+        }
+    }
+
+    fn id(&self) -> u32 {
+        match self.entity {
+            EntityRef::Account(id) | EntityRef::Category(id) | EntityRef::Currency(id) => id,
+        }
+    }
+
+    /// Returns whether the dialog should remain open.
+    pub fn add(&mut self, ctx: &Context, app_data: &mut AppData) -> bool {
+        let mut is_open = true;
+        let mut clicked_reassign = false;
+        let mut clicked_delete_all = false;
+        Window::new(format!("Delete {}", self.name(app_data)))
+            .open(&mut is_open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} is still used by {}.",
+                    self.name(app_data),
+                    self.dependents
+                ));
+                ui.label(self.reassign_explanation());
+                ui.horizontal(|ui| {
+                    ui.label("Reassign to");
+                    match self.entity {
+                        EntityRef::Account(id) => {
+                            let picker = AccountPicker::new(
+                                "delete-dialog-account-picker",
+                                &mut self.reassign_to,
+                                false,
+                                app_data,
+                            );
+                            match app_data.accounts().get(&id) {
+                                Some(account) => ui.add(picker.currency(account.currency_id)),
+                                None => ui.add(picker),
+                            }
+                        }
+                        EntityRef::Category(id) => ui.add(CategoryPicker::new(
+                            "delete-dialog-category-picker",
+                            &mut self.reassign_to,
+                            false,
+                            &Some(id),
+                            app_data,
+                        )),
+                        EntityRef::Currency(_) => ui.add(CurrencyPicker::new(
+                            "delete-dialog-currency-picker",
+                            &mut self.reassign_to,
+                            false,
+                            app_data,
+                        )),
+                    };
+                    let can_reassign =
+                        self.reassign_to.is_some() && self.reassign_to != Some(self.id());
+                    if ui
+                        .add_enabled(can_reassign, Button::new("Reassign and delete"))
+                        .clicked()
+                    {
+                        clicked_reassign = true;
+                    }
+                });
+                ui.separator();
+                if ui
+                    .button("Delete it and everything that depends on it")
+                    .clicked()
+                {
+                    clicked_delete_all = true;
+                }
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+            });
+
+        let description = Self::describe(app_data, self.entity);
+        let (label, updates) = if clicked_reassign {
+            let updates = match reassign_updates(app_data, self.entity, self.reassign_to.unwrap()) {
+                Ok(updates) => updates,
+                Err(err) => {
+                    self.error = Some(err.to_string());
+                    return true;
+                }
+            };
+            (
+                format!("Delete {} and reassign its dependents", description),
+                updates,
+            )
+        } else if clicked_delete_all {
+            (
+                format!("Delete {} and its dependents", description),
+                Deletion::cascade(app_data, self.entity).updates(app_data),
+            )
+        } else {
+            return is_open;
+        };
+        match app_data.perform_update(label, updates) {
+            Ok(()) => false,
+            Err(Error::HasDependents(_entity, dependents)) => {
+                self.dependents = *dependents;
+                self.error = None;
+                true
+            }
+            Err(err) => {
+                self.error = Some(err.to_string());
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use egui::{Context, Window};
+
+/// Shows an error that the user needs to know about, such as a file that couldn't be loaded or
+/// saved.
+pub struct ErrorDialog {
+    title: String,
+    message: String,
+}
+
+impl ErrorDialog {
+    pub fn new(title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Returns whether the dialog should remain open.
+    pub fn add(&mut self, ctx: &Context) -> bool {
+        let mut is_open = true;
+        let mut clicked_ok = false;
+        Window::new(&self.title)
+            .open(&mut is_open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.colored_label(ui.visuals().error_fg_color, &self.message);
+                if ui.button("OK").clicked() {
+                    clicked_ok = true;
+                }
+            });
+        is_open && !clicked_ok
+    }
+}
@@ -0,0 +1,183 @@
+use chrono::naive::NaiveDate as Date;
+use egui::{Button, Context, Grid, ScrollArea, Ui};
+
+use crate::data::{merge_rates, parse_rates_csv, AppData, ExchangeRate, Update};
+use crate::widgets::{date_input, stringable_input, CurrencyPicker};
+
+pub struct ExchangeRateManager {
+    currency_id: u32,
+    other_currency_id: Option<u32>,
+    new_date: Date,
+    new_rate: f64,
+    import_message: Option<String>,
+}
+
+impl ExchangeRateManager {
+    pub fn new(currency_id: u32) -> Self {
+        Self {
+            currency_id,
+            other_currency_id: None,
+            new_date: chrono::offset::Local::now().date_naive(),
+            new_rate: 1.0,
+            import_message: None,
+        }
+    }
+
+    pub fn add(&mut self, ui: &mut Ui, _ctx: &Context, app_data: &mut AppData) {
+        let currency = match app_data.currencies().get(&self.currency_id) {
+            Some(currency) => currency.clone(),
+            None => return,
+        };
+        if !self
+            .other_currency_id
+            .is_some_and(|id| app_data.currencies().contains_key(&id))
+        {
+            self.other_currency_id = app_data
+                .currencies()
+                .keys()
+                .find(|id| **id != currency.id)
+                .copied();
+        }
+
+        let mut updated_currency = None;
+        if ui.button("Import CSV…").clicked() {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("csv", &["csv"])
+                .pick_file()
+            {
+                match std::fs::read_to_string(path)
+                    .map_err(|err| err.into())
+                    .and_then(|text| parse_rates_csv(app_data, &currency, &text))
+                {
+                    Ok(tables) => {
+                        let mut new_currency = currency.clone();
+                        let mut count = 0;
+                        for (other_id, rates) in tables {
+                            count += rates.len();
+                            merge_rates(
+                                new_currency.exchange_rates.entry(other_id).or_default(),
+                                rates,
+                            );
+                        }
+                        // Imported tables can be against any currency, not just the one shown
+                        // below, so they are saved right away.
+                        let label = format!("Import exchange rates for {}", currency.code);
+                        self.import_message = match app_data
+                            .perform_update(label, vec![Update::SetCurrency(new_currency)])
+                        {
+                            Ok(()) => Some(format!("Imported {} rates", count)),
+                            Err(err) => {
+                                println!("Failed to save exchange rates: {:?}", err);
+                                Some(format!("Failed to import: {}", err))
+                            }
+                        };
+                    }
+                    Err(err) => {
+                        println!("Failed to import exchange rates: {:?}", err);
+                        self.import_message = Some(format!("Failed to import: {}", err));
+                    }
+                }
+            }
+        }
+        if let Some(import_message) = &self.import_message {
+            ui.label(import_message);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(format!("Rates for 1 {} in", currency.code));
+            ui.add(CurrencyPicker::new(
+                format!("exchange-rate-currency-picker-{}", currency.id).as_str(),
+                &mut self.other_currency_id,
+                false,
+                app_data,
+            ));
+        });
+        let other_currency = match self
+            .other_currency_id
+            .and_then(|id| app_data.currencies().get(&id))
+        {
+            Some(other_currency) if other_currency.id != currency.id => other_currency,
+            _ => return,
+        };
+        let table = currency
+            .exchange_rates
+            .get(&other_currency.id)
+            .map_or(&[][..], |table| table.as_slice());
+
+        let mut delete_date = None;
+        let row_height = ui.spacing().interact_size.y;
+        ScrollArea::vertical().max_height(300.0).show_rows(
+            ui,
+            row_height,
+            table.len(),
+            |ui, row_range| {
+                Grid::new(format!("exchange-rate-grid-{}", currency.id))
+                    .num_columns(3)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        // Newest first.
+                        for index in row_range {
+                            let rate = &table[table.len() - 1 - index];
+                            ui.label(rate.date.to_string());
+                            ui.label(format!("{} {}", rate.rate, other_currency.code));
+                            if ui.button("Delete").clicked() {
+                                delete_date = Some(rate.date);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            },
+        );
+        if table.is_empty() {
+            ui.label(format!(
+                "No rates against {}; conversions use the equivalent rates",
+                other_currency.code
+            ));
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(date_input(&mut self.new_date));
+            ui.add(stringable_input(&mut self.new_rate));
+            ui.label(&other_currency.code);
+            let is_ok = self.new_rate.is_finite() && self.new_rate > 0.0;
+            if ui.add_enabled(is_ok, Button::new("Add Rate")).clicked() {
+                let mut new_currency = currency.clone();
+                merge_rates(
+                    new_currency
+                        .exchange_rates
+                        .entry(other_currency.id)
+                        .or_default(),
+                    [ExchangeRate {
+                        date: self.new_date,
+                        rate: self.new_rate,
+                    }],
+                );
+                updated_currency = Some(new_currency);
+            }
+        });
+
+        if let Some(delete_date) = delete_date {
+            let mut new_currency = currency.clone();
+            if let Some(table) = new_currency.exchange_rates.get_mut(&other_currency.id) {
+                table.retain(|rate| rate.date != delete_date);
+                if table.is_empty() {
+                    new_currency.exchange_rates.remove(&other_currency.id);
+                }
+            }
+            updated_currency = Some(new_currency);
+        }
+
+        if let Some(updated_currency) = updated_currency {
+            let label = format!(
+                "Edit {}/{} exchange rates",
+                currency.code, other_currency.code
+            );
+            if let Err(err) =
+                app_data.perform_update(label, vec![Update::SetCurrency(updated_currency)])
+            {
+                println!("Failed to save exchange rates: {:?}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use chrono::{DateTime, Local};
+use egui::{Grid, RichText, ScrollArea, Ui};
+
+use crate::data::{AppData, Settings, Update, Updates};
+
+/// Lists the edits that can be undone and redone, and jumps to any point between them.
+#[derive(Default)]
+pub struct HistoryManager;
+
+fn time_text(updates: &Updates) -> String {
+    DateTime::<Local>::from(updates.time())
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+impl HistoryManager {
+    pub fn add(&mut self, ui: &mut Ui, app_data: &mut AppData) {
+        let mut save_history = app_data.settings().save_history;
+        ui.checkbox(&mut save_history, "Save history with the file");
+        if save_history != app_data.settings().save_history {
+            let label = if save_history {
+                "Save history with the file"
+            } else {
+                "Stop saving history with the file"
+            };
+            if let Err(err) = app_data.perform_update(
+                label,
+                vec![Update::SetSettings(Settings {
+                    save_history,
+                    ..app_data.settings().clone()
+                })],
+            ) {
+                println!("Failed to save settings: {:?}", err);
+            }
+        }
+        ui.separator();
+
+        let undo_count = app_data.undo_history().len();
+        let mut go_to = None;
+        ScrollArea::vertical().show(ui, |ui| {
+            Grid::new("history-grid")
+                .num_columns(3)
+                .spacing([20.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("Time");
+                    ui.strong("Edit");
+                    ui.label("");
+                    ui.end_row();
+
+                    // Each row is the state after its edit; the first is the state before any.
+                    let rows = std::iter::once(None)
+                        .chain(app_data.undo_history().iter().map(Some))
+                        .chain(app_data.redo_history().map(Some));
+                    for (index, updates) in rows.enumerate() {
+                        let is_undone = index > undo_count;
+                        let (time, label) = match updates {
+                            Some(updates) => (time_text(updates), updates.label().to_string()),
+                            None => (String::new(), "Start".to_string()),
+                        };
+                        let style = |text: String| {
+                            let text = RichText::new(text);
+                            if is_undone {
+                                text.weak()
+                            } else {
+                                text
+                            }
+                        };
+                        ui.label(style(time));
+                        ui.label(style(label));
+                        if index == undo_count {
+                            ui.strong("Current");
+                        } else if ui.button("Go here").clicked() {
+                            go_to = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+        if let Some(go_to) = go_to {
+            app_data.go_to_history(go_to);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+use egui::{Context, Window};
+
+use crate::data::{SaveFile, Updates};
+
+/// Offers to replay the edits that were made after a file was last saved, if the app quit before
+/// saving them.
+pub struct JournalRecovery {
+    count: usize,
+    time: Option<SystemTime>,
+    error: Option<String>,
+}
+
+impl JournalRecovery {
+    /// Returns a dialog for recovering `recoverable_updates`, if there are any.
+    pub fn new(recoverable_updates: &[Updates]) -> Option<Self> {
+        if recoverable_updates.is_empty() {
+            return None;
+        }
+        Some(Self {
+            count: recoverable_updates.len(),
+            time: recoverable_updates.last().map(|updates| updates.time()),
+            error: None,
+        })
+    }
+
+    /// Returns whether the dialog should remain open.
+    pub fn add(&mut self, ctx: &Context, save_file: &mut SaveFile) -> bool {
+        let mut clicked_replay = false;
+        let mut clicked_discard = false;
+        Window::new("Recover Unsaved Changes")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} edits were made to this file after it was last saved{}.",
+                    self.count,
+                    match self.time {
+                        Some(time) => format!(
+                            ", the last at {}",
+                            DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S")
+                        ),
+                        None => String::new(),
+                    }
+                ));
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Replay").clicked() {
+                        clicked_replay = true;
+                    }
+                    if ui.button("Discard").clicked() {
+                        clicked_discard = true;
+                    }
+                });
+            });
+
+        let result = if clicked_replay {
+            save_file.replay_journal().map_err(|err| {
+                println!("Failed to replay unsaved changes: {:?}", err);
+                // The edits before the one that failed have been replayed, and are no longer
+                // offered.
+                let recoverable_updates = save_file.recoverable_updates().unwrap_or_default();
+                self.count = recoverable_updates.len();
+                self.time = recoverable_updates.last().map(|updates| updates.time());
+                format!("Failed to replay unsaved changes: {}", err)
+            })
+        } else if clicked_discard {
+            save_file.discard_journal().map_err(|err| {
+                println!("Failed to discard unsaved changes: {:?}", err);
+                format!("Failed to discard unsaved changes: {}", err)
+            })
+        } else {
+            return true;
+        };
+        match result {
+            Ok(()) => false,
+            Err(error) => {
+                self.error = Some(error);
+                true
+            }
+        }
+    }
+}
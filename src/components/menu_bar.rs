@@ -1,9 +1,18 @@
+use std::path::PathBuf;
+
 use egui::{Button, Key, KeyboardShortcut, Modifiers, Widget};
 
-use crate::data::SaveFile;
+use crate::data::{parse_statement, ParsedStatement, SaveFile};
 
 pub struct MenuBarResponse {
     pub save_file_changed: bool,
+    pub parsed_statement: Option<ParsedStatement>,
+    /// A file picked to be opened. Loading it is left to the caller, since it might need a
+    /// passphrase or restoring from a backup.
+    pub open_path: Option<PathBuf>,
+    pub change_passphrase_pressed: bool,
+    /// Why saving failed, if it did.
+    pub save_error: Option<String>,
 }
 
 pub struct MenuBar;
@@ -24,10 +33,12 @@ impl MenuBar {
         )
     }
 
+    /// Unless `editable`, the items that modify or save the file are disabled.
     pub fn add(
         ui: &mut egui::Ui,
         ctx: &egui::Context,
         save_file: &mut Option<SaveFile>,
+        editable: bool,
     ) -> MenuBarResponse {
         let (new_button, mut new_pressed) =
             Self::button_with_shortcut(ui, ctx, "New", Modifiers::COMMAND, Key::N);
@@ -51,6 +62,14 @@ impl MenuBar {
             Modifiers::COMMAND.plus(Modifiers::SHIFT),
             Key::Z,
         );
+        let mut import_statement_pressed = false;
+        let mut change_passphrase_pressed = false;
+        // The shortcuts work even when their buttons are disabled.
+        if !editable {
+            save_pressed = false;
+            undo_pressed = false;
+            redo_pressed = false;
+        }
 
         egui::menu::bar(ui, |ui| {
             ui.menu_button("File", |ui| {
@@ -62,7 +81,7 @@ impl MenuBar {
                     open_pressed = true;
                     ui.close_menu();
                 }
-                ui.add_enabled_ui(save_file.is_some(), |ui| {
+                ui.add_enabled_ui(save_file.is_some() && editable, |ui| {
                     if ui.add(save_button).clicked() {
                         save_pressed = true;
                         ui.close_menu();
@@ -72,13 +91,34 @@ impl MenuBar {
                     load_sample_pressed = true;
                     ui.close_menu();
                 }
+                ui.add_enabled_ui(
+                    save_file.as_ref().map(|s| !s.is_sample).unwrap_or(false) && editable,
+                    |ui| {
+                        let label = match save_file {
+                            Some(save_file) if save_file.is_encrypted() => "Change passphrase",
+                            _ => "Set passphrase",
+                        };
+                        if ui.button(label).clicked() {
+                            change_passphrase_pressed = true;
+                            ui.close_menu();
+                        }
+                    },
+                );
+                ui.separator();
+                ui.add_enabled_ui(save_file.is_some() && editable, |ui| {
+                    if ui.button("Import statement").clicked() {
+                        import_statement_pressed = true;
+                        ui.close_menu();
+                    }
+                });
             });
             ui.menu_button("Edit", |ui| {
                 ui.add_enabled_ui(
                     save_file
                         .as_ref()
                         .map(|s| s.app_data.can_undo())
-                        .unwrap_or(false),
+                        .unwrap_or(false)
+                        && editable,
                     |ui| {
                         if ui.add(undo_button).clicked() {
                             undo_pressed = true;
@@ -90,7 +130,8 @@ impl MenuBar {
                     save_file
                         .as_ref()
                         .map(|s| s.app_data.can_redo())
-                        .unwrap_or(false),
+                        .unwrap_or(false)
+                        && editable,
                     |ui| {
                         if ui.add(redo_button).clicked() {
                             redo_pressed = true;
@@ -113,51 +154,67 @@ impl MenuBar {
             }
         }
 
+        let mut open_path = None;
         if open_pressed {
-            if let Some(path) = rfd::FileDialog::new()
+            open_path = rfd::FileDialog::new()
                 .add_filter("ebbify", &["ebb"])
+                .pick_file();
+        }
+
+        let mut save_error = None;
+        if save_pressed {
+            if let Some(save_file) = save_file {
+                if let Err(err) = save_file.save() {
+                    println!("Failed to save budget file: {:?}", err);
+                    save_error = Some(format!(
+                        "{} could not be saved: {}",
+                        save_file.path.display(),
+                        err
+                    ));
+                }
+            }
+        }
+
+        let mut parsed_statement = None;
+        if import_statement_pressed && save_file.is_some() {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("statement", &["pdf"])
                 .pick_file()
             {
-                match SaveFile::load(path) {
-                    Ok(file) => {
-                        *save_file = Some(file);
-                        save_file_changed = true;
+                match parse_statement(&path) {
+                    Ok(statement) => {
+                        parsed_statement = Some(statement);
                     }
                     Err(err) => {
-                        println!("Failed to load budget file: {:?}", err);
+                        println!("Failed to parse statement: {:?}", err);
                     }
                 }
             }
         }
 
-        if save_pressed {
-            match save_file {
-                Some(save_file) => {
-                    let _ = save_file.save();
-                }
-                None => (),
-            }
-        }
-
         if load_sample_pressed {
             *save_file = Some(SaveFile::load_sample());
             save_file_changed = true;
         }
 
         if undo_pressed {
-            match save_file {
-                Some(save_file) => save_file.app_data.undo(),
-                None => (),
+            if let Some(save_file) = save_file {
+                save_file.app_data.undo();
             }
         }
 
         if redo_pressed {
-            match save_file {
-                Some(save_file) => save_file.app_data.redo(),
-                None => (),
+            if let Some(save_file) = save_file {
+                save_file.app_data.redo();
             }
         }
 
-        MenuBarResponse { save_file_changed }
+        MenuBarResponse {
+            save_file_changed,
+            parsed_statement,
+            open_path,
+            change_passphrase_pressed,
+            save_error,
+        }
     }
 }
@@ -1,13 +1,45 @@
 mod account_manager;
+mod backup_restorer;
 mod balance_manager;
+mod budget_manager;
 mod category_manager;
 mod currency_manager;
+mod delete_dialog;
+mod error_dialog;
+mod exchange_rate_manager;
+mod history_manager;
+mod journal_recovery;
 mod menu_bar;
+mod net_worth_chart;
+mod passphrase_changer;
+mod passphrase_prompt;
+mod report_manager;
+mod rule_manager;
+mod statement_importer;
+mod transaction_group_editor;
 mod transaction_list;
+mod transaction_manager;
+mod transfer_matcher;
 
 pub use account_manager::AccountManager;
+pub use backup_restorer::BackupRestorer;
 pub use balance_manager::BalanceManager;
+pub use budget_manager::BudgetManager;
 pub use category_manager::CategoryManager;
 pub use currency_manager::CurrencyManager;
+pub use delete_dialog::DeleteDialog;
+pub use error_dialog::ErrorDialog;
+pub use exchange_rate_manager::ExchangeRateManager;
+pub use history_manager::HistoryManager;
+pub use journal_recovery::JournalRecovery;
 pub use menu_bar::MenuBar;
+pub use net_worth_chart::NetWorthChart;
+pub use passphrase_changer::PassphraseChanger;
+pub use passphrase_prompt::PassphrasePrompt;
+pub use report_manager::ReportManager;
+pub use rule_manager::RuleManager;
+pub use statement_importer::StatementImporter;
+pub use transaction_group_editor::TransactionGroupEditor;
 pub use transaction_list::TransactionList;
+pub use transaction_manager::TransactionManager;
+pub use transfer_matcher::TransferMatcher;
@@ -0,0 +1,155 @@
+use chrono::naive::NaiveDate as Date;
+use chrono::{Datelike, Months};
+use egui::plot::{Legend, Line, Plot, PlotPoints};
+use egui::{Color32, Context, RichText, Ui};
+
+use crate::data::{
+    AppData, CachedValue, Dependency, Money, NetWorthOptions, NetWorthSeries, Price, SampleInterval,
+};
+use crate::widgets::date_input;
+
+const OVERFLOW_MESSAGE: &str = "Some balances can't be converted or are too large to represent.";
+
+pub struct NetWorthChart {
+    start_date: Date,
+    end_date: Date,
+    interval: SampleInterval,
+    currency_id: Option<u32>,
+    /// `None` if there is no base currency, and `Some(None)` if it couldn't be computed.
+    series: CachedValue<Option<Option<NetWorthSeries>>>,
+}
+
+impl Default for NetWorthChart {
+    fn default() -> Self {
+        let today = chrono::offset::Local::now().date_naive();
+        Self {
+            start_date: today.checked_sub_months(Months::new(12)).unwrap(),
+            end_date: today,
+            interval: SampleInterval::Week,
+            currency_id: None,
+            series: CachedValue::depending_on(&[
+                Dependency::Accounts,
+                Dependency::Currencies,
+                Dependency::Transactions,
+            ]),
+        }
+    }
+}
+
+fn x_of_date(date: Date) -> f64 {
+    date.num_days_from_ce() as f64
+}
+
+fn date_of_x(x: f64) -> Option<Date> {
+    Date::from_num_days_from_ce_opt(x.round() as i32)
+}
+
+impl NetWorthChart {
+    fn options(&self) -> Option<NetWorthOptions> {
+        Some(NetWorthOptions {
+            start_date: self.start_date,
+            end_date: self.end_date,
+            interval: self.interval,
+            currency_id: self.currency_id?,
+        })
+    }
+
+    pub fn add(&mut self, ui: &mut Ui, _ctx: &Context, app_data: &mut AppData) {
+        self.currency_id = app_data.base_currency().map(|currency| currency.id);
+        let old_options = self.options();
+        ui.horizontal(|ui| {
+            ui.label("From");
+            ui.add(date_input(&mut self.start_date));
+            ui.label("To");
+            ui.add(date_input(&mut self.end_date));
+            ui.selectable_value(&mut self.interval, SampleInterval::Day, "Daily");
+            ui.selectable_value(&mut self.interval, SampleInterval::Week, "Weekly");
+            ui.selectable_value(&mut self.interval, SampleInterval::Month, "Monthly");
+            if let Some(currency) = app_data.base_currency() {
+                ui.label(format!("In {}", currency.code));
+            }
+        });
+        let options = self.options();
+        if options != old_options {
+            self.series.invalidate();
+        }
+
+        let series = match self.series.get(app_data, |app_data: &AppData| {
+            options
+                .clone()
+                .map(|options| NetWorthSeries::new(app_data, options))
+        }) {
+            Some(Some(series)) => series,
+            Some(None) => {
+                ui.label(RichText::new(OVERFLOW_MESSAGE).color(Color32::RED));
+                return;
+            }
+            None => return,
+        };
+        let currency = app_data
+            .currencies()
+            .get(&series.options.currency_id)
+            .unwrap();
+        let points = |amounts: &[Money]| -> PlotPoints {
+            series
+                .dates
+                .iter()
+                .zip(amounts.iter())
+                .map(|(date, amount)| {
+                    [
+                        x_of_date(*date),
+                        Price::new(*amount, currency).scaled_amount(),
+                    ]
+                })
+                .collect()
+        };
+
+        // Stack the assets upwards and the liabilities downwards from zero. Each account's area is
+        // drawn from zero to the top of its part of the stack, largest stack first.
+        let mut areas = Vec::new();
+        for debit_account in [true, false] {
+            let mut stack = vec![Money::ZERO; series.dates.len()];
+            let mut lines = Vec::new();
+            for (account_id, amounts) in series.accounts.iter() {
+                let account = app_data.accounts().get(account_id).unwrap();
+                if account.debit_account != debit_account {
+                    continue;
+                }
+                for (total, amount) in stack.iter_mut().zip(amounts.iter()) {
+                    match total.checked_add(*amount) {
+                        Some(sum) => *total = sum,
+                        None => {
+                            ui.label(RichText::new(OVERFLOW_MESSAGE).color(Color32::RED));
+                            return;
+                        }
+                    }
+                }
+                lines.push(Line::new(points(&stack)).fill(0.0).name(&account.name));
+            }
+            areas.extend(lines.into_iter().rev());
+        }
+
+        let label_currency = currency.clone();
+        Plot::new("net-worth-plot")
+            .legend(Legend::default())
+            .x_axis_formatter(|x, _range| date_of_x(x).map_or(String::new(), |d| d.to_string()))
+            .label_formatter(move |name, point| match date_of_x(point.x) {
+                Some(date) => {
+                    let amount = (point.y * label_currency.major as f64).round() as i64;
+                    let price = Price::new(Money::from_minor(amount), &label_currency);
+                    format!("{}\n{}\n{}", name, date, price)
+                }
+                None => String::new(),
+            })
+            .show(ui, |plot_ui| {
+                for area in areas {
+                    plot_ui.line(area);
+                }
+                plot_ui.line(
+                    Line::new(points(&series.totals))
+                        .width(2.0)
+                        .name("Net worth"),
+                );
+            });
+    }
+}
@@ -0,0 +1,61 @@
+use egui::{Context, Grid, TextEdit, Window};
+
+use crate::data::SaveFile;
+
+/// Sets, changes or removes the passphrase a save file is encrypted with.
+#[derive(Default)]
+pub struct PassphraseChanger {
+    passphrase: String,
+    confirmation: String,
+    error: Option<String>,
+}
+
+impl PassphraseChanger {
+    /// Returns whether the dialog should remain open.
+    pub fn add(&mut self, ctx: &Context, save_file: &mut SaveFile) -> bool {
+        let mut is_open = true;
+        let mut clicked_change = false;
+        Window::new("Change Passphrase")
+            .open(&mut is_open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                Grid::new("passphrase-changer-grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("New passphrase");
+                        ui.add(TextEdit::singleline(&mut self.passphrase).password(true));
+                        ui.end_row();
+                        ui.label("Confirm");
+                        ui.add(TextEdit::singleline(&mut self.confirmation).password(true));
+                        ui.end_row();
+                    });
+                let matches = self.passphrase == self.confirmation;
+                if !matches {
+                    ui.colored_label(ui.visuals().error_fg_color, "The passphrases don't match.");
+                } else if self.passphrase.is_empty() {
+                    ui.label("Without a passphrase, the file is saved unencrypted.");
+                }
+                ui.label(
+                    "The change takes effect when the file is saved, which also removes its backups.",
+                );
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                ui.add_enabled_ui(matches, |ui| {
+                    if ui.button("Change").clicked() {
+                        clicked_change = true;
+                    }
+                });
+            });
+
+        if clicked_change {
+            let passphrase = Some(self.passphrase.as_str()).filter(|p| !p.is_empty());
+            if let Err(err) = save_file.set_passphrase(passphrase) {
+                println!("Failed to change passphrase: {:?}", err);
+                self.error = Some(format!("Failed to change passphrase: {}", err));
+                return is_open;
+            }
+        }
+        is_open && !clicked_change
+    }
+}
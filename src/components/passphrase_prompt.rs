@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use egui::{Context, Key, TextEdit, Window};
+
+pub struct PassphrasePromptResponse {
+    pub is_open: bool,
+    pub passphrase: Option<String>,
+}
+
+/// Asks for the passphrase of an encrypted save file that is being opened.
+pub struct PassphrasePrompt {
+    path: PathBuf,
+    passphrase: String,
+    error: Option<String>,
+    focused: bool,
+}
+
+impl PassphrasePrompt {
+    pub fn new(path: PathBuf, error: Option<String>) -> Self {
+        Self {
+            path,
+            passphrase: String::new(),
+            error,
+            focused: false,
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn add(&mut self, ctx: &Context) -> PassphrasePromptResponse {
+        let mut is_open = true;
+        let mut submitted = false;
+        Window::new("Enter Passphrase")
+            .open(&mut is_open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!("{} is encrypted.", self.path.display()));
+                let response = ui.add(
+                    TextEdit::singleline(&mut self.passphrase)
+                        .password(true)
+                        .hint_text("Passphrase"),
+                );
+                if !self.focused {
+                    response.request_focus();
+                    self.focused = true;
+                }
+                if response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                    submitted = true;
+                }
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                if ui.button("Open").clicked() {
+                    submitted = true;
+                }
+            });
+
+        let passphrase = if submitted {
+            is_open = false;
+            Some(std::mem::take(&mut self.passphrase))
+        } else {
+            None
+        };
+        PassphrasePromptResponse {
+            is_open,
+            passphrase,
+        }
+    }
+}
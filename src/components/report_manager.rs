@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+
+use chrono::naive::NaiveDate as Date;
+use chrono::Datelike;
+use egui::{Color32, ComboBox, Context, Grid, Label, RichText, ScrollArea, Sense, Ui, Window};
+
+use super::TransactionList;
+use crate::data::{
+    AppData, CachedValue, CategoryNode, Dependency, Money, PeriodLength, Price, ReportBasis,
+    ReportOptions, SpendingReport,
+};
+use crate::widgets::date_input;
+
+struct DrillDown {
+    title: String,
+    transaction_ids: Vec<u32>,
+}
+
+pub struct ReportManager {
+    period_length: PeriodLength,
+    start_date: Date,
+    end_date: Date,
+    currency_id: Option<u32>,
+    basis: ReportBasis,
+    show_currency_totals: bool,
+    /// `None` if there is no base currency, and `Some(None)` if it couldn't be computed.
+    report: CachedValue<Option<Option<SpendingReport>>>,
+    expanded: HashSet<u32>,
+    drill_down: Option<DrillDown>,
+}
+
+impl Default for ReportManager {
+    fn default() -> Self {
+        let today = chrono::offset::Local::now().date_naive();
+        Self {
+            period_length: PeriodLength::Month,
+            start_date: Date::from_ymd_opt(today.year(), 1, 1).unwrap(),
+            end_date: today,
+            currency_id: None,
+            basis: ReportBasis::Cash,
+            show_currency_totals: false,
+            report: CachedValue::depending_on(&[
+                Dependency::Categories,
+                Dependency::Currencies,
+                Dependency::Flows,
+            ]),
+            expanded: HashSet::new(),
+            drill_down: None,
+        }
+    }
+}
+
+fn period_length_text(period_length: PeriodLength) -> &'static str {
+    match period_length {
+        PeriodLength::Month => "Month",
+        PeriodLength::Quarter => "Quarter",
+        PeriodLength::Year => "Year",
+        PeriodLength::Custom => "Custom range",
+    }
+}
+
+impl ReportManager {
+    fn options(&self) -> Option<ReportOptions> {
+        Some(ReportOptions {
+            period_length: self.period_length,
+            start_date: self.start_date,
+            end_date: self.end_date,
+            currency_id: self.currency_id?,
+            basis: self.basis,
+        })
+    }
+
+    fn add_options(&mut self, ui: &mut Ui, app_data: &AppData) {
+        ui.horizontal(|ui| {
+            ui.label("Period");
+            ComboBox::from_id_source("report-period-length")
+                .selected_text(period_length_text(self.period_length))
+                .show_ui(ui, |ui| {
+                    for period_length in [
+                        PeriodLength::Month,
+                        PeriodLength::Quarter,
+                        PeriodLength::Year,
+                        PeriodLength::Custom,
+                    ] {
+                        ui.selectable_value(
+                            &mut self.period_length,
+                            period_length,
+                            period_length_text(period_length),
+                        );
+                    }
+                });
+            ui.label("From");
+            ui.add(date_input(&mut self.start_date));
+            ui.label("To");
+            ui.add(date_input(&mut self.end_date));
+            if let Some(currency) = app_data.base_currency() {
+                ui.label(format!("In {}", currency.code));
+            }
+            ui.selectable_value(&mut self.basis, ReportBasis::Cash, "Cash basis");
+            ui.selectable_value(&mut self.basis, ReportBasis::Accrual, "Accrual basis");
+            ui.checkbox(&mut self.show_currency_totals, "By currency");
+        });
+    }
+
+    /// Adds the row of `node` and, if it is expanded, its children. Returns a drill-down if one of
+    /// the totals was clicked.
+    fn add_row(
+        &mut self,
+        ui: &mut Ui,
+        app_data: &AppData,
+        report: &SpendingReport,
+        node: &CategoryNode,
+        depth: usize,
+    ) -> Option<DrillDown> {
+        let mut drill_down = None;
+        let name = &app_data.categories().get(&node.id).unwrap().name;
+        let currency = app_data
+            .currencies()
+            .get(&report.options.currency_id)
+            .unwrap();
+        let is_expanded = self.expanded.contains(&node.id);
+        ui.horizontal(|ui| {
+            ui.add_space(depth as f32 * 16.0);
+            if !node.children.is_empty() && ui.link(if is_expanded { "▾" } else { "▸" }).clicked()
+            {
+                if is_expanded {
+                    self.expanded.remove(&node.id);
+                } else {
+                    self.expanded.insert(node.id);
+                }
+            }
+            ui.label(name);
+        });
+        let totals = report.totals(node.id);
+        let mut cells: Vec<(Option<usize>, RichText, String)> = totals
+            .iter()
+            .enumerate()
+            .map(|(index, amount)| {
+                let period_label = report.options.period_label(&report.periods[index]);
+                (
+                    Some(index),
+                    RichText::new(format!("{}", Price::new(*amount, currency))),
+                    format!("{}, {}", name, period_label),
+                )
+            })
+            .collect();
+        let total = totals
+            .iter()
+            .try_fold(Money::ZERO, |total, amount| total.checked_add(*amount));
+        let total = match total {
+            Some(total) => format!("{}", Price::new(total, currency)),
+            None => "Overflow".into(),
+        };
+        cells.push((None, RichText::new(total).strong(), name.clone()));
+        for (period_index, text, title) in cells {
+            if ui.add(Label::new(text).sense(Sense::click())).clicked() {
+                drill_down = Some(DrillDown {
+                    title,
+                    transaction_ids: report.transaction_ids(app_data, node, period_index),
+                });
+            }
+        }
+        if self.show_currency_totals {
+            let currency_totals: Vec<String> = report
+                .currency_totals(node.id)
+                .into_iter()
+                .flatten()
+                .filter(|(_, amount)| **amount != Money::ZERO)
+                .filter_map(|(currency_id, amount)| {
+                    let currency = app_data.currencies().get(currency_id)?;
+                    Some(format!("{}", Price::new(*amount, currency)))
+                })
+                .collect();
+            ui.label(currency_totals.join(", "));
+        }
+        ui.end_row();
+        if is_expanded {
+            for child in node.children.iter() {
+                if let Some(child_drill_down) = self.add_row(ui, app_data, report, child, depth + 1)
+                {
+                    drill_down = Some(child_drill_down);
+                }
+            }
+        }
+        drill_down
+    }
+
+    pub fn add(&mut self, ui: &mut Ui, ctx: &Context, app_data: &mut AppData) {
+        self.currency_id = app_data.base_currency().map(|currency| currency.id);
+        let old_options = self.options();
+        self.add_options(ui, app_data);
+        let options = self.options();
+        if options != old_options {
+            self.report.invalidate();
+        }
+        ui.separator();
+
+        // Take the cached report so that rows can update `self` while reading it.
+        let mut report = std::mem::take(&mut self.report);
+        match report.get(app_data, |app_data: &AppData| {
+            options
+                .clone()
+                .map(|options| SpendingReport::new(app_data, options))
+        }) {
+            Some(Some(report)) => {
+                let mut drill_down = None;
+                ScrollArea::both().show(ui, |ui| {
+                    Grid::new("report-grid")
+                        .num_columns(report.periods.len() + 2 + self.show_currency_totals as usize)
+                        .spacing([20.0, 4.0])
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Category");
+                            for period in report.periods.iter() {
+                                ui.strong(report.options.period_label(period));
+                            }
+                            ui.strong("Total");
+                            if self.show_currency_totals {
+                                ui.strong("By currency");
+                            }
+                            ui.end_row();
+                            for node in app_data.category_trees().iter() {
+                                if let Some(row_drill_down) =
+                                    self.add_row(ui, app_data, report, node, 0)
+                                {
+                                    drill_down = Some(row_drill_down);
+                                }
+                            }
+                        });
+                });
+                if drill_down.is_some() {
+                    self.drill_down = drill_down;
+                }
+            }
+            Some(None) => {
+                ui.label(
+                    RichText::new("Some amounts can't be converted or are too large to represent.")
+                        .color(Color32::RED),
+                );
+            }
+            None => (),
+        }
+        self.report = report;
+
+        let mut is_open = true;
+        if let Some(drill_down) = &self.drill_down {
+            Window::new(&drill_down.title)
+                .open(&mut is_open)
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    // Some of the transactions may have been deleted since the drill-down opened.
+                    let transaction_ids = drill_down
+                        .transaction_ids
+                        .iter()
+                        .filter(|id| app_data.transactions().contains_key(id))
+                        .copied()
+                        .collect();
+                    TransactionList::new(&transaction_ids).add(ui, app_data);
+                });
+        }
+        if !is_open {
+            self.drill_down = None;
+        }
+    }
+}
@@ -0,0 +1,517 @@
+use chrono::naive::NaiveDate as Date;
+use egui::{Button, Color32, Context, Grid, RichText, ScrollArea, Ui, Window};
+use regex::Regex;
+
+use crate::data::{
+    next_id, AmortizationType, AppData, CachedValue, Currency, Money, Price, Rule, RuleEngine,
+    RuleFlow, RuleMatch, Update,
+};
+use crate::widgets::{
+    date_input, months_input, price_input, AccountPicker, AmortizationPicker, CategoryPicker,
+};
+
+#[derive(Default)]
+struct RuleFlowEditor {
+    category_id: Option<u32>,
+    description: String,
+    amount: Option<Money>,
+    amortization_type: Option<AmortizationType>,
+    amortization_length: Option<i32>,
+}
+
+impl RuleFlowEditor {
+    fn of_rule_flow(rule_flow: &RuleFlow) -> Self {
+        Self {
+            category_id: Some(rule_flow.category_id),
+            description: rule_flow.description.clone().unwrap_or_default(),
+            amount: rule_flow.amount,
+            amortization_type: rule_flow.amortization_type,
+            amortization_length: rule_flow.amortization_length,
+        }
+    }
+}
+
+struct RuleEditor {
+    id: Option<u32>,
+    name: String,
+    description_regex: String,
+    min_amount: Option<Money>,
+    max_amount: Option<Money>,
+    account_id: Option<u32>,
+    start_date: Option<Date>,
+    end_date: Option<Date>,
+    flows: Vec<RuleFlowEditor>,
+    autofocus: bool,
+}
+
+impl Default for RuleEditor {
+    fn default() -> Self {
+        Self {
+            id: None,
+            name: Default::default(),
+            description_regex: Default::default(),
+            min_amount: None,
+            max_amount: None,
+            account_id: None,
+            start_date: None,
+            end_date: None,
+            flows: vec![Default::default()],
+            autofocus: true,
+        }
+    }
+}
+
+impl RuleEditor {
+    fn of_rule(rule: &Rule) -> Self {
+        Self {
+            id: Some(rule.id),
+            name: rule.name.clone(),
+            description_regex: rule.description_regex.clone().unwrap_or_default(),
+            min_amount: rule.min_amount,
+            max_amount: rule.max_amount,
+            account_id: rule.account_id,
+            start_date: rule.start_date,
+            end_date: rule.end_date,
+            flows: rule
+                .flows
+                .iter()
+                .map(RuleFlowEditor::of_rule_flow)
+                .collect(),
+            autofocus: true,
+        }
+    }
+
+    fn error(&self) -> Option<String> {
+        if self.name.is_empty() {
+            return Some("Rule needs a name".into());
+        }
+        if let Err(err) = Regex::new(&self.description_regex) {
+            return Some(format!("Invalid description regex: {}", err));
+        }
+        if self.flows.is_empty() {
+            return Some("Rule needs at least one flow".into());
+        }
+        if self.flows.iter().any(|flow| flow.category_id.is_none()) {
+            return Some("Every flow needs a category".into());
+        }
+        let has_amounts = self.min_amount.is_some()
+            || self.max_amount.is_some()
+            || self.flows.iter().any(|flow| flow.amount.is_some());
+        if has_amounts && self.account_id.is_none() {
+            return Some("Rules with amounts need an account, which sets their currency".into());
+        }
+        if self
+            .flows
+            .iter()
+            .filter(|flow| flow.amount.is_none())
+            .count()
+            > 1
+        {
+            return Some("Only one flow can take the rest of the amount".into());
+        }
+        None
+    }
+}
+
+/// A warning listing the rules with ids `invalid_rule_ids`, which are skipped because their
+/// description regex doesn't compile, or `None` if there are none.
+pub(super) fn invalid_rules_warning(
+    app_data: &AppData,
+    invalid_rule_ids: &[u32],
+) -> Option<String> {
+    if invalid_rule_ids.is_empty() {
+        return None;
+    }
+    let names: Vec<String> = invalid_rule_ids
+        .iter()
+        .filter_map(|id| app_data.rules().get(id))
+        .map(|rule| format!("'{}'", rule.name))
+        .collect();
+    Some(format!(
+        "Skipping rules with an invalid description regex: {}",
+        names.join(", ")
+    ))
+}
+
+/// Shows a checkbox that toggles whether `value` is set, and if it is, an input for it.
+fn optional_input<T: Clone>(
+    ui: &mut Ui,
+    value: &mut Option<T>,
+    default: T,
+    add_input: impl FnOnce(&mut Ui, &mut T),
+) {
+    ui.horizontal(|ui| {
+        let mut enabled = value.is_some();
+        ui.checkbox(&mut enabled, "");
+        *value = if enabled {
+            let mut t = value.clone().unwrap_or(default);
+            add_input(ui, &mut t);
+            Some(t)
+        } else {
+            None
+        };
+    });
+}
+
+#[derive(Default)]
+pub struct RuleManager {
+    rule_editor: Option<RuleEditor>,
+    /// The planned matches and the ids of the rules that were skipped.
+    dry_run: Option<CachedValue<(Vec<RuleMatch>, Vec<u32>)>>,
+}
+
+impl RuleManager {
+    /// The currency used to enter rule amounts: that of the rule's account. Rules without an
+    /// account match transactions in any currency, so they can't have amounts.
+    fn amount_currency(app_data: &AppData, account_id: Option<u32>) -> Option<&Currency> {
+        let account = app_data.accounts().get(&account_id?)?;
+        app_data.currencies().get(&account.currency_id)
+    }
+
+    fn plan_unassigned(app_data: &AppData) -> (Vec<RuleMatch>, Vec<u32>) {
+        let rule_engine = RuleEngine::new(app_data);
+        let rule_matches = rule_engine.plan(app_data, app_data.transactions().values());
+        (rule_matches, rule_engine.invalid_rule_ids)
+    }
+
+    fn add_dry_run(&mut self, ui: &mut Ui, app_data: &mut AppData) {
+        let dry_run = match &mut self.dry_run {
+            Some(dry_run) => dry_run,
+            None => return,
+        };
+        let (rule_matches, invalid_rule_ids) = dry_run.get(app_data, Self::plan_unassigned);
+        if let Some(warning) = invalid_rules_warning(app_data, invalid_rule_ids) {
+            ui.label(RichText::new(warning).color(Color32::RED));
+        }
+        let mut clicked_apply = false;
+        let mut clicked_close = false;
+        ui.horizontal(|ui| {
+            ui.strong(format!(
+                "Rules would assign {} unassigned transactions",
+                rule_matches.len()
+            ));
+            if ui
+                .add_enabled(!rule_matches.is_empty(), Button::new("Apply"))
+                .clicked()
+            {
+                clicked_apply = true;
+            }
+            if ui.button("Close").clicked() {
+                clicked_close = true;
+            }
+        });
+        ScrollArea::vertical().show(ui, |ui| {
+            Grid::new("rule-manager-dry-run-grid")
+                .num_columns(5)
+                .spacing([40.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("Date");
+                    ui.strong("Description");
+                    ui.strong("Amount");
+                    ui.strong("Rule");
+                    ui.strong("Flows");
+                    ui.end_row();
+                    for rule_match in rule_matches.iter() {
+                        let transaction = &rule_match.transaction;
+                        let account = app_data.accounts().get(&transaction.account_id).unwrap();
+                        let currency = app_data.currencies().get(&account.currency_id).unwrap();
+                        ui.label(transaction.date.to_string());
+                        ui.label(&transaction.description);
+                        ui.label(format!("{}", Price::new(transaction.amount, currency)));
+                        ui.label(&app_data.rules().get(&rule_match.rule_id).unwrap().name);
+                        ui.vertical(|ui| {
+                            for flow in rule_match.flows.iter() {
+                                let category_name = app_data
+                                    .categories()
+                                    .get(&flow.category_id)
+                                    .map_or("?", |category| &category.name);
+                                ui.label(format!(
+                                    "{}: {}",
+                                    category_name,
+                                    Price::new(flow.amount, currency)
+                                ));
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+
+        if clicked_apply {
+            let (rule_matches, _invalid_rule_ids) = Self::plan_unassigned(app_data);
+            if let Err(err) = app_data.perform_update(
+                format!("Apply rules to {} transactions", rule_matches.len()),
+                rule_matches
+                    .into_iter()
+                    .flat_map(|rule_match| rule_match.updates())
+                    .collect(),
+            ) {
+                println!("Failed to apply rules: {:?}", err);
+            }
+        }
+        if clicked_apply || clicked_close {
+            self.dry_run = None;
+        }
+    }
+
+    pub fn add(&mut self, ui: &mut Ui, ctx: &Context, app_data: &mut AppData) {
+        let mut rule_to_remove = None;
+        Grid::new("rule-manager-grid")
+            .num_columns(4)
+            .spacing([40.0, 4.0])
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Rule");
+                ui.strong("Description regex");
+                ui.strong("Edit");
+                ui.strong("Delete");
+                ui.end_row();
+                for rule in app_data.rules().values() {
+                    ui.label(&rule.name);
+                    ui.label(rule.description_regex.as_deref().unwrap_or(""));
+                    if ui.button("Edit").clicked() && self.rule_editor.is_none() {
+                        self.rule_editor = Some(RuleEditor::of_rule(rule));
+                    }
+                    if ui.button("Delete").clicked() {
+                        rule_to_remove = Some(rule.id);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(rule_to_remove) = rule_to_remove {
+            let label = format!("Delete rule '{}'", app_data.rules()[&rule_to_remove].name);
+            if let Err(err) =
+                app_data.perform_update(label, vec![Update::DeleteRule(rule_to_remove)])
+            {
+                println!("Failed to delete rule: {:?}", err);
+            }
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("New Rule").clicked() {
+                self.rule_editor = Some(Default::default());
+            }
+            if ui.button("Dry run on unassigned transactions").clicked() {
+                self.dry_run = Some(Default::default());
+            }
+        });
+        ui.separator();
+        self.add_dry_run(ui, app_data);
+
+        let mut is_open = true;
+        let mut clicked_create = false;
+        if let Some(rule_editor) = &mut self.rule_editor {
+            let (title, button_text) = if rule_editor.id.is_some() {
+                ("Edit Rule", "Save")
+            } else {
+                ("New Rule", "Create")
+            };
+            let currency = Self::amount_currency(app_data, rule_editor.account_id);
+            Window::new(title)
+                .open(&mut is_open)
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    Grid::new("rule-editor-grid")
+                        .num_columns(2)
+                        .spacing([40.0, 4.0])
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("Name");
+                            let resp = ui.text_edit_singleline(&mut rule_editor.name);
+                            if rule_editor.autofocus {
+                                rule_editor.autofocus = false;
+                                ui.memory_mut(|m| m.request_focus(resp.id));
+                            }
+                            ui.end_row();
+
+                            ui.label("Description regex");
+                            ui.text_edit_singleline(&mut rule_editor.description_regex);
+                            ui.end_row();
+
+                            ui.label("Account");
+                            ui.add(AccountPicker::new(
+                                "rule-editor-account-picker",
+                                &mut rule_editor.account_id,
+                                true,
+                                app_data,
+                            ));
+                            ui.end_row();
+
+                            if let Some(currency) = currency {
+                                ui.label("Minimum amount");
+                                optional_input(
+                                    ui,
+                                    &mut rule_editor.min_amount,
+                                    Money::ZERO,
+                                    |ui, amount| {
+                                        ui.add(price_input(amount, currency));
+                                    },
+                                );
+                                ui.end_row();
+
+                                ui.label("Maximum amount");
+                                optional_input(
+                                    ui,
+                                    &mut rule_editor.max_amount,
+                                    Money::ZERO,
+                                    |ui, amount| {
+                                        ui.add(price_input(amount, currency));
+                                    },
+                                );
+                                ui.end_row();
+                            } else {
+                                ui.label("Amounts");
+                                ui.label("Choose an account to set amounts");
+                                ui.end_row();
+                            }
+
+                            let today = chrono::offset::Local::now().date_naive();
+                            ui.label("Start date");
+                            optional_input(ui, &mut rule_editor.start_date, today, |ui, date| {
+                                ui.add(date_input(date));
+                            });
+                            ui.end_row();
+
+                            ui.label("End date");
+                            optional_input(ui, &mut rule_editor.end_date, today, |ui, date| {
+                                ui.add(date_input(date));
+                            });
+                            ui.end_row();
+                        });
+
+                    ui.separator();
+                    let mut flow_to_remove = None;
+                    Grid::new("rule-editor-flows-grid")
+                        .num_columns(6)
+                        .spacing([20.0, 4.0])
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Category");
+                            ui.strong("Description");
+                            ui.strong("Fixed amount");
+                            ui.strong("Amortization");
+                            ui.strong("Length");
+                            ui.strong("Delete");
+                            ui.end_row();
+                            for (index, flow) in rule_editor.flows.iter_mut().enumerate() {
+                                ui.add(CategoryPicker::new(
+                                    &format!("rule-editor-flow-category-picker-{}", index),
+                                    &mut flow.category_id,
+                                    false,
+                                    &None,
+                                    app_data,
+                                ));
+                                ui.text_edit_singleline(&mut flow.description);
+                                match currency {
+                                    Some(currency) => {
+                                        optional_input(
+                                            ui,
+                                            &mut flow.amount,
+                                            Money::ZERO,
+                                            |ui, amount| {
+                                                ui.add(price_input(amount, currency));
+                                            },
+                                        );
+                                    }
+                                    None => {
+                                        ui.label("");
+                                    }
+                                }
+                                ui.add(AmortizationPicker::new(
+                                    &format!("rule-editor-flow-amortization-picker-{}", index),
+                                    &mut flow.amortization_type,
+                                ));
+                                if flow.amortization_type.is_some() {
+                                    let mut length = flow.amortization_length.unwrap_or(1);
+                                    ui.add(months_input(&mut length));
+                                    flow.amortization_length = Some(length);
+                                } else {
+                                    flow.amortization_length = None;
+                                    ui.label("");
+                                }
+                                if ui.button("Delete").clicked() {
+                                    flow_to_remove = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    if let Some(flow_to_remove) = flow_to_remove {
+                        rule_editor.flows.remove(flow_to_remove);
+                    }
+                    if ui.button("Add Flow").clicked() {
+                        rule_editor.flows.push(Default::default());
+                    }
+                    ui.label("A flow without a fixed amount receives the rest of the transaction.");
+
+                    ui.separator();
+                    let error = rule_editor.error();
+                    if ui
+                        .add_enabled(error.is_none(), Button::new(button_text))
+                        .clicked()
+                    {
+                        clicked_create = true;
+                    }
+                    if let Some(error) = error {
+                        ui.label(RichText::new(error).color(Color32::RED));
+                    }
+                });
+        }
+
+        if clicked_create {
+            let RuleEditor {
+                id,
+                name,
+                description_regex,
+                min_amount,
+                max_amount,
+                account_id,
+                start_date,
+                end_date,
+                flows,
+                autofocus: _,
+            } = self.rule_editor.take().unwrap();
+            let (id, label) = match id {
+                Some(id) => (id, format!("Edit rule '{}'", name)),
+                None => (next_id(app_data.rules()), format!("Create rule '{}'", name)),
+            };
+            if let Err(err) = app_data.perform_update(
+                label,
+                vec![Update::SetRule(Rule {
+                    id,
+                    name,
+                    description_regex: if !description_regex.is_empty() {
+                        Some(description_regex)
+                    } else {
+                        None
+                    },
+                    min_amount,
+                    max_amount,
+                    account_id,
+                    start_date,
+                    end_date,
+                    flows: flows
+                        .into_iter()
+                        .map(|flow| RuleFlow {
+                            category_id: flow.category_id.unwrap(),
+                            description: if !flow.description.is_empty() {
+                                Some(flow.description)
+                            } else {
+                                None
+                            },
+                            amount: flow.amount,
+                            amortization_type: flow.amortization_type,
+                            amortization_length: flow.amortization_length,
+                        })
+                        .collect(),
+                })],
+            ) {
+                println!("Failed to save rule: {:?}", err);
+            }
+        }
+
+        if !is_open || clicked_create {
+            self.rule_editor = None;
+        }
+    }
+}
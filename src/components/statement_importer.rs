@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+
+use chrono::naive::NaiveDate as Date;
+use egui::{Button, Color32, Context, Grid, RichText, Window};
+
+use super::rule_manager::invalid_rules_warning;
+use super::TransactionList;
+use crate::data::{
+    AppData, CachedValue, Dependency, ParsedStatement, Price, RuleEngine, StatementImport,
+};
+use crate::widgets::AccountPicker;
+
+pub struct StatementImporter {
+    parsed_statement: ParsedStatement,
+    account_id: Option<u32>,
+    statement_import: Option<StatementImport>,
+    included_ids: HashSet<u32>,
+    invalid_rule_ids: CachedValue<Vec<u32>>,
+}
+
+impl StatementImporter {
+    pub fn new(parsed_statement: ParsedStatement) -> Self {
+        Self {
+            parsed_statement,
+            account_id: None,
+            statement_import: None,
+            included_ids: HashSet::new(),
+            invalid_rule_ids: CachedValue::depending_on(&[Dependency::Rules]),
+        }
+    }
+
+    fn update_account(&mut self, app_data: &AppData) {
+        let account_id = match self.account_id {
+            Some(account_id) => account_id,
+            None => return,
+        };
+        let statement_import = match &mut self.statement_import {
+            Some(statement_import) => {
+                statement_import.set_account(app_data, account_id);
+                statement_import
+            }
+            None => self.statement_import.insert(StatementImport::new(
+                app_data,
+                account_id,
+                &self.parsed_statement,
+                chrono::offset::Local::now().date_naive(),
+            )),
+        };
+        // Likely duplicates are skipped unless the user ticks them.
+        self.included_ids = statement_import
+            .transactions
+            .iter()
+            .map(|transaction| transaction.id)
+            .filter(|id| !statement_import.duplicate_ids.contains(id))
+            .collect();
+    }
+
+    /// Returns whether the importer should remain open.
+    pub fn add(&mut self, ctx: &Context, app_data: &mut AppData) -> bool {
+        let mut is_open = true;
+        let mut clicked_import = false;
+        let mut clicked_discard = false;
+        Window::new("Import Statement")
+            .open(&mut is_open)
+            .collapsible(false)
+            .default_width(800.0)
+            .show(ctx, |ui| {
+                Grid::new("statement-importer-grid")
+                    .num_columns(2)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Period");
+                        ui.label(format!(
+                            "{} to {}",
+                            self.parsed_statement.start_date, self.parsed_statement.end_date
+                        ));
+                        ui.end_row();
+
+                        ui.label("Account");
+                        let old_account_id = self.account_id;
+                        ui.add(AccountPicker::new(
+                            "statement-importer-account-picker",
+                            &mut self.account_id,
+                            false,
+                            app_data,
+                        ));
+                        if old_account_id != self.account_id {
+                            self.update_account(app_data);
+                        }
+                        ui.end_row();
+                    });
+
+                let statement_import = match &mut self.statement_import {
+                    Some(statement_import) => statement_import,
+                    None => {
+                        ui.label("Choose an account to preview the transactions.");
+                        if ui.button("Discard").clicked() {
+                            clicked_discard = true;
+                        }
+                        return;
+                    }
+                };
+
+                if let Some(statement) = statement_import
+                    .repeated_statement_id
+                    .and_then(|id| app_data.statements().get(&id))
+                {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!(
+                            "This statement was already imported on {}.",
+                            statement.import_date
+                        ),
+                    );
+                }
+                if !statement_import.duplicate_ids.is_empty() {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!(
+                            "{} transactions look like duplicates of existing transactions and \
+                             will be skipped unless ticked.",
+                            statement_import.duplicate_ids.len()
+                        ),
+                    );
+                }
+
+                let invalid_rule_ids = self.invalid_rule_ids.get(app_data, |app_data| {
+                    RuleEngine::new(app_data).invalid_rule_ids
+                });
+                if let Some(warning) = invalid_rules_warning(app_data, invalid_rule_ids) {
+                    ui.colored_label(ui.visuals().warn_fg_color, warning);
+                }
+
+                ui.separator();
+                let currency = app_data
+                    .accounts()
+                    .get(&statement_import.statement.account_id)
+                    .and_then(|account| app_data.currencies().get(&account.currency_id));
+                let currency = match currency {
+                    Some(currency) => currency,
+                    None => {
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            "The account or its currency no longer exists. Choose another account.",
+                        );
+                        ui.horizontal(|ui| {
+                            ui.add_enabled(false, Button::new("Import"));
+                            if ui.button("Discard").clicked() {
+                                clicked_discard = true;
+                            }
+                        });
+                        return;
+                    }
+                };
+                Grid::new("statement-importer-balance-grid")
+                    .num_columns(3)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Date");
+                        ui.strong("Balance");
+                        ui.strong("Computed after import");
+                        ui.end_row();
+                        for (balance, computed) in
+                            statement_import.balance_checks(app_data, &self.included_ids)
+                        {
+                            let computed_text = match computed {
+                                Some(computed) => format!("{}", Price::new(computed, currency)),
+                                None => "Overflow".into(),
+                            };
+                            if balance.date < Date::MAX {
+                                ui.label(balance.date.to_string());
+                                ui.label(format!("{}", Price::new(balance.amount, currency)));
+                                let color = if computed == Some(balance.amount) {
+                                    Color32::GREEN
+                                } else {
+                                    Color32::RED
+                                };
+                                ui.label(RichText::new(computed_text).color(color));
+                            } else {
+                                ui.label("Latest");
+                                ui.label("");
+                                ui.label(computed_text);
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add(Button::new(format!(
+                            "Import {} transactions",
+                            self.included_ids.len()
+                        )))
+                        .clicked()
+                    {
+                        clicked_import = true;
+                    }
+                    if ui.button("Discard").clicked() {
+                        clicked_discard = true;
+                    }
+                });
+                ui.separator();
+
+                let response =
+                    TransactionList::new_of_transactions(&mut statement_import.transactions)
+                        .selection(&mut self.included_ids)
+                        .highlight(&statement_import.duplicate_ids)
+                        .add(ui, app_data);
+                if response.edited {
+                    // Transactions that become duplicates are unticked, and those that stop being
+                    // duplicates are ticked.
+                    let old_duplicate_ids = statement_import.duplicate_ids.clone();
+                    statement_import.find_duplicates(app_data);
+                    for id in statement_import
+                        .duplicate_ids
+                        .difference(&old_duplicate_ids)
+                    {
+                        self.included_ids.remove(id);
+                    }
+                    for id in old_duplicate_ids.difference(&statement_import.duplicate_ids) {
+                        self.included_ids.insert(*id);
+                    }
+                }
+            });
+
+        if clicked_import {
+            let statement_import = self.statement_import.as_ref().unwrap();
+            if let Err(err) = app_data.perform_update(
+                format!("Import {} transactions", self.included_ids.len()),
+                statement_import.updates(app_data, &self.included_ids),
+            ) {
+                println!("Failed to import statement: {:?}", err);
+                return true;
+            }
+        }
+
+        is_open && !clicked_import && !clicked_discard
+    }
+}
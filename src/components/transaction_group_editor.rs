@@ -0,0 +1,449 @@
+use std::collections::HashSet;
+
+use chrono::naive::NaiveDate as Date;
+use egui::{Button, Color32, Context, Grid, RichText, ScrollArea, Ui, Window};
+
+use crate::data::{
+    default_amortization, is_balanced, next_id, transaction_group_updates, unallocated_amounts,
+    AccrualPeriod, AmortizationType, AppData, Flow, Money, Price,
+};
+use crate::widgets::{
+    date_input, months_input, price_input, AmortizationPicker, CategoryPicker, CurrencyPicker,
+};
+
+struct FlowEditor {
+    id: Option<u32>,
+    category_id: Option<u32>,
+    date: Date,
+    description: String,
+    amount: Money,
+    currency_id: Option<u32>,
+    amortization_type: Option<AmortizationType>,
+    amortization_length: Option<i32>,
+}
+
+impl FlowEditor {
+    fn of_flow(flow: &Flow) -> Self {
+        Self {
+            id: Some(flow.id),
+            category_id: Some(flow.category_id),
+            date: flow.date,
+            description: flow.description.clone().unwrap_or_default(),
+            amount: flow.amount,
+            currency_id: Some(flow.currency_id),
+            amortization_type: flow.amortization_type,
+            amortization_length: flow.amortization_length,
+        }
+    }
+
+    /// Converts the editor into a flow, or `None` if it is missing a category or currency.
+    fn to_flow(&self, id: u32, transaction_group_id: u32) -> Option<Flow> {
+        Some(Flow {
+            id,
+            category_id: self.category_id?,
+            date: self.date,
+            description: if !self.description.is_empty() {
+                Some(self.description.clone())
+            } else {
+                None
+            },
+            amount: self.amount,
+            currency_id: self.currency_id?,
+            amortization_type: self.amortization_type,
+            amortization_length: self.amortization_length,
+            transaction_group_id,
+        })
+    }
+}
+
+pub struct TransactionGroupEditor {
+    id: Option<u32>,
+    /// Other groups whose transactions and flows were pulled into this one. They are deleted when
+    /// the group is saved.
+    merged_group_ids: Vec<u32>,
+    transaction_ids: Vec<u32>,
+    flows: Vec<FlowEditor>,
+    /// The index of the flow whose amortization schedule is shown.
+    preview_index: Option<usize>,
+    preview_period: AccrualPeriod,
+}
+
+impl TransactionGroupEditor {
+    /// Opens an editor for the selected transactions. If any of them already belong to groups,
+    /// those groups are combined into one.
+    pub fn of_selection(app_data: &AppData, selection: &HashSet<u32>) -> Self {
+        let mut editor = Self {
+            id: None,
+            merged_group_ids: Vec::new(),
+            transaction_ids: Vec::new(),
+            flows: Vec::new(),
+            preview_index: None,
+            preview_period: AccrualPeriod::Month,
+        };
+        let mut transaction_ids: Vec<u32> = selection.iter().copied().collect();
+        transaction_ids.sort();
+        editor.add_transactions(app_data, &transaction_ids);
+        editor
+    }
+
+    fn add_transactions(&mut self, app_data: &AppData, transaction_ids: &[u32]) {
+        for transaction_id in transaction_ids {
+            if self.transaction_ids.contains(transaction_id) {
+                continue;
+            }
+            let transaction = match app_data.transactions().get(transaction_id) {
+                Some(transaction) => transaction,
+                None => continue,
+            };
+            let transaction_group = transaction
+                .transaction_group_id
+                .and_then(|id| app_data.transaction_groups().get(&id));
+            match transaction_group {
+                Some(transaction_group)
+                    if Some(transaction_group.id) != self.id
+                        && !self.merged_group_ids.contains(&transaction_group.id) =>
+                {
+                    if self.id.is_none() {
+                        self.id = Some(transaction_group.id);
+                    } else {
+                        self.merged_group_ids.push(transaction_group.id);
+                    }
+                    for id in transaction_group.transaction_ids.iter() {
+                        if !self.transaction_ids.contains(id) {
+                            self.transaction_ids.push(*id);
+                        }
+                    }
+                    for flow_id in transaction_group.flow_ids.iter() {
+                        if let Some(flow) = app_data.flows().get(flow_id) {
+                            self.flows.push(FlowEditor::of_flow(flow));
+                        }
+                    }
+                }
+                _ => self.transaction_ids.push(*transaction_id),
+            }
+        }
+    }
+
+    fn new_flow(&self, app_data: &AppData) -> FlowEditor {
+        let transactions = self
+            .transaction_ids
+            .iter()
+            .filter_map(|id| app_data.transactions().get(id));
+        let date = transactions
+            .map(|transaction| transaction.date)
+            .min()
+            .unwrap_or_else(|| chrono::offset::Local::now().date_naive());
+        // Start the flow off with whatever hasn't been allocated yet.
+        let (currency_id, amount) = unallocated_amounts(
+            app_data,
+            &self.transaction_ids,
+            &self.flows_with_placeholder_ids(),
+        )
+        .into_iter()
+        .find_map(|(id, amount)| {
+            amount
+                .filter(|amount| *amount != Money::ZERO)
+                .map(|amount| (id, amount))
+        })
+        .map_or(
+            (app_data.currencies().keys().next().copied(), Money::ZERO),
+            |(id, amount)| (Some(id), amount),
+        );
+        FlowEditor {
+            id: None,
+            category_id: None,
+            date,
+            description: Default::default(),
+            amount,
+            currency_id,
+            amortization_type: None,
+            amortization_length: None,
+        }
+    }
+
+    /// The complete flows, with ids that are only good enough for computing totals.
+    fn flows_with_placeholder_ids(&self) -> Vec<Flow> {
+        self.flows
+            .iter()
+            .filter_map(|flow| flow.to_flow(flow.id.unwrap_or(0), 0))
+            .collect()
+    }
+
+    fn show_preview(&mut self, ui: &mut Ui, app_data: &AppData) {
+        let flow = match self
+            .preview_index
+            .and_then(|index| self.flows.get(index))
+            .and_then(|flow| flow.to_flow(0, 0))
+        {
+            Some(flow) if flow.amortization_type.is_some() => flow,
+            _ => return,
+        };
+        let currency = match app_data.currencies().get(&flow.currency_id) {
+            Some(currency) => currency,
+            None => return,
+        };
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.strong("Schedule");
+            ui.selectable_value(&mut self.preview_period, AccrualPeriod::Month, "Monthly");
+            ui.selectable_value(&mut self.preview_period, AccrualPeriod::Day, "Daily");
+            if ui.small_button("Hide").clicked() {
+                self.preview_index = None;
+            }
+        });
+        ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+            Grid::new("transaction-group-editor-schedule-grid")
+                .num_columns(2)
+                .spacing([40.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    for (date, amount) in flow.accrued_amounts(self.preview_period) {
+                        ui.label(date.to_string());
+                        ui.label(format!("{}", Price::new(amount, currency)));
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+
+    /// Returns whether the editor should remain open. Transactions in `selection` can be added to
+    /// the group.
+    pub fn add(&mut self, ctx: &Context, app_data: &mut AppData, selection: &HashSet<u32>) -> bool {
+        // The transactions can be deleted while the editor is open.
+        self.transaction_ids
+            .retain(|id| app_data.transactions().contains_key(id));
+        let mut is_open = true;
+        let mut clicked_save = false;
+        let title = if self.id.is_some() {
+            "Edit Transaction Group"
+        } else {
+            "New Transaction Group"
+        };
+        Window::new(title)
+            .open(&mut is_open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.strong("Transactions");
+                let mut transaction_to_remove = None;
+                Grid::new("transaction-group-editor-transactions-grid")
+                    .num_columns(5)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Date");
+                        ui.strong("Account");
+                        ui.strong("Description");
+                        ui.strong("Amount");
+                        ui.strong("Remove");
+                        ui.end_row();
+                        for (index, transaction_id) in self.transaction_ids.iter().enumerate() {
+                            let transaction = &app_data.transactions()[transaction_id];
+                            let (account, currency) = match app_data
+                                .accounts()
+                                .get(&transaction.account_id)
+                                .and_then(|account| {
+                                    Some((
+                                        account,
+                                        app_data.currencies().get(&account.currency_id)?,
+                                    ))
+                                }) {
+                                Some(account_and_currency) => account_and_currency,
+                                None => continue,
+                            };
+                            ui.label(transaction.date.to_string());
+                            ui.label(&account.name);
+                            ui.label(&transaction.description);
+                            ui.label(format!("{}", Price::new(transaction.amount, currency)));
+                            if ui.button("Remove").clicked() {
+                                transaction_to_remove = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(transaction_to_remove) = transaction_to_remove {
+                    self.transaction_ids.remove(transaction_to_remove);
+                }
+                if ui
+                    .add_enabled(
+                        selection
+                            .iter()
+                            .any(|id| !self.transaction_ids.contains(id)),
+                        Button::new("Add selected transactions"),
+                    )
+                    .clicked()
+                {
+                    let mut transaction_ids: Vec<u32> = selection.iter().copied().collect();
+                    transaction_ids.sort();
+                    self.add_transactions(app_data, &transaction_ids);
+                }
+
+                ui.separator();
+                ui.strong("Flows");
+                let mut flow_to_remove = None;
+                Grid::new("transaction-group-editor-flows-grid")
+                    .num_columns(9)
+                    .spacing([20.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Category");
+                        ui.strong("Date");
+                        ui.strong("Description");
+                        ui.strong("Amount");
+                        ui.strong("Currency");
+                        ui.strong("Amortization");
+                        ui.strong("Months");
+                        ui.strong("Schedule");
+                        ui.strong("Delete");
+                        ui.end_row();
+                        for (index, flow) in self.flows.iter_mut().enumerate() {
+                            let old_category_id = flow.category_id;
+                            ui.add(CategoryPicker::new(
+                                &format!("transaction-group-editor-category-picker-{}", index),
+                                &mut flow.category_id,
+                                false,
+                                &None,
+                                app_data,
+                            ));
+                            // New flows start with the amortization of their category.
+                            if let (None, Some(category_id)) = (flow.id, flow.category_id) {
+                                if flow.category_id != old_category_id {
+                                    (flow.amortization_type, flow.amortization_length) =
+                                        default_amortization(app_data, category_id);
+                                }
+                            }
+                            ui.add(date_input(&mut flow.date));
+                            ui.text_edit_singleline(&mut flow.description);
+                            match flow
+                                .currency_id
+                                .and_then(|id| app_data.currencies().get(&id))
+                            {
+                                Some(currency) => {
+                                    ui.add(price_input(&mut flow.amount, currency));
+                                }
+                                None => {
+                                    ui.label("");
+                                }
+                            }
+                            ui.add(CurrencyPicker::new(
+                                &format!("transaction-group-editor-currency-picker-{}", index),
+                                &mut flow.currency_id,
+                                false,
+                                app_data,
+                            ));
+                            ui.add(AmortizationPicker::new(
+                                &format!("transaction-group-editor-amortization-picker-{}", index),
+                                &mut flow.amortization_type,
+                            ));
+                            if flow.amortization_type.is_some() {
+                                let mut length = flow.amortization_length.unwrap_or(1);
+                                ui.add(months_input(&mut length));
+                                flow.amortization_length = Some(length);
+                            } else {
+                                flow.amortization_length = None;
+                                ui.label("");
+                            }
+                            if ui
+                                .add_enabled(
+                                    flow.amortization_type.is_some(),
+                                    Button::new("Preview"),
+                                )
+                                .clicked()
+                            {
+                                self.preview_index = Some(index);
+                            }
+                            if ui.button("Delete").clicked() {
+                                flow_to_remove = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(flow_to_remove) = flow_to_remove {
+                    self.flows.remove(flow_to_remove);
+                    self.preview_index = None;
+                }
+                self.show_preview(ui, app_data);
+                if ui.button("Add Flow").clicked() {
+                    let flow = self.new_flow(app_data);
+                    self.flows.push(flow);
+                }
+
+                ui.separator();
+                let flows_complete = self
+                    .flows
+                    .iter()
+                    .all(|flow| flow.category_id.is_some() && flow.currency_id.is_some());
+                let flows = self.flows_with_placeholder_ids();
+                for (currency_id, amount) in
+                    unallocated_amounts(app_data, &self.transaction_ids, &flows)
+                {
+                    let currency = match app_data.currencies().get(&currency_id) {
+                        Some(currency) => currency,
+                        None => continue,
+                    };
+                    let color = if amount == Some(Money::ZERO) {
+                        Color32::GREEN
+                    } else {
+                        Color32::RED
+                    };
+                    let amount = match amount {
+                        Some(amount) => format!("{}", Price::new(amount, currency)),
+                        None => "Overflow".into(),
+                    };
+                    ui.label(RichText::new(format!("Unallocated: {}", amount)).color(color));
+                }
+                let is_ok = flows_complete
+                    && is_balanced(app_data, &self.transaction_ids, &flows)
+                    && (!self.transaction_ids.is_empty() || self.id.is_some());
+                let button_text = if !self.transaction_ids.is_empty() {
+                    "Save"
+                } else {
+                    "Delete group"
+                };
+                if ui.add_enabled(is_ok, Button::new(button_text)).clicked() {
+                    clicked_save = true;
+                }
+                if !flows_complete {
+                    ui.label(
+                        RichText::new("Every flow needs a category and currency")
+                            .color(Color32::RED),
+                    );
+                }
+            });
+
+        if clicked_save {
+            let (group_id, label) = match self.id {
+                Some(id) => (id, "Edit transaction group"),
+                None => (next_id(app_data.transaction_groups()), "Group transactions"),
+            };
+            let mut next_flow_id = next_id(app_data.flows());
+            let flows = self
+                .flows
+                .iter()
+                .map(|flow| {
+                    let id = match flow.id {
+                        Some(id) => id,
+                        None => {
+                            next_flow_id += 1;
+                            next_flow_id - 1
+                        }
+                    };
+                    flow.to_flow(id, group_id).unwrap()
+                })
+                .collect();
+            if let Err(err) = app_data.perform_update(
+                label,
+                transaction_group_updates(
+                    app_data,
+                    group_id,
+                    &self.merged_group_ids,
+                    &self.transaction_ids,
+                    flows,
+                ),
+            ) {
+                println!("Failed to save transaction group: {:?}", err);
+            }
+        }
+
+        is_open && !clicked_save
+    }
+}
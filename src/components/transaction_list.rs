@@ -1,14 +1,15 @@
 use std::collections::HashSet;
 
-use egui::{FontSelection, Label, Ui};
-use egui_extras::{Column, TableBuilder};
+use egui::{FontSelection, Label, RichText, Sense, TextEdit, Ui};
+use egui_extras::{Column, TableBuilder, TableRow};
 
 use crate::data::{AppData, Price, Transaction};
+use crate::widgets::{date_input, price_input};
 
 enum TransactionsSource<'a> {
     Ids(&'a Vec<u32>),
-    #[allow(dead_code)]
-    Transactions(&'a Vec<Transaction>),
+    /// Transactions that are not (yet) part of `AppData`, and so may be edited in place.
+    Transactions(&'a mut Vec<Transaction>),
 }
 
 impl TransactionsSource<'_> {
@@ -29,9 +30,18 @@ impl TransactionsSource<'_> {
     }
 }
 
+pub struct TransactionListResponse {
+    /// The transaction whose row was double-clicked. Only rows of transactions in `AppData` can be
+    /// double-clicked, since the others are edited in place.
+    pub double_clicked_id: Option<u32>,
+    /// Whether the date, description or amount of a transaction edited in place changed.
+    pub edited: bool,
+}
+
 pub struct TransactionList<'a> {
     transactions: TransactionsSource<'a>,
     selection: Option<&'a mut HashSet<u32>>,
+    highlight: Option<&'a HashSet<u32>>,
 }
 
 impl<'a> TransactionList<'a> {
@@ -39,14 +49,15 @@ impl<'a> TransactionList<'a> {
         Self {
             transactions: TransactionsSource::Ids(transaction_ids),
             selection: None,
+            highlight: None,
         }
     }
 
-    #[allow(dead_code)]
-    pub fn new_of_transactions(transactions: &'a Vec<Transaction>) -> Self {
+    pub fn new_of_transactions(transactions: &'a mut Vec<Transaction>) -> Self {
         Self {
             transactions: TransactionsSource::Transactions(transactions),
             selection: None,
+            highlight: None,
         }
     }
 
@@ -57,8 +68,19 @@ impl<'a> TransactionList<'a> {
         }
     }
 
-    pub fn add(mut self, ui: &mut Ui, app_data: &AppData) {
+    /// Draws the descriptions of the transactions in `highlight` in the warning color.
+    pub fn highlight(self, highlight: &'a HashSet<u32>) -> Self {
+        Self {
+            highlight: Some(highlight),
+            ..self
+        }
+    }
+
+    pub fn add(mut self, ui: &mut Ui, app_data: &AppData) -> TransactionListResponse {
+        let mut double_clicked_id = None;
+        let mut edited = false;
         let row_height = FontSelection::Default.resolve(ui.style()).size + 6.0;
+        let warn_color = ui.visuals().warn_fg_color;
         let builder = TableBuilder::new(ui)
             .striped(true)
             .cell_layout(egui::Layout::left_to_right(egui::Align::Center));
@@ -77,24 +99,21 @@ impl<'a> TransactionList<'a> {
                     transactions,
                     ..
                 } = &mut self;
-                match selection {
-                    Some(selection) => {
-                        header.col(|ui| {
-                            let all_selected = selection.len() == transactions.len();
-                            let mut all_checked = all_selected;
-                            ui.checkbox(&mut all_checked, "");
-                            if all_checked != all_selected {
-                                if all_checked {
-                                    transactions.iter_ids().for_each(|id| {
-                                        selection.insert(*id);
-                                    });
-                                } else {
-                                    selection.clear();
-                                }
+                if let Some(selection) = selection {
+                    header.col(|ui| {
+                        let all_selected = selection.len() == transactions.len();
+                        let mut all_checked = all_selected;
+                        ui.checkbox(&mut all_checked, "");
+                        if all_checked != all_selected {
+                            if all_checked {
+                                transactions.iter_ids().for_each(|id| {
+                                    selection.insert(*id);
+                                });
+                            } else {
+                                selection.clear();
                             }
-                        });
-                    }
-                    None => (),
+                        }
+                    });
                 }
                 header.col(|ui| {
                     ui.strong("Date");
@@ -119,39 +138,109 @@ impl<'a> TransactionList<'a> {
                     };
                     let account = app_data.accounts().get(&transaction.account_id).unwrap();
                     let currency = app_data.currencies().get(&account.currency_id).unwrap();
-                    match &mut self.selection {
-                        Some(selection) => {
-                            let row_selected = selection.contains(&transaction.id);
-                            let mut checked = row_selected;
+                    let description_color = match self.highlight {
+                        Some(highlight) if highlight.contains(&transaction.id) => Some(warn_color),
+                        _ => None,
+                    };
+                    if let Some(selection) = &mut self.selection {
+                        let row_selected = selection.contains(&transaction.id);
+                        let mut checked = row_selected;
+                        row.col(|ui| {
+                            ui.checkbox(&mut checked, "");
+                        });
+                        if row_selected != checked {
+                            if checked {
+                                (*selection).insert(transaction.id);
+                            } else {
+                                (*selection).remove(&transaction.id);
+                            }
+                        }
+                    }
+                    // Transactions that aren't part of `AppData` are edited on a copy, which is
+                    // written back once the row has been drawn.
+                    let mut edited_transaction = match &self.transactions {
+                        TransactionsSource::Ids(_) => None,
+                        TransactionsSource::Transactions(_) => Some(transaction.clone()),
+                    };
+                    match &mut edited_transaction {
+                        Some(edited_transaction) => {
                             row.col(|ui| {
-                                ui.checkbox(&mut checked, "");
+                                ui.add(date_input(&mut edited_transaction.date));
                             });
-                            if row_selected != checked {
-                                if checked {
-                                    (*selection).insert(transaction.id);
-                                } else {
-                                    (*selection).remove(&transaction.id);
-                                }
+                            row.col(|ui| {
+                                ui.add(Label::new(&account.name).wrap(false));
+                            });
+                            row.col(|ui| {
+                                ui.add(
+                                    TextEdit::singleline(&mut edited_transaction.description)
+                                        .text_color_opt(description_color)
+                                        .desired_width(f32::INFINITY),
+                                );
+                            });
+                            row.col(|ui| {
+                                ui.add(price_input(&mut edited_transaction.amount, currency));
+                            });
+                        }
+                        None => {
+                            let mut double_clicked = false;
+                            let mut add_label = |row: &mut TableRow, text: RichText| {
+                                row.col(|ui| {
+                                    if ui
+                                        .add(Label::new(text).wrap(false).sense(Sense::click()))
+                                        .double_clicked()
+                                    {
+                                        double_clicked = true;
+                                    }
+                                });
+                            };
+                            add_label(&mut row, RichText::new(transaction.date.to_string()));
+                            add_label(&mut row, RichText::new(&account.name));
+                            let mut description = RichText::new(&transaction.description);
+                            if let Some(description_color) = description_color {
+                                description = description.color(description_color);
+                            }
+                            add_label(&mut row, description);
+                            let mut amount =
+                                format!("{}", Price::new(transaction.amount, currency));
+                            if let Some((original, original_currency)) =
+                                transaction.original.and_then(|original| {
+                                    app_data
+                                        .currencies()
+                                        .get(&original.currency_id)
+                                        .map(|original_currency| (original, original_currency))
+                                })
+                            {
+                                amount = format!(
+                                    "{} ({})",
+                                    amount,
+                                    Price::new(original.amount, original_currency)
+                                );
+                            }
+                            add_label(&mut row, RichText::new(amount));
+                            if double_clicked {
+                                double_clicked_id = Some(transaction.id);
                             }
                         }
-                        None => (),
                     }
-                    row.col(|ui| {
-                        ui.add(Label::new(&transaction.date.to_string()).wrap(false));
-                    });
-                    row.col(|ui| {
-                        ui.add(Label::new(&account.name).wrap(false));
-                    });
-                    row.col(|ui| {
-                        ui.add(Label::new(&transaction.description).wrap(false));
-                    });
-                    row.col(|ui| {
-                        ui.add(
-                            Label::new(format!("{}", Price::new(transaction.amount, currency)))
-                                .wrap(false),
-                        );
-                    });
+                    if let (
+                        Some(edited_transaction),
+                        TransactionsSource::Transactions(transactions),
+                    ) = (edited_transaction, &mut self.transactions)
+                    {
+                        let transaction = &transactions[row_index];
+                        if edited_transaction.date != transaction.date
+                            || edited_transaction.description != transaction.description
+                            || edited_transaction.amount != transaction.amount
+                        {
+                            edited = true;
+                        }
+                        transactions[row_index] = edited_transaction;
+                    }
                 });
             });
+        TransactionListResponse {
+            double_clicked_id,
+            edited,
+        }
     }
 }
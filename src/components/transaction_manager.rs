@@ -0,0 +1,419 @@
+use std::collections::HashSet;
+
+use chrono::naive::NaiveDate as Date;
+use egui::{Button, Color32, Context, Grid, RichText, Ui, Window};
+
+use super::{ErrorDialog, TransactionGroupEditor, TransactionList, TransferMatcher};
+use crate::data::{
+    delete_transactions_updates, link_transfer_updates, net_worth_change, next_id, transfer_fee,
+    transfer_rate, unlink_transfer_updates, AppData, ForeignAmount, Money, Price, Transaction,
+    Update,
+};
+use crate::widgets::{date_input, price_input, AccountPicker, CurrencyPicker};
+
+struct TransactionEditor {
+    id: Option<u32>,
+    account_id: Option<u32>,
+    date: Date,
+    description: String,
+    amount: Money,
+    original_amount: Money,
+    original_currency_id: Option<u32>,
+    autofocus: bool,
+    /// Why the transaction couldn't be saved, if it couldn't.
+    error: Option<String>,
+}
+
+impl Default for TransactionEditor {
+    fn default() -> Self {
+        Self {
+            id: None,
+            account_id: None,
+            date: chrono::offset::Local::now().date_naive(),
+            description: Default::default(),
+            amount: Money::ZERO,
+            original_amount: Money::ZERO,
+            original_currency_id: None,
+            autofocus: true,
+            error: None,
+        }
+    }
+}
+
+impl TransactionEditor {
+    fn of_transaction(transaction: &Transaction) -> Self {
+        Self {
+            id: Some(transaction.id),
+            account_id: Some(transaction.account_id),
+            date: transaction.date,
+            description: transaction.description.clone(),
+            amount: transaction.amount,
+            original_amount: transaction
+                .original
+                .map_or(Money::ZERO, |original| original.amount),
+            original_currency_id: transaction.original.map(|original| original.currency_id),
+            autofocus: true,
+            error: None,
+        }
+    }
+
+    /// The transaction being edited as it is now, which is `None` for a new transaction or one
+    /// that has been deleted since the editor was opened.
+    fn current<'a>(&self, app_data: &'a AppData) -> Option<&'a Transaction> {
+        self.id.and_then(|id| app_data.transactions().get(&id))
+    }
+
+    fn is_deleted(&self, app_data: &AppData) -> bool {
+        self.id.is_some() && self.current(app_data).is_none()
+    }
+
+    /// Converts the editor into a transaction. Its group, statement and transfer aren't edited
+    /// here, so they are taken from the transaction as it is now, since they may have changed
+    /// while the editor was open.
+    fn to_transaction(&self, app_data: &AppData, id: u32, account_id: u32) -> Transaction {
+        let current = self.current(app_data);
+        Transaction {
+            id,
+            account_id,
+            date: self.date,
+            description: self.description.clone(),
+            amount: self.amount,
+            transaction_group_id: current.and_then(|current| current.transaction_group_id),
+            statement_id: current.and_then(|current| current.statement_id),
+            original: self.original_currency_id.map(|currency_id| ForeignAmount {
+                amount: self.original_amount,
+                currency_id,
+            }),
+            transfer_transaction_id: current.and_then(|current| current.transfer_transaction_id),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TransactionManager {
+    transaction_editor: Option<TransactionEditor>,
+    transaction_group_editor: Option<TransactionGroupEditor>,
+    transfer_matcher: Option<TransferMatcher>,
+    error_dialog: Option<ErrorDialog>,
+}
+
+impl TransactionManager {
+    pub fn add(
+        &mut self,
+        ui: &mut Ui,
+        ctx: &Context,
+        app_data: &mut AppData,
+        selection: &mut HashSet<u32>,
+    ) {
+        let mut clicked_delete = false;
+        let mut transfer_updates = Vec::new();
+        let mut transfer_label = "";
+        ui.horizontal(|ui| {
+            if ui.button("New Transaction").clicked() {
+                self.transaction_editor = Some(Default::default());
+            }
+            if ui
+                .add_enabled(!selection.is_empty(), Button::new("Delete selected"))
+                .clicked()
+            {
+                clicked_delete = true;
+            }
+            if ui
+                .add_enabled(
+                    self.transaction_group_editor.is_none() && !selection.is_empty(),
+                    Button::new("Group selected"),
+                )
+                .clicked()
+            {
+                self.transaction_group_editor =
+                    Some(TransactionGroupEditor::of_selection(app_data, selection));
+            }
+            let selected: Vec<&Transaction> = selection
+                .iter()
+                .filter_map(|id| app_data.transactions().get(id))
+                .collect();
+            let can_link = selected.len() == 2 && selected[0].account_id != selected[1].account_id;
+            if ui
+                .add_enabled(can_link, Button::new("Link as transfer"))
+                .clicked()
+            {
+                transfer_label = "Link transfer";
+                transfer_updates = link_transfer_updates(app_data, selected[0].id, selected[1].id);
+            }
+            let can_unlink = selected
+                .iter()
+                .any(|transaction| transaction.transfer_transaction_id.is_some());
+            if ui
+                .add_enabled(can_unlink, Button::new("Unlink transfer"))
+                .clicked()
+            {
+                transfer_label = "Unlink transfers";
+                // Both sides of a transfer may be selected, but it should only be unlinked once.
+                let mut unlinked_ids = HashSet::new();
+                for transaction in selected.iter() {
+                    if unlinked_ids.insert(transaction.id) {
+                        unlinked_ids.extend(transaction.transfer_transaction_id);
+                        transfer_updates.extend(unlink_transfer_updates(app_data, transaction.id));
+                    }
+                }
+            }
+            if ui
+                .add_enabled(
+                    self.transfer_matcher.is_none(),
+                    Button::new("Find transfers"),
+                )
+                .clicked()
+            {
+                self.transfer_matcher = Some(Default::default());
+            }
+        });
+        if !transfer_updates.is_empty() {
+            if let Err(err) = app_data.perform_update(transfer_label, transfer_updates) {
+                println!("Failed to update transfers: {:?}", err);
+                self.error_dialog = Some(ErrorDialog::new(
+                    "Could Not Update Transfers",
+                    format!("{} failed: {}", transfer_label, err),
+                ));
+            }
+        }
+        if let Some(transfer_matcher) = &mut self.transfer_matcher {
+            if !transfer_matcher.add(ctx, app_data) {
+                self.transfer_matcher = None;
+            }
+        }
+        if clicked_delete {
+            let mut transaction_ids: Vec<u32> = selection.iter().copied().collect();
+            transaction_ids.sort();
+            match app_data.perform_update(
+                format!("Delete {} transactions", transaction_ids.len()),
+                delete_transactions_updates(app_data, &transaction_ids),
+            ) {
+                Ok(()) => selection.clear(),
+                Err(err) => {
+                    println!("Failed to delete transactions: {:?}", err);
+                    self.error_dialog = Some(ErrorDialog::new(
+                        "Could Not Delete Transactions",
+                        format!(
+                            "The {} selected transactions couldn't be deleted: {}",
+                            transaction_ids.len(),
+                            err
+                        ),
+                    ));
+                }
+            }
+        }
+        if let Some(error_dialog) = &mut self.error_dialog {
+            if !error_dialog.add(ctx) {
+                self.error_dialog = None;
+            }
+        }
+
+        let response = TransactionList::new(&app_data.transactions().keys().copied().collect())
+            .selection(selection)
+            .add(ui, app_data);
+        if let Some(id) = response.double_clicked_id {
+            if self.transaction_editor.is_none() {
+                self.transaction_editor = Some(TransactionEditor::of_transaction(
+                    app_data.transactions().get(&id).unwrap(),
+                ));
+            }
+        }
+
+        if let Some(transaction_group_editor) = &mut self.transaction_group_editor {
+            if !transaction_group_editor.add(ctx, app_data, selection) {
+                self.transaction_group_editor = None;
+            }
+        }
+
+        let mut is_open = true;
+        let mut clicked_create = false;
+        if let Some(transaction_editor) = &mut self.transaction_editor {
+            let (title, button_text) = if transaction_editor.id.is_some() {
+                ("Edit Transaction", "Save")
+            } else {
+                ("New Transaction", "Create")
+            };
+            Window::new(title)
+                .open(&mut is_open)
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    Grid::new("transaction-editor-grid")
+                        .num_columns(2)
+                        .spacing([40.0, 4.0])
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("Account");
+                            ui.add(AccountPicker::new(
+                                "transaction-editor-account-picker",
+                                &mut transaction_editor.account_id,
+                                false,
+                                app_data,
+                            ));
+                            ui.end_row();
+
+                            ui.label("Date");
+                            ui.add(date_input(&mut transaction_editor.date));
+                            ui.end_row();
+
+                            ui.label("Description");
+                            let resp = ui.text_edit_singleline(&mut transaction_editor.description);
+                            if transaction_editor.autofocus {
+                                transaction_editor.autofocus = false;
+                                ui.memory_mut(|m| m.request_focus(resp.id));
+                            }
+                            ui.end_row();
+
+                            if let Some(account) = transaction_editor
+                                .account_id
+                                .and_then(|id| app_data.accounts().get(&id))
+                            {
+                                let currency =
+                                    app_data.currencies().get(&account.currency_id).unwrap();
+                                ui.label("Amount");
+                                ui.add(price_input(&mut transaction_editor.amount, currency));
+                                ui.end_row();
+
+                                ui.label("Original currency");
+                                ui.add(CurrencyPicker::new(
+                                    "transaction-editor-original-currency-picker",
+                                    &mut transaction_editor.original_currency_id,
+                                    true,
+                                    app_data,
+                                ));
+                                ui.end_row();
+
+                                if let Some(original_currency) = transaction_editor
+                                    .original_currency_id
+                                    .and_then(|id| app_data.currencies().get(&id))
+                                {
+                                    ui.label("Original amount");
+                                    ui.add(price_input(
+                                        &mut transaction_editor.original_amount,
+                                        original_currency,
+                                    ));
+                                    ui.end_row();
+
+                                    let transaction =
+                                        transaction_editor.to_transaction(app_data, 0, account.id);
+                                    if let Some(rate) = transaction.implied_rate(app_data) {
+                                        ui.label("Implied rate");
+                                        ui.label(format!(
+                                            "1 {} = {:.6} {}",
+                                            original_currency.code, rate, currency.code
+                                        ));
+                                        ui.end_row();
+                                    }
+                                    if let Some(fx_fee) = transaction.fx_fee(app_data) {
+                                        ui.label("Exchange fee");
+                                        ui.label(format!("{}", Price::new(fx_fee, currency)));
+                                        ui.end_row();
+                                    }
+                                }
+
+                                if let Some(other) = transaction_editor
+                                    .current(app_data)
+                                    .and_then(|current| current.transfer_transaction_id)
+                                    .and_then(|id| app_data.transactions().get(&id))
+                                {
+                                    let other_account =
+                                        app_data.accounts().get(&other.account_id).unwrap();
+                                    let other_currency =
+                                        app_data.account_currency(other.account_id).unwrap();
+                                    ui.label("Transfer with");
+                                    ui.label(format!(
+                                        "{} on {}: {}",
+                                        other_account.name,
+                                        other.date,
+                                        Price::new(other.amount, other_currency)
+                                    ));
+                                    ui.end_row();
+
+                                    let transaction =
+                                        transaction_editor.to_transaction(app_data, 0, account.id);
+                                    let (from_currency, to_currency) =
+                                        if net_worth_change(app_data, &transaction)
+                                            .is_some_and(|amount| amount.is_negative())
+                                        {
+                                            (currency, other_currency)
+                                        } else {
+                                            (other_currency, currency)
+                                        };
+                                    if let Some(rate) = transfer_rate(app_data, &transaction) {
+                                        ui.label("Realized rate");
+                                        ui.label(format!(
+                                            "1 {} = {:.6} {}",
+                                            from_currency.code, rate, to_currency.code
+                                        ));
+                                        ui.end_row();
+                                    }
+                                    if let Some(fee) = transfer_fee(app_data, &transaction) {
+                                        ui.label("Transfer fee");
+                                        ui.label(format!("{}", Price::new(fee, from_currency)));
+                                        ui.end_row();
+                                    }
+                                }
+                            }
+                        });
+                    if transaction_editor
+                        .current(app_data)
+                        .is_some_and(|current| current.transaction_group_id.is_some())
+                    {
+                        ui.label(
+                            RichText::new(
+                                "This transaction is in a group; changing its amount will \
+                                 unbalance the group's flows.",
+                            )
+                            .color(Color32::YELLOW),
+                        );
+                    }
+                    let is_deleted = transaction_editor.is_deleted(app_data);
+                    if is_deleted {
+                        ui.label(
+                            RichText::new("This transaction has been deleted.").color(Color32::RED),
+                        );
+                    }
+                    if let Some(error) = &transaction_editor.error {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    }
+                    let is_ok = transaction_editor.account_id.is_some() && !is_deleted;
+                    if ui.add_enabled(is_ok, Button::new(button_text)).clicked() {
+                        clicked_create = true;
+                    }
+                });
+        }
+
+        if clicked_create {
+            let transaction_editor = self.transaction_editor.as_mut().unwrap();
+            if transaction_editor.is_deleted(app_data) {
+                return;
+            }
+            let (id, label) = match transaction_editor.id {
+                Some(id) => (
+                    id,
+                    format!("Edit transaction '{}'", transaction_editor.description),
+                ),
+                None => (
+                    next_id(app_data.transactions()),
+                    format!("Create transaction '{}'", transaction_editor.description),
+                ),
+            };
+            let transaction = transaction_editor.to_transaction(
+                app_data,
+                id,
+                transaction_editor.account_id.unwrap(),
+            );
+            if let Err(err) =
+                app_data.perform_update(label, vec![Update::SetTransaction(transaction)])
+            {
+                println!("Failed to save transaction: {:?}", err);
+                // The editor stays open, so that the edits aren't lost.
+                transaction_editor.error = Some(format!("Failed to save transaction: {}", err));
+                clicked_create = false;
+            }
+        }
+
+        if !is_open || clicked_create {
+            self.transaction_editor = None;
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use egui::{Context, Grid, ScrollArea, Window};
+
+use crate::data::{
+    link_transfer_updates, suggest_transfers, AppData, CachedValue, Dependency, Price,
+    TransferSuggestion, Update,
+};
+use crate::widgets::stringable_input;
+
+pub struct TransferMatcher {
+    max_days: u64,
+    suggestions: CachedValue<Vec<TransferSuggestion>>,
+}
+
+impl Default for TransferMatcher {
+    fn default() -> Self {
+        Self {
+            max_days: 5,
+            suggestions: CachedValue::depending_on(&[
+                Dependency::Accounts,
+                Dependency::Currencies,
+                Dependency::Transactions,
+            ]),
+        }
+    }
+}
+
+impl TransferMatcher {
+    /// Shows the suggested transfers in a window. Returns whether the window is still open.
+    pub fn add(&mut self, ctx: &Context, app_data: &mut AppData) -> bool {
+        let mut is_open = true;
+        let mut links: Vec<TransferSuggestion> = Vec::new();
+        Window::new("Find Transfers")
+            .open(&mut is_open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Days apart");
+                    let old_max_days = self.max_days;
+                    ui.add(stringable_input(&mut self.max_days));
+                    if self.max_days != old_max_days {
+                        self.suggestions.invalidate();
+                    }
+                });
+                let max_days = self.max_days;
+                let suggestions = self.suggestions.get(app_data, |app_data: &AppData| {
+                    suggest_transfers(app_data, max_days)
+                });
+                if suggestions.is_empty() {
+                    ui.label("No transfers found");
+                    return;
+                }
+
+                ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    Grid::new("transfer-matcher-grid")
+                        .num_columns(7)
+                        .spacing([20.0, 4.0])
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Date");
+                            ui.strong("From");
+                            ui.strong("Amount");
+                            ui.strong("Date");
+                            ui.strong("To");
+                            ui.strong("Amount");
+                            ui.strong("Link");
+                            ui.end_row();
+                            for suggestion in suggestions.iter() {
+                                for id in [suggestion.from_id, suggestion.to_id] {
+                                    let transaction = app_data.transactions().get(&id).unwrap();
+                                    let account =
+                                        app_data.accounts().get(&transaction.account_id).unwrap();
+                                    let currency = app_data.account_currency(account.id).unwrap();
+                                    ui.label(transaction.date.to_string());
+                                    ui.label(&account.name);
+                                    ui.label(format!(
+                                        "{}",
+                                        Price::new(transaction.amount, currency)
+                                    ));
+                                }
+                                if ui.button("Link").clicked() {
+                                    links.push(*suggestion);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+                if ui.button("Link all").clicked() {
+                    links = suggestions.clone();
+                }
+            });
+
+        if !links.is_empty() {
+            let updates: Vec<Update> = links
+                .iter()
+                .flat_map(|link| link_transfer_updates(app_data, link.from_id, link.to_id))
+                .collect();
+            if let Err(err) =
+                app_data.perform_update(format!("Link {} transfers", links.len()), updates)
+            {
+                println!("Failed to link transfers: {:?}", err);
+            }
+        }
+        is_open
+    }
+}
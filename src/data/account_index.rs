@@ -0,0 +1,201 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::naive::NaiveDate as Date;
+use chrono::Datelike;
+
+use super::{Money, Transaction};
+
+/// The transactions of one account in date order, with running totals so that the total up to
+/// any date can be found, and a transaction added or removed, in time logarithmic in the number of
+/// days. The totals are kept as `i128`s, which no number of transactions can overflow, and only
+/// need to fit in `Money` when they are asked for.
+#[derive(Debug, Default, PartialEq)]
+pub struct AccountIndex {
+    transaction_ids: BTreeSet<(Date, u32)>,
+    /// A Fenwick tree over the days from `Date::MIN` to `Date::MAX`, in minor units. The node for
+    /// a day holds the total of the transactions in a range of days ending on it, sized by the
+    /// lowest set bit of its slot. Nodes with a total of zero aren't stored, so the tree only takes
+    /// space for the days that have transactions.
+    totals: HashMap<usize, i128>,
+}
+
+impl AccountIndex {
+    /// `new` indexes `transactions`, which all belong to the same account.
+    pub(super) fn new<'a>(transactions: impl Iterator<Item = &'a Transaction>) -> Self {
+        let mut account_index = Self::default();
+        for transaction in transactions {
+            account_index.insert_transaction(transaction);
+        }
+        account_index
+    }
+
+    /// The slot of `date` in the tree, counting from 1.
+    fn slot(date: Date) -> usize {
+        (date.num_days_from_ce() - Date::MIN.num_days_from_ce()) as usize + 1
+    }
+
+    fn add_to_totals(&mut self, date: Date, amount: i128) {
+        let last_slot = Self::slot(Date::MAX);
+        let mut slot = Self::slot(date);
+        while slot <= last_slot {
+            let total = self.totals.entry(slot).or_insert(0);
+            *total += amount;
+            if *total == 0 {
+                self.totals.remove(&slot);
+            }
+            slot += slot & slot.wrapping_neg();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transaction_ids.is_empty()
+    }
+
+    /// The ids of the account's transactions from `start` to `end` inclusive, ordered by date and
+    /// then id.
+    pub fn transaction_ids(&self, start: Date, end: Date) -> impl Iterator<Item = u32> + '_ {
+        self.transaction_ids
+            .range((start, 0)..=(end, u32::MAX))
+            .map(|(_, id)| *id)
+    }
+
+    fn minor_total_through(&self, date: Date) -> i128 {
+        let mut total = 0;
+        let mut slot = Self::slot(date);
+        while slot > 0 {
+            total += self.totals.get(&slot).unwrap_or(&0);
+            slot -= slot & slot.wrapping_neg();
+        }
+        total
+    }
+
+    /// The total of the account's transactions on or before `date`, or `None` if it is too large
+    /// to represent.
+    pub fn total_through(&self, date: Date) -> Option<Money> {
+        i64::try_from(self.minor_total_through(date))
+            .ok()
+            .map(Money::from_minor)
+    }
+
+    /// The total of the account's transactions after `start` and on or before `end`, or `None` if
+    /// it is too large to represent.
+    pub fn total_between(&self, start: Date, end: Date) -> Option<Money> {
+        i64::try_from(self.minor_total_through(end) - self.minor_total_through(start))
+            .ok()
+            .map(Money::from_minor)
+    }
+
+    pub(super) fn insert_transaction(&mut self, transaction: &Transaction) {
+        if self
+            .transaction_ids
+            .insert((transaction.date, transaction.id))
+        {
+            self.add_to_totals(transaction.date, transaction.amount.minor() as i128);
+        }
+    }
+
+    pub(super) fn remove_transaction(&mut self, transaction: &Transaction) {
+        if self
+            .transaction_ids
+            .remove(&(transaction.date, transaction.id))
+        {
+            self.add_to_totals(transaction.date, -(transaction.amount.minor() as i128));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> Date {
+        Date::from_ymd_opt(2023, 5, day).unwrap()
+    }
+
+    fn transaction(id: u32, day: u32, amount: i64) -> Transaction {
+        Transaction {
+            id,
+            account_id: 0,
+            date: date(day),
+            description: format!("Transaction {}", id),
+            amount: Money::from_minor(amount),
+            transaction_group_id: None,
+            statement_id: None,
+            original: None,
+            transfer_transaction_id: None,
+        }
+    }
+
+    fn sample_transactions() -> Vec<Transaction> {
+        vec![
+            transaction(0, 3, 1000),
+            transaction(1, 1, -250),
+            transaction(2, 3, -400),
+            transaction(3, 10, 75),
+            transaction(4, 7, -5),
+        ]
+    }
+
+    #[test]
+    fn totals_through_and_between_dates() {
+        let account_index = AccountIndex::new(sample_transactions().iter());
+        let ids: Vec<u32> = account_index.transaction_ids(date(1), date(7)).collect();
+        assert_eq!(ids, [1, 0, 2, 4]);
+        assert_eq!(
+            account_index.total_through(date(2)),
+            Some(Money::from_minor(-250))
+        );
+        assert_eq!(
+            account_index.total_through(date(3)),
+            Some(Money::from_minor(350))
+        );
+        assert_eq!(
+            account_index.total_through(Date::MAX),
+            Some(Money::from_minor(420))
+        );
+        assert_eq!(account_index.total_through(Date::MIN), Some(Money::ZERO));
+        assert_eq!(
+            account_index.total_between(date(1), date(7)),
+            Some(Money::from_minor(595))
+        );
+    }
+
+    #[test]
+    fn inserts_and_removals_match_a_rebuild() {
+        let transactions = sample_transactions();
+        let mut account_index = AccountIndex::default();
+        for transaction in transactions.iter() {
+            account_index.insert_transaction(transaction);
+        }
+        assert_eq!(account_index, AccountIndex::new(transactions.iter()));
+
+        // Remove one of two transactions on a date, the only one on a date, and one that isn't in
+        // the index.
+        for removed in [&transactions[0], &transactions[3], &transaction(9, 3, 1)] {
+            account_index.remove_transaction(removed);
+        }
+        let remaining = [&transactions[1], &transactions[2], &transactions[4]];
+        assert_eq!(account_index, AccountIndex::new(remaining.into_iter()));
+
+        for transaction in remaining {
+            account_index.remove_transaction(transaction);
+        }
+        assert!(account_index.is_empty());
+        assert_eq!(account_index, AccountIndex::default());
+    }
+
+    #[test]
+    fn totals_that_overflow_are_none() {
+        let transactions = [transaction(0, 1, i64::MAX), transaction(1, 2, i64::MAX)];
+        let account_index = AccountIndex::new(transactions.iter());
+        assert_eq!(
+            account_index.total_through(date(1)),
+            Some(Money::from_minor(i64::MAX))
+        );
+        assert_eq!(account_index.total_through(date(2)), None);
+        assert_eq!(
+            account_index.total_between(date(1), date(2)),
+            Some(Money::from_minor(i64::MAX))
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use chrono::naive::NaiveDate as Date;
+use chrono::Months;
+
+use super::{AmortizationType, AppData, Flow, Money};
+
+/// The longest a flow can be amortized over, in months.
+pub const MAX_AMORTIZATION_LENGTH: i32 = 1200;
+
+/// How finely an amortization schedule is broken down.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccrualPeriod {
+    Day,
+    Month,
+}
+
+/// `split_amount` splits `amount` in proportion to `weights`, rounding so that the parts add up to
+/// exactly `amount`.
+fn split_amount(amount: Money, weights: &[i64]) -> Vec<Money> {
+    let amount = amount.minor() as i128;
+    let total = weights.iter().sum::<i64>() as i128;
+    let mut cumulative_weight = 0;
+    let mut allocated = 0;
+    weights
+        .iter()
+        .map(|weight| {
+            cumulative_weight += *weight as i128;
+            let target = (2 * amount * cumulative_weight + total).div_euclid(2 * total);
+            let part = target - allocated;
+            allocated = target;
+            Money::from_minor(part as i64)
+        })
+        .collect()
+}
+
+impl Flow {
+    /// `accrued_amounts` spreads the flow over `amortization_length` months starting on its date.
+    /// Linear amortization accrues the same amount each month. Declining amortization accrues in
+    /// proportion to the number of months left, so a flow over three months accrues 3/6, 2/6 and
+    /// 1/6 of its amount. With `AccrualPeriod::Day`, each month's amount is spread evenly over its
+    /// days. A flow that isn't amortized accrues entirely on its date. The amounts add up to
+    /// exactly the flow amount; periods where nothing accrues are left out. Lengths are limited to
+    /// between 1 and `MAX_AMORTIZATION_LENGTH` months.
+    pub fn accrued_amounts(&self, period: AccrualPeriod) -> Vec<(Date, Money)> {
+        let amortization_type = match self.amortization_type {
+            Some(amortization_type) => amortization_type,
+            None => return vec![(self.date, self.amount)],
+        };
+        let months = self
+            .amortization_length
+            .unwrap_or(1)
+            .clamp(1, MAX_AMORTIZATION_LENGTH) as i64;
+        let weights: Vec<i64> = match amortization_type {
+            AmortizationType::Linear => vec![1; months as usize],
+            AmortizationType::Declining => (1..=months).rev().collect(),
+        };
+        let month_start = |month: usize| {
+            self.date
+                .checked_add_months(Months::new(month as u32))
+                .unwrap_or(Date::MAX)
+        };
+        let mut accrued_amounts = Vec::new();
+        for (month, amount) in split_amount(self.amount, &weights).into_iter().enumerate() {
+            let start = month_start(month);
+            match period {
+                AccrualPeriod::Month => accrued_amounts.push((start, amount)),
+                AccrualPeriod::Day => {
+                    let days = (month_start(month + 1) - start).num_days().max(1);
+                    let daily_amounts = split_amount(amount, &vec![1; days as usize]);
+                    accrued_amounts.extend(start.iter_days().zip(daily_amounts));
+                }
+            }
+        }
+        accrued_amounts.retain(|(_, amount)| *amount != Money::ZERO);
+        accrued_amounts
+    }
+}
+
+/// `default_amortization` returns the amortization type and length that new flows in the category
+/// start with. Categories without a default inherit the one of their nearest ancestor that has one.
+pub fn default_amortization(
+    app_data: &AppData,
+    category_id: u32,
+) -> (Option<AmortizationType>, Option<i32>) {
+    let mut category = app_data.categories().get(&category_id);
+    // Bound the walk in case the parents form a cycle.
+    for _ in 0..app_data.categories().len() {
+        match category {
+            Some(c) if c.default_amortization_type.is_some() => {
+                return (c.default_amortization_type, c.default_amortization_length)
+            }
+            Some(c) => {
+                category = c
+                    .parent_id
+                    .and_then(|parent_id| app_data.categories().get(&parent_id))
+            }
+            None => break,
+        }
+    }
+    (None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flow(
+        amount: i64,
+        amortization_type: Option<AmortizationType>,
+        amortization_length: Option<i32>,
+    ) -> Flow {
+        Flow {
+            id: 0,
+            category_id: 0,
+            date: Date::from_ymd_opt(2023, 1, 31).unwrap(),
+            description: None,
+            amount: Money::from_minor(amount),
+            currency_id: 0,
+            amortization_type,
+            amortization_length,
+            transaction_group_id: 0,
+        }
+    }
+
+    fn minors(amounts: &[Money]) -> Vec<i64> {
+        amounts.iter().map(|amount| amount.minor()).collect()
+    }
+
+    fn total(accrued_amounts: &[(Date, Money)]) -> Money {
+        accrued_amounts
+            .iter()
+            .try_fold(Money::ZERO, |total, (_, amount)| total.checked_add(*amount))
+            .unwrap()
+    }
+
+    #[test]
+    fn split_amount_rounds_to_the_exact_total() {
+        assert_eq!(
+            minors(&split_amount(Money::from_minor(100), &[1, 1, 1])),
+            [33, 34, 33]
+        );
+        assert_eq!(
+            minors(&split_amount(Money::from_minor(-100), &[1, 1, 1])),
+            [-33, -34, -33]
+        );
+        assert_eq!(
+            minors(&split_amount(Money::from_minor(1000), &[3, 2, 1])),
+            [500, 333, 167]
+        );
+        assert_eq!(
+            minors(&split_amount(Money::from_minor(2), &[1, 1, 1, 1, 1])),
+            [0, 1, 0, 1, 0]
+        );
+        let parts = split_amount(Money::from_minor(i64::MAX), &[1; 7]);
+        assert_eq!(
+            parts.iter().map(|part| part.minor() as i128).sum::<i128>(),
+            i64::MAX as i128
+        );
+    }
+
+    #[test]
+    fn flows_without_amortization_accrue_on_their_date() {
+        let flow = flow(-1234, None, Some(12));
+        assert_eq!(
+            flow.accrued_amounts(AccrualPeriod::Day),
+            [(flow.date, flow.amount)]
+        );
+    }
+
+    #[test]
+    fn linear_amortization_accrues_evenly_by_month() {
+        let flow = flow(1000, Some(AmortizationType::Linear), Some(3));
+        let accrued_amounts = flow.accrued_amounts(AccrualPeriod::Month);
+        let dates: Vec<Date> = accrued_amounts.iter().map(|(date, _)| *date).collect();
+        // Months that are too short for the day of the flow end on their last day.
+        assert_eq!(
+            dates,
+            [
+                Date::from_ymd_opt(2023, 1, 31).unwrap(),
+                Date::from_ymd_opt(2023, 2, 28).unwrap(),
+                Date::from_ymd_opt(2023, 3, 31).unwrap(),
+            ]
+        );
+        let amounts: Vec<Money> = accrued_amounts.iter().map(|(_, amount)| *amount).collect();
+        assert_eq!(minors(&amounts), [333, 334, 333]);
+    }
+
+    #[test]
+    fn declining_amortization_accrues_less_each_month() {
+        let flow = flow(-600, Some(AmortizationType::Declining), Some(3));
+        let amounts: Vec<Money> = flow
+            .accrued_amounts(AccrualPeriod::Month)
+            .iter()
+            .map(|(_, amount)| *amount)
+            .collect();
+        assert_eq!(minors(&amounts), [-300, -200, -100]);
+    }
+
+    #[test]
+    fn daily_amounts_add_up_to_the_flow() {
+        for amortization_type in [AmortizationType::Linear, AmortizationType::Declining] {
+            let flow = flow(-100_001, Some(amortization_type), Some(7));
+            let accrued_amounts = flow.accrued_amounts(AccrualPeriod::Day);
+            assert_eq!(total(&accrued_amounts), flow.amount);
+            assert_eq!(accrued_amounts[0].0, flow.date);
+            assert!(accrued_amounts.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        }
+        // Most days accrue nothing and are left out.
+        let flow = flow(5, Some(AmortizationType::Linear), Some(1));
+        let accrued_amounts = flow.accrued_amounts(AccrualPeriod::Day);
+        assert_eq!(accrued_amounts.len(), 5);
+        assert_eq!(total(&accrued_amounts), flow.amount);
+    }
+
+    #[test]
+    fn lengths_are_limited() {
+        let flow_of_length = |length| flow(100, Some(AmortizationType::Linear), Some(length));
+        for length in [0, -3] {
+            let flow = flow_of_length(length);
+            assert_eq!(
+                flow.accrued_amounts(AccrualPeriod::Month),
+                [(flow.date, flow.amount)]
+            );
+        }
+        let flow = flow(i64::MAX, Some(AmortizationType::Declining), Some(i32::MAX));
+        let accrued_amounts = flow.accrued_amounts(AccrualPeriod::Month);
+        assert_eq!(accrued_amounts.len(), MAX_AMORTIZATION_LENGTH as usize);
+        assert_eq!(total(&accrued_amounts), flow.amount);
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+
+use chrono::naive::NaiveDate as Date;
+use chrono::{Datelike, Months};
+
+use super::exchange_rates::Converter;
+use super::{AccrualPeriod, AppData, CategoryNode, Flow, Money, ReportBasis, Rollover};
+
+/// `month_start` returns the first day of the month containing `date`.
+pub fn month_start(date: Date) -> Date {
+    date.with_day(1).unwrap()
+}
+
+/// `budget_category_id` returns the id of the category whose budget the flows of `category_id`
+/// count towards: the category itself or its nearest ancestor with a budget.
+fn budget_category_id(app_data: &AppData, category_id: u32) -> Option<u32> {
+    let mut category = app_data.categories().get(&category_id);
+    // Bound the walk in case the parents form a cycle.
+    for _ in 0..app_data.categories().len() {
+        match category {
+            Some(c) if app_data.budgets().contains_key(&c.id) => return Some(c.id),
+            Some(c) => {
+                category = c
+                    .parent_id
+                    .and_then(|parent_id| app_data.categories().get(&parent_id))
+            }
+            None => break,
+        }
+    }
+    None
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct BudgetTotals {
+    pub allocated: Money,
+    /// The flows in the month, so spending is negative.
+    pub actual: Money,
+    /// The money left at the end of the month, including what carried over from earlier months.
+    pub available: Money,
+}
+
+/// The budgets of every category for a single month, in a single currency.
+pub struct BudgetReport {
+    pub month: Date,
+    pub currency_id: u32,
+    /// The totals of each category, including those of its descendants.
+    totals: BTreeMap<u32, BudgetTotals>,
+    /// Income up to the end of the month. Flows that don't count towards any budget are income if
+    /// they are positive; spending outside of any budget is left out.
+    pub income: Money,
+    /// Income up to the end of the month that hasn't been allocated, plus whatever budgets have
+    /// given back by not carrying it over.
+    pub to_be_budgeted: Money,
+}
+
+impl BudgetReport {
+    /// `new` returns `None` if any of the flows can't be converted into the currency, or any of
+    /// the totals is too large to represent.
+    pub fn new(
+        app_data: &AppData,
+        month: Date,
+        currency_id: u32,
+        basis: ReportBasis,
+    ) -> Option<Self> {
+        let month = month_start(month);
+        let mut report = Self {
+            month,
+            currency_id,
+            totals: BTreeMap::new(),
+            income: Money::ZERO,
+            to_be_budgeted: Money::ZERO,
+        };
+        let currency = match app_data.currencies().get(&currency_id) {
+            Some(currency) => currency,
+            None => return Some(report),
+        };
+
+        let mut direct_totals: BTreeMap<u32, BudgetTotals> = BTreeMap::new();
+        // The flows that count towards each budget in each month, up to the report month.
+        let mut budget_activity: BTreeMap<u32, BTreeMap<Date, Money>> = BTreeMap::new();
+        let mut converter = Converter::new(app_data);
+        for flow in app_data.flows().values() {
+            let flow_currency = match app_data.currencies().get(&flow.currency_id) {
+                Some(flow_currency) => flow_currency,
+                None => continue,
+            };
+            let amount = converter.convert(flow.amount, flow_currency, currency, flow.date)?;
+            let amounts = match basis {
+                ReportBasis::Cash => vec![(flow.date, amount)],
+                ReportBasis::Accrual => Flow {
+                    amount,
+                    ..flow.clone()
+                }
+                .accrued_amounts(AccrualPeriod::Month),
+            };
+            let budget_id = budget_category_id(app_data, flow.category_id);
+            for (date, amount) in amounts {
+                let flow_month = month_start(date);
+                if flow_month > month {
+                    continue;
+                }
+                if flow_month == month {
+                    let totals = direct_totals.entry(flow.category_id).or_default();
+                    totals.actual = totals.actual.checked_add(amount)?;
+                }
+                match budget_id {
+                    Some(budget_id) => {
+                        let activity = budget_activity
+                            .entry(budget_id)
+                            .or_default()
+                            .entry(flow_month)
+                            .or_default();
+                        *activity = activity.checked_add(amount)?;
+                    }
+                    None if !flow.amount.is_negative() => {
+                        report.income = report.income.checked_add(amount)?
+                    }
+                    None => (),
+                }
+            }
+        }
+
+        let mut allocated = Money::ZERO;
+        let mut released = Money::ZERO;
+        for budget in app_data.budgets().values() {
+            let activity = budget_activity
+                .remove(&budget.category_id)
+                .unwrap_or_default();
+            let first_month = budget
+                .allocations
+                .first()
+                .map(|allocation| allocation.month)
+                .into_iter()
+                .chain(activity.keys().next().copied())
+                .min();
+            let mut current = match first_month {
+                Some(first_month) => first_month,
+                None => continue,
+            };
+            let mut carried = Money::ZERO;
+            while current <= month {
+                let allocation = budget.allocation(current);
+                allocated = allocated.checked_add(allocation)?;
+                let available = carried
+                    .checked_add(allocation)?
+                    .checked_add(activity.get(&current).copied().unwrap_or(Money::ZERO))?;
+                if current == month {
+                    let totals = direct_totals.entry(budget.category_id).or_default();
+                    totals.allocated = allocation;
+                    totals.available = available;
+                    break;
+                }
+                carried = match budget.rollover {
+                    Rollover::CarrySurplus => available.max(Money::ZERO),
+                    Rollover::CarryDeficit => available.min(Money::ZERO),
+                    Rollover::Reset => Money::ZERO,
+                };
+                released = released.checked_add(available.checked_sub(carried)?)?;
+                current = match current.checked_add_months(Months::new(1)) {
+                    Some(next) => next,
+                    None => break,
+                };
+            }
+        }
+        report.to_be_budgeted = report
+            .income
+            .checked_sub(allocated)?
+            .checked_add(released)?;
+
+        for node in app_data.category_trees().iter() {
+            report.roll_up(node, &direct_totals)?;
+        }
+        Some(report)
+    }
+
+    fn roll_up(
+        &mut self,
+        node: &CategoryNode,
+        direct_totals: &BTreeMap<u32, BudgetTotals>,
+    ) -> Option<()> {
+        let mut totals = direct_totals.get(&node.id).copied().unwrap_or_default();
+        for child in node.children.iter() {
+            self.roll_up(child, direct_totals)?;
+            let child_totals = self.totals[&child.id];
+            totals.allocated = totals.allocated.checked_add(child_totals.allocated)?;
+            totals.actual = totals.actual.checked_add(child_totals.actual)?;
+            totals.available = totals.available.checked_add(child_totals.available)?;
+        }
+        self.totals.insert(node.id, totals);
+        Some(())
+    }
+
+    /// The totals of a category and its descendants.
+    pub fn totals(&self, category_id: u32) -> BudgetTotals {
+        self.totals.get(&category_id).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_fixtures::{self, category, currency};
+    use crate::data::{AmortizationType, Budget, Update};
+
+    fn date(month: u32, day: u32) -> Date {
+        Date::from_ymd_opt(2023, month, day).unwrap()
+    }
+
+    fn flow(id: u32, category_id: u32, date: Date, amount: i64) -> Flow {
+        Flow {
+            id,
+            category_id,
+            date,
+            description: None,
+            amount: Money::from_minor(amount),
+            currency_id: 0,
+            amortization_type: None,
+            amortization_length: None,
+            transaction_group_id: 0,
+        }
+    }
+
+    /// A Food budget of 100 a month from January to March, covering its Groceries subcategory,
+    /// with 1000 of income in January.
+    fn app_data_with(rollover: Rollover, flows: Vec<Flow>) -> AppData {
+        let mut updates = vec![Update::SetCurrency(currency(0, "USD"))];
+        for (id, name, parent_id) in [
+            (0, "Food", None),
+            (1, "Groceries", Some(0)),
+            (2, "Pay", None),
+        ] {
+            updates.push(Update::SetCategory(category(id, name, parent_id)));
+        }
+        let mut budget = Budget {
+            category_id: 0,
+            rollover,
+            allocations: Vec::new(),
+        };
+        for month in 1..=3 {
+            budget.set_allocation(date(month, 1), Money::from_minor(100));
+        }
+        updates.push(Update::SetBudget(budget));
+        updates.push(Update::SetFlow(flow(0, 2, date(1, 15), 1000)));
+        updates.extend(flows.into_iter().map(Update::SetFlow));
+        test_fixtures::app_data_with(updates)
+    }
+
+    fn groceries() -> Vec<Flow> {
+        vec![
+            flow(1, 1, date(1, 10), -80),
+            flow(2, 1, date(2, 10), -150),
+            flow(3, 1, date(3, 10), -50),
+        ]
+    }
+
+    fn march_report(app_data: &AppData) -> BudgetReport {
+        BudgetReport::new(app_data, date(3, 20), 0, ReportBasis::Cash).unwrap()
+    }
+
+    #[test]
+    fn carry_surplus_covers_overspending_from_to_be_budgeted() {
+        let report = march_report(&app_data_with(Rollover::CarrySurplus, groceries()));
+        assert_eq!(report.month, date(3, 1));
+        assert_eq!(
+            report.totals(0),
+            BudgetTotals {
+                allocated: Money::from_minor(100),
+                actual: Money::from_minor(-50),
+                available: Money::from_minor(50),
+            }
+        );
+        assert_eq!(report.totals(1).actual, Money::from_minor(-50));
+        assert_eq!(report.totals(1).allocated, Money::ZERO);
+        assert_eq!(report.income, Money::from_minor(1000));
+        // February's overspending of 30 comes out of the money to be budgeted.
+        assert_eq!(report.to_be_budgeted, Money::from_minor(670));
+    }
+
+    #[test]
+    fn carry_deficit_takes_overspending_from_the_next_month() {
+        let report = march_report(&app_data_with(Rollover::CarryDeficit, groceries()));
+        assert_eq!(report.totals(0).available, Money::ZERO);
+        // January's surplus of 20 goes back to be budgeted.
+        assert_eq!(report.to_be_budgeted, Money::from_minor(720));
+    }
+
+    #[test]
+    fn reset_starts_every_month_from_nothing() {
+        let report = march_report(&app_data_with(Rollover::Reset, groceries()));
+        assert_eq!(report.totals(0).available, Money::from_minor(50));
+        assert_eq!(report.to_be_budgeted, Money::from_minor(670));
+    }
+
+    #[test]
+    fn accrual_basis_spreads_amortized_flows() {
+        let mut insurance = flow(1, 1, date(1, 10), -300);
+        insurance.amortization_type = Some(AmortizationType::Linear);
+        insurance.amortization_length = Some(3);
+        let app_data = app_data_with(Rollover::CarrySurplus, vec![insurance]);
+
+        let cash = BudgetReport::new(&app_data, date(2, 1), 0, ReportBasis::Cash).unwrap();
+        assert_eq!(cash.totals(0).actual, Money::ZERO);
+        // January's overspending isn't carried into February.
+        assert_eq!(cash.totals(0).available, Money::from_minor(100));
+
+        let accrual = BudgetReport::new(&app_data, date(2, 1), 0, ReportBasis::Accrual).unwrap();
+        assert_eq!(accrual.totals(0).actual, Money::from_minor(-100));
+        assert_eq!(accrual.totals(0).available, Money::ZERO);
+    }
+
+    #[test]
+    fn totals_that_overflow_are_none() {
+        let app_data = app_data_with(
+            Rollover::CarrySurplus,
+            vec![flow(1, 2, date(1, 1), i64::MAX)],
+        );
+        assert!(BudgetReport::new(&app_data, date(1, 1), 0, ReportBasis::Cash).is_none());
+    }
+}
@@ -1,17 +1,75 @@
+use std::collections::HashMap;
+
 use super::AppData;
 
+/// Data in `AppData` that a `CachedValue` can depend on.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Dependency {
+    Accounts,
+    Categories,
+    Currencies,
+    Flows,
+    TransactionGroups,
+    Transactions,
+    Statements,
+    Rules,
+    Budgets,
+    Settings,
+    /// A single account, including its balances and transactions.
+    Account(u32),
+}
+
+/// Counters that are incremented whenever the data they cover changes. Unlike the modification
+/// count, they never go backwards, even on undo.
+#[derive(Default)]
+pub(super) struct Versions {
+    all: u32,
+    dependencies: HashMap<Dependency, u32>,
+}
+
+impl Versions {
+    pub fn bump(&mut self, dependencies: &[Dependency]) {
+        self.all += 1;
+        for dependency in dependencies {
+            *self.dependencies.entry(*dependency).or_insert(0) += 1;
+        }
+    }
+
+    fn get(&self, dependencies: &Option<Vec<Dependency>>) -> Vec<u32> {
+        match dependencies {
+            Some(dependencies) => dependencies
+                .iter()
+                .map(|dependency| *self.dependencies.get(dependency).unwrap_or(&0))
+                .collect(),
+            None => vec![self.all],
+        }
+    }
+}
+
 pub struct CachedValue<T> {
-    cache: Option<(u32, T)>,
+    /// What the value depends on, or `None` if it depends on everything.
+    dependencies: Option<Vec<Dependency>>,
+    cache: Option<(Vec<u32>, T)>,
 }
 
 impl<T> Default for CachedValue<T> {
     fn default() -> Self {
-        Self { cache: None }
+        Self {
+            dependencies: None,
+            cache: None,
+        }
     }
 }
 
 impl<T> CachedValue<T> {
-    #[allow(dead_code)]
+    /// A value that is only recomputed when one of `dependencies` changes.
+    pub fn depending_on(dependencies: &[Dependency]) -> Self {
+        Self {
+            dependencies: Some(dependencies.to_vec()),
+            cache: None,
+        }
+    }
+
     pub fn invalidate(&mut self) {
         self.cache = None;
     }
@@ -20,13 +78,14 @@ impl<T> CachedValue<T> {
     where
         F: Fn(&AppData) -> T,
     {
+        let versions = app_data.versions().get(&self.dependencies);
         let cache_is_good = match &self.cache {
-            Some((modification_count, _t)) => *modification_count == app_data.modification_count(),
+            Some((cached_versions, _t)) => *cached_versions == versions,
             None => false,
         };
         if !cache_is_good {
             let t = compute(app_data);
-            self.cache = Some((app_data.modification_count(), t));
+            self.cache = Some((versions, t));
         }
         &self.cache.as_ref().unwrap().1
     }
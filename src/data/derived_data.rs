@@ -0,0 +1,164 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::naive::NaiveDate as Date;
+
+use super::account_index::AccountIndex;
+use super::cached_value::Dependency;
+use super::models::*;
+
+/// A change that `Update::apply` has just made to one kind of entity. The new entities are in
+/// `AppData`; variants carry whatever else keeping the derived data up to date needs, such as the
+/// transaction as it was before the change.
+pub(super) enum Change<'a> {
+    Account(u32),
+    Category,
+    Currency,
+    Flow,
+    TransactionGroup,
+    Transaction(u32, Option<&'a Transaction>),
+    Statement,
+    Rule,
+    Budget,
+    Settings,
+}
+
+impl Change<'_> {
+    /// The data that the change affects, now that it has been made to `app_data`.
+    pub fn dependencies(&self, app_data: &AppData) -> Vec<Dependency> {
+        match self {
+            Change::Account(id) => vec![Dependency::Accounts, Dependency::Account(*id)],
+            Change::Category => vec![Dependency::Categories],
+            Change::Currency => vec![Dependency::Currencies],
+            Change::Flow => vec![Dependency::Flows],
+            Change::TransactionGroup => vec![Dependency::TransactionGroups],
+            Change::Transaction(id, old_transaction) => {
+                let mut dependencies = vec![Dependency::Transactions];
+                let account_ids = old_transaction
+                    .map(|transaction| transaction.account_id)
+                    .into_iter()
+                    .chain(
+                        app_data
+                            .transactions
+                            .get(id)
+                            .map(|transaction| transaction.account_id),
+                    );
+                for account_id in account_ids {
+                    if !dependencies.contains(&Dependency::Account(account_id)) {
+                        dependencies.push(Dependency::Account(account_id));
+                    }
+                }
+                dependencies
+            }
+            Change::Statement => vec![Dependency::Statements],
+            Change::Rule => vec![Dependency::Rules],
+            Change::Budget => vec![Dependency::Budgets],
+            Change::Settings => vec![Dependency::Settings],
+        }
+    }
+}
+
+/// The data structures `AppData` derives from its core data. `build` computes them from scratch
+/// and `apply` brings them up to date after a single change, so adding an index means adding a
+/// field and handling it in both.
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct DerivedData {
+    pub category_trees: Vec<CategoryNode>,
+    pub transactions_by_date: BTreeMap<Date, BTreeSet<u32>>,
+    pub transactions_by_account: BTreeMap<u32, AccountIndex>,
+}
+
+fn build_category_trees(categories: &BTreeMap<u32, Category>) -> Vec<CategoryNode> {
+    let mut roots: Vec<u32> = Vec::new();
+    let mut children_map: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    for (id, category) in categories.iter() {
+        match category.parent_id {
+            Some(parent_id) => children_map.entry(parent_id).or_default().push(*id),
+            None => roots.push(*id),
+        }
+    }
+    roots
+        .into_iter()
+        .map(|id| CategoryNode::new(id, &children_map))
+        .collect()
+}
+
+impl DerivedData {
+    pub fn build(app_data: &AppData) -> Self {
+        let mut transactions_by_date: BTreeMap<Date, BTreeSet<u32>> = BTreeMap::new();
+        let mut account_transactions: BTreeMap<u32, Vec<&Transaction>> = BTreeMap::new();
+        for transaction in app_data.transactions.values() {
+            transactions_by_date
+                .entry(transaction.date)
+                .or_default()
+                .insert(transaction.id);
+            account_transactions
+                .entry(transaction.account_id)
+                .or_default()
+                .push(transaction);
+        }
+        Self {
+            category_trees: build_category_trees(&app_data.categories),
+            transactions_by_date,
+            transactions_by_account: account_transactions
+                .into_iter()
+                .map(|(account_id, transactions)| {
+                    (account_id, AccountIndex::new(transactions.into_iter()))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn apply(&mut self, app_data: &AppData, change: &Change) {
+        match change {
+            Change::Category => {
+                // There are few enough categories that rebuilding the trees is cheap.
+                self.category_trees = build_category_trees(&app_data.categories);
+            }
+            Change::Transaction(id, old_transaction) => {
+                if let Some(old_transaction) = old_transaction {
+                    self.remove_transaction(old_transaction);
+                }
+                if let Some(transaction) = app_data.transactions.get(id) {
+                    self.insert_transaction(transaction);
+                }
+            }
+            Change::Account(_)
+            | Change::Currency
+            | Change::Flow
+            | Change::TransactionGroup
+            | Change::Statement
+            | Change::Rule
+            | Change::Budget
+            | Change::Settings => (),
+        }
+    }
+
+    fn insert_transaction(&mut self, transaction: &Transaction) {
+        self.transactions_by_date
+            .entry(transaction.date)
+            .or_default()
+            .insert(transaction.id);
+        self.transactions_by_account
+            .entry(transaction.account_id)
+            .or_default()
+            .insert_transaction(transaction);
+    }
+
+    fn remove_transaction(&mut self, transaction: &Transaction) {
+        if let Some(ids) = self.transactions_by_date.get_mut(&transaction.date) {
+            ids.remove(&transaction.id);
+            if ids.is_empty() {
+                self.transactions_by_date.remove(&transaction.date);
+            }
+        }
+        if let Some(account_index) = self
+            .transactions_by_account
+            .get_mut(&transaction.account_id)
+        {
+            account_index.remove_transaction(transaction);
+            if account_index.is_empty() {
+                self.transactions_by_account.remove(&transaction.account_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+use crate::error::Error;
+use crate::result::Result;
+
+/// The Argon2id parameters for new keys: 64 MiB of memory and three passes.
+const M_COST: u32 = 64 * 1024;
+const T_COST: u32 = 3;
+const P_COST: u32 = 1;
+/// Parameters beyond these are refused when reading a header, rather than trying to allocate
+/// however much memory a damaged file asks for.
+const MAX_M_COST: u32 = 1024 * 1024;
+const MAX_T_COST: u32 = 64;
+
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+/// The length of the header written by `Encryption::header`: the three parameters, the salt and
+/// the key check.
+const HEADER_LEN: usize = 12 + SALT_LEN + KEY_LEN;
+
+/// A key derived from a passphrase with Argon2id, used to encrypt save files with
+/// XChaCha20-Poly1305. The KDF output is twice the key length; the second half is stored in the
+/// file as a key check, so that a wrong passphrase can be told apart from a tampered file.
+#[derive(Clone)]
+pub struct Encryption {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: [u8; SALT_LEN],
+    key: [u8; KEY_LEN],
+    key_check: [u8; KEY_LEN],
+}
+
+impl Encryption {
+    /// `new` derives a key from `passphrase` with a fresh salt.
+    pub fn new(passphrase: &str) -> Result<Self> {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(passphrase, M_COST, T_COST, P_COST, salt)
+    }
+
+    fn derive(
+        passphrase: &str,
+        m_cost: u32,
+        t_cost: u32,
+        p_cost: u32,
+        salt: [u8; SALT_LEN],
+    ) -> Result<Self> {
+        let corrupt = |err: argon2::Error| Error::Corrupt(format!("key derivation: {}", err));
+        let params = Params::new(m_cost, t_cost, p_cost, Some(2 * KEY_LEN)).map_err(corrupt)?;
+        let mut output = [0; 2 * KEY_LEN];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut output)
+            .map_err(corrupt)?;
+        let mut key = [0; KEY_LEN];
+        let mut key_check = [0; KEY_LEN];
+        key.copy_from_slice(&output[..KEY_LEN]);
+        key_check.copy_from_slice(&output[KEY_LEN..]);
+        Ok(Self {
+            m_cost,
+            t_cost,
+            p_cost,
+            salt,
+            key,
+            key_check,
+        })
+    }
+
+    /// `header` returns what is needed to derive the key again from the passphrase.
+    pub fn header(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&self.m_cost.to_le_bytes());
+        header.extend_from_slice(&self.t_cost.to_le_bytes());
+        header.extend_from_slice(&self.p_cost.to_le_bytes());
+        header.extend_from_slice(&self.salt);
+        header.extend_from_slice(&self.key_check);
+        header
+    }
+
+    /// `from_header` derives the key described by the start of `bytes` from `passphrase`, and
+    /// returns it with the rest of `bytes`. A damaged header can't be told apart from a wrong
+    /// passphrase.
+    pub fn from_header<'a>(bytes: &'a [u8], passphrase: &str) -> Result<(Self, &'a [u8])> {
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::Corrupt("truncated encryption header".into()));
+        }
+        let (header, rest) = bytes.split_at(HEADER_LEN);
+        let parameter = |i: usize| u32::from_le_bytes(header[4 * i..4 * i + 4].try_into().unwrap());
+        let (m_cost, t_cost, p_cost) = (parameter(0), parameter(1), parameter(2));
+        if m_cost > MAX_M_COST || t_cost > MAX_T_COST {
+            return Err(Error::Corrupt(
+                "key derivation parameters are too large".into(),
+            ));
+        }
+        let salt = header[12..12 + SALT_LEN].try_into().unwrap();
+        let encryption = Self::derive(passphrase, m_cost, t_cost, p_cost, salt)?;
+        if encryption.key_check[..] != header[12 + SALT_LEN..] {
+            return Err(Error::WrongPassphrase);
+        }
+        Ok((encryption, rest))
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.key.into())
+    }
+
+    /// `seal` encrypts `plaintext` with a random nonce, authenticating `aad` along with it, and
+    /// returns the nonce followed by the ciphertext.
+    pub fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .expect("plaintext is too long to encrypt");
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        sealed
+    }
+
+    /// `open` reverses `seal`, failing with `Error::Tampered` if `sealed` or `aad` has changed.
+    pub fn open(&self, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            return Err(Error::Tampered);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher()
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| Error::Tampered)
+    }
+}
+
+#[cfg(test)]
+impl Encryption {
+    /// `cheap` derives a key with the smallest parameters Argon2 allows, so that tests run quickly.
+    pub fn cheap(passphrase: &str) -> Self {
+        Self::derive(passphrase, 8, 1, 1, [0; SALT_LEN]).unwrap()
+    }
+}
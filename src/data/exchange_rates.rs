@@ -0,0 +1,430 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chrono::naive::NaiveDate as Date;
+
+use super::{AppData, Currency, ExchangeRate, Money, Settings, Update};
+use crate::error::Error;
+use crate::result::Result;
+
+/// `rate_on_date` interpolates between the rates either side of `date`, or uses the nearest rate
+/// if `date` is outside the table. `rates` must be sorted by date.
+fn rate_on_date(rates: &[ExchangeRate], date: Date) -> Option<f64> {
+    let index = rates.partition_point(|rate| rate.date < date);
+    let before = index.checked_sub(1).map(|index| &rates[index]);
+    match (before, rates.get(index)) {
+        (_, Some(after)) if after.date == date => Some(after.rate),
+        (Some(before), Some(after)) => {
+            let fraction = (date - before.date).num_days() as f64
+                / (after.date - before.date).num_days() as f64;
+            Some(before.rate + (after.rate - before.rate) * fraction)
+        }
+        (Some(rate), None) | (None, Some(rate)) => Some(rate.rate),
+        (None, None) => None,
+    }
+}
+
+/// An exchange rate table in a chain of them, inverted if it converts the other way.
+#[derive(Clone, Copy)]
+struct Link<'a> {
+    rates: &'a [ExchangeRate],
+    inverted: bool,
+}
+
+impl Link<'_> {
+    fn rate_on_date(&self, date: Date) -> Option<f64> {
+        let rate = rate_on_date(self.rates, date)?;
+        Some(if self.inverted { 1.0 / rate } else { rate })
+    }
+}
+
+/// Whether every rate in the table is a positive number, so that any rate interpolated from it
+/// is as well.
+fn is_usable(rates: &[ExchangeRate]) -> bool {
+    !rates.is_empty()
+        && rates
+            .iter()
+            .all(|rate| rate.rate.is_finite() && rate.rate > 0.0)
+}
+
+/// The usable tables from the currency `id` to every currency it has one with, in either
+/// direction.
+fn neighbouring_links(app_data: &AppData, id: u32) -> Vec<(u32, Link<'_>)> {
+    let mut links = Vec::new();
+    if let Some(currency) = app_data.currencies().get(&id) {
+        for (other_id, rates) in currency.exchange_rates.iter() {
+            links.push((
+                *other_id,
+                Link {
+                    rates,
+                    inverted: false,
+                },
+            ));
+        }
+    }
+    for other in app_data.currencies().values() {
+        if let Some(rates) = other.exchange_rates.get(&id) {
+            links.push((
+                other.id,
+                Link {
+                    rates,
+                    inverted: true,
+                },
+            ));
+        }
+    }
+    links.retain(|(other_id, link)| {
+        app_data.currencies().contains_key(other_id) && is_usable(link.rates)
+    });
+    links
+}
+
+/// `find_chain` returns the tables to chain to convert from the currency `from_id` to `to_id`, or
+/// `None` if there is no chain. A breadth-first search chains the fewest tables, since each one
+/// adds some error.
+fn find_chain(app_data: &AppData, from_id: u32, to_id: u32) -> Option<Vec<Link<'_>>> {
+    // The currency and table that each reached currency was reached through.
+    let mut previous: HashMap<u32, Option<(u32, Link)>> = HashMap::from([(from_id, None)]);
+    let mut queue = VecDeque::from([from_id]);
+    while let Some(id) = queue.pop_front() {
+        if id == to_id {
+            let mut chain = Vec::new();
+            let mut current = id;
+            while let Some((previous_id, link)) = previous[&current] {
+                chain.push(link);
+                current = previous_id;
+            }
+            chain.reverse();
+            return Some(chain);
+        }
+        for (other_id, link) in neighbouring_links(app_data, id) {
+            if let Entry::Vacant(entry) = previous.entry(other_id) {
+                entry.insert(Some((id, link)));
+                queue.push_back(other_id);
+            }
+        }
+    }
+    None
+}
+
+/// Converts amounts between currencies, finding the chain of tables between each pair of
+/// currencies only once. It doesn't notice changes to the rates, so it should only live as long
+/// as a single report.
+pub struct Converter<'a> {
+    app_data: &'a AppData,
+    chains: HashMap<(u32, u32), Option<Vec<Link<'a>>>>,
+}
+
+impl<'a> Converter<'a> {
+    pub fn new(app_data: &'a AppData) -> Self {
+        Self {
+            app_data,
+            chains: HashMap::new(),
+        }
+    }
+
+    /// The value of one major unit of `from` in major units of `to` on `date`. If there is no
+    /// table for the pair, rates are chained through other currencies, and if there is no chain
+    /// at all, the `equivalent_base` rates are used.
+    pub fn exchange_rate(&mut self, from: &Currency, to: &Currency, date: Date) -> f64 {
+        let app_data = self.app_data;
+        let chain = self
+            .chains
+            .entry((from.id, to.id))
+            .or_insert_with(|| find_chain(app_data, from.id, to.id));
+        chain
+            .as_ref()
+            .and_then(|chain| {
+                chain
+                    .iter()
+                    .map(|link| link.rate_on_date(date))
+                    .product::<Option<f64>>()
+            })
+            .unwrap_or(from.equivalent_base as f64 / to.equivalent_base as f64)
+    }
+
+    /// `convert` converts `amount` between currencies at the rate on `date`. Returns `None` if
+    /// the rate isn't a positive number, e.g. because an `equivalent_base` rate is zero, or if
+    /// the converted amount is too large to represent.
+    pub fn convert(
+        &mut self,
+        amount: Money,
+        from: &Currency,
+        to: &Currency,
+        date: Date,
+    ) -> Option<Money> {
+        if from.id == to.id {
+            return Some(amount);
+        }
+        let rate = self.exchange_rate(from, to, date);
+        if !rate.is_finite() || rate <= 0.0 || from.major <= 0 || to.major <= 0 {
+            return None;
+        }
+        let major_amount = amount.minor() as f64 / from.major as f64 * rate;
+        let minor_amount = (major_amount * to.major as f64).round();
+        // `i64::MAX as f64` rounds up to 2^63, which is out of range.
+        if !minor_amount.is_finite() || minor_amount.abs() >= i64::MAX as f64 {
+            return None;
+        }
+        Some(Money::from_minor(minor_amount as i64))
+    }
+}
+
+/// `convert` is `Converter::convert`, for a single conversion.
+pub fn convert(
+    app_data: &AppData,
+    amount: Money,
+    from: &Currency,
+    to: &Currency,
+    date: Date,
+) -> Option<Money> {
+    Converter::new(app_data).convert(amount, from, to, date)
+}
+
+fn split_csv_line(line: &str) -> impl Iterator<Item = &str> {
+    line.split(',').map(|cell| cell.trim().trim_matches('"'))
+}
+
+/// `parse_rates_csv` reads rates against `base` from a file with a date column followed by a
+/// column per currency code, like the ECB's reference rate files. Columns for currencies that
+/// don't exist and cells that aren't numbers (the ECB writes "N/A") are skipped, but rates that
+/// are zero, negative or infinite are an error.
+pub fn parse_rates_csv(
+    app_data: &AppData,
+    base: &Currency,
+    text: &str,
+) -> Result<BTreeMap<u32, Vec<ExchangeRate>>> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header = lines
+        .next()
+        .ok_or_else(|| Error::RatesParse("the file is empty".into()))?;
+    let column_currency_ids: Vec<Option<u32>> = split_csv_line(header)
+        .skip(1)
+        .map(|code| {
+            app_data
+                .currencies()
+                .values()
+                .find(|currency| currency.code == code && currency.id != base.id)
+                .map(|currency| currency.id)
+        })
+        .collect();
+
+    let mut tables: BTreeMap<u32, Vec<ExchangeRate>> = BTreeMap::new();
+    for line in lines {
+        let mut cells = split_csv_line(line);
+        let date_cell = cells.next().unwrap_or_default();
+        let date = Date::parse_from_str(date_cell, "%Y-%m-%d")
+            .map_err(|_| Error::RatesParse(format!("invalid date {:?}", date_cell)))?;
+        for (currency_id, cell) in column_currency_ids.iter().zip(cells) {
+            match (currency_id, cell.parse::<f64>()) {
+                (Some(currency_id), Ok(rate)) if rate.is_finite() && rate > 0.0 => tables
+                    .entry(*currency_id)
+                    .or_default()
+                    .push(ExchangeRate { date, rate }),
+                (Some(_), Ok(_)) => {
+                    return Err(Error::RatesParse(format!(
+                        "invalid rate {:?} on {}",
+                        cell, date
+                    )))
+                }
+                _ => (),
+            }
+        }
+    }
+    for table in tables.values_mut() {
+        table.sort_by_key(|rate| rate.date);
+        table.dedup_by_key(|rate| rate.date);
+    }
+    Ok(tables)
+}
+
+/// `merge_rates` adds `rates` to `table`, replacing any existing rates on the same dates.
+pub fn merge_rates(table: &mut Vec<ExchangeRate>, rates: impl IntoIterator<Item = ExchangeRate>) {
+    let mut merged: BTreeMap<Date, f64> = table.iter().map(|rate| (rate.date, rate.rate)).collect();
+    merged.extend(rates.into_iter().map(|rate| (rate.date, rate.rate)));
+    *table = merged
+        .into_iter()
+        .map(|(date, rate)| ExchangeRate { date, rate })
+        .collect();
+}
+
+/// `base_currency_updates` makes `base_id` the base currency, re-expressing the `equivalent_base`
+/// rate of every currency relative to it.
+pub fn base_currency_updates(app_data: &AppData, base_id: u32) -> Vec<Update> {
+    let base_rate = match app_data.currencies().get(&base_id) {
+        Some(base) => base.equivalent_base,
+        None => return Vec::new(),
+    };
+    let mut updates = vec![Update::SetSettings(Settings {
+        base_currency_id: Some(base_id),
+        ..app_data.settings().clone()
+    })];
+    if base_rate.is_finite() && base_rate > 0.0 {
+        for currency in app_data.currencies().values() {
+            let mut currency = currency.clone();
+            currency.equivalent_base /= base_rate;
+            updates.push(Update::SetCurrency(currency));
+        }
+    }
+    updates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_fixtures::{self, app_data_with};
+
+    fn date(month: u32, day: u32) -> Date {
+        Date::from_ymd_opt(2023, month, day).unwrap()
+    }
+
+    fn rate(month: u32, day: u32, rate: f64) -> ExchangeRate {
+        ExchangeRate {
+            date: date(month, day),
+            rate,
+        }
+    }
+
+    fn currency(id: u32, code: &str, equivalent_base: f32) -> Currency {
+        Currency {
+            equivalent_base,
+            ..test_fixtures::currency(id, code)
+        }
+    }
+
+    /// EUR (0), USD (1), GBP (2) and JPY (3). EUR has a table against USD, and GBP has one
+    /// against USD; JPY has none.
+    fn sample() -> AppData {
+        let mut eur = currency(0, "EUR", 1.1);
+        eur.exchange_rates
+            .insert(1, vec![rate(1, 1, 1.0), rate(1, 11, 1.2)]);
+        let mut gbp = currency(2, "GBP", 1.25);
+        gbp.exchange_rates.insert(1, vec![rate(1, 1, 1.25)]);
+        let mut jpy = currency(3, "JPY", 0.0075);
+        jpy.major = 1;
+        app_data_with(vec![
+            Update::SetCurrency(eur),
+            Update::SetCurrency(currency(1, "USD", 1.0)),
+            Update::SetCurrency(gbp),
+            Update::SetCurrency(jpy),
+        ])
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn interpolates_between_rates() {
+        let rates = [rate(1, 1, 1.0), rate(1, 11, 1.2), rate(1, 21, 1.1)];
+        assert_eq!(rate_on_date(&rates, date(1, 11)), Some(1.2));
+        assert_close(rate_on_date(&rates, date(1, 6)).unwrap(), 1.1);
+        assert_close(rate_on_date(&rates, date(1, 13)).unwrap(), 1.18);
+        // Outside the table, the nearest rate is used.
+        assert_eq!(
+            rate_on_date(&rates, date(1, 1).pred_opt().unwrap()),
+            Some(1.0)
+        );
+        assert_eq!(rate_on_date(&rates, date(12, 31)), Some(1.1));
+        assert_eq!(rate_on_date(&[], date(1, 1)), None);
+    }
+
+    #[test]
+    fn converts_in_either_direction_and_through_other_currencies() {
+        let app_data = sample();
+        let currencies = app_data.currencies();
+        let (eur, usd, gbp, jpy) = (
+            &currencies[&0],
+            &currencies[&1],
+            &currencies[&2],
+            &currencies[&3],
+        );
+        let mut converter = Converter::new(&app_data);
+        assert_close(converter.exchange_rate(eur, usd, date(1, 6)), 1.1);
+        assert_close(converter.exchange_rate(usd, eur, date(1, 11)), 1.0 / 1.2);
+        assert_close(converter.exchange_rate(gbp, eur, date(1, 11)), 1.25 / 1.2);
+        // Without any tables, the equivalent rates are used.
+        assert_close(converter.exchange_rate(jpy, usd, date(1, 1)), 0.0075);
+        assert_eq!(
+            converter.convert(Money::from_minor(10000), gbp, eur, date(1, 1)),
+            Some(Money::from_minor(12500))
+        );
+        assert_eq!(
+            converter.convert(Money::from_minor(1000), jpy, usd, date(1, 1)),
+            Some(Money::from_minor(750))
+        );
+        assert_eq!(
+            convert(&app_data, Money::from_minor(i64::MAX), gbp, usd, date(1, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn skips_tables_with_invalid_rates() {
+        let mut app_data = sample();
+        let mut gbp = app_data.currencies()[&2].clone();
+        gbp.exchange_rates.insert(0, vec![rate(1, 1, 0.0)]);
+        app_data
+            .perform_update("Edit", vec![Update::SetCurrency(gbp)])
+            .unwrap();
+        let currencies = app_data.currencies();
+        let mut converter = Converter::new(&app_data);
+        assert_close(
+            converter.exchange_rate(&currencies[&2], &currencies[&0], date(1, 1)),
+            1.25,
+        );
+    }
+
+    #[test]
+    fn parses_csv_rates() {
+        let app_data = sample();
+        let usd = &app_data.currencies()[&1];
+        let text = "Date,EUR,XYZ,GBP,USD,\n\
+                    2023-01-02,0.91,1,N/A,1,\n\
+                    \n\
+                    \"2023-01-01\", \"0.92\",2,0.8,1,\n\
+                    2023-01-02,0.95,3,0.81,1,\n";
+        let tables = parse_rates_csv(&app_data, usd, text).unwrap();
+        // Unknown currencies and the base currency itself are skipped. The first rate on a date
+        // is kept.
+        assert_eq!(tables.keys().collect::<Vec<_>>(), [&0, &2]);
+        assert_eq!(tables[&0], [rate(1, 1, 0.92), rate(1, 2, 0.91)]);
+        assert_eq!(tables[&2], [rate(1, 1, 0.8), rate(1, 2, 0.81)]);
+    }
+
+    #[test]
+    fn refuses_invalid_csv_rates() {
+        let app_data = sample();
+        let usd = &app_data.currencies()[&1];
+        for text in [
+            "",
+            "Date,EUR\n01/02/2023,0.91\n",
+            "Date,EUR\n2023-01-02,0\n",
+            "Date,EUR\n2023-01-02,-1\n",
+            "Date,EUR\n2023-01-02,inf\n",
+        ] {
+            assert!(
+                matches!(
+                    parse_rates_csv(&app_data, usd, text),
+                    Err(Error::RatesParse(_))
+                ),
+                "{:?}",
+                text
+            );
+        }
+        // A column for a currency that doesn't exist isn't checked.
+        assert!(parse_rates_csv(&app_data, usd, "Date,XYZ\n2023-01-02,0\n")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn merged_rates_replace_those_on_the_same_date() {
+        let mut table = vec![rate(1, 1, 1.0), rate(1, 3, 1.2)];
+        merge_rates(
+            &mut table,
+            [rate(1, 3, 1.3), rate(1, 2, 1.1), rate(1, 2, 1.15)],
+        );
+        assert_eq!(table, [rate(1, 1, 1.0), rate(1, 2, 1.15), rate(1, 3, 1.3)]);
+    }
+}
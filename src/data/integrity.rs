@@ -0,0 +1,759 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{AppData, Budget, Currency, Rule, Settings, Transaction, TransactionGroup, Update};
+use crate::error::Error;
+use crate::result::Result;
+
+/// An entity that other entities refer to by id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityRef {
+    Account(u32),
+    Category(u32),
+    Currency(u32),
+}
+
+impl EntityRef {
+    fn exists(&self, app_data: &AppData) -> bool {
+        match self {
+            Self::Account(id) => app_data.accounts().contains_key(id),
+            Self::Category(id) => app_data.categories().contains_key(id),
+            Self::Currency(id) => app_data.currencies().contains_key(id),
+        }
+    }
+}
+
+/// The entities that refer directly to some other entity.
+#[derive(Debug, Default, Clone)]
+pub struct Dependents {
+    pub accounts: Vec<u32>,
+    pub categories: Vec<u32>,
+    pub flows: Vec<u32>,
+    pub transactions: Vec<u32>,
+    pub statements: Vec<u32>,
+    pub rules: Vec<u32>,
+    /// The ids of the categories whose budgets refer to the entity.
+    pub budgets: Vec<u32>,
+    /// The ids of the currencies with exchange rates against the entity.
+    pub exchange_rates: Vec<u32>,
+    /// Whether the entity is the base currency.
+    pub base_currency: bool,
+}
+
+impl Dependents {
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+            && self.categories.is_empty()
+            && self.flows.is_empty()
+            && self.transactions.is_empty()
+            && self.statements.is_empty()
+            && self.rules.is_empty()
+            && self.budgets.is_empty()
+            && self.exchange_rates.is_empty()
+            && !self.base_currency
+    }
+}
+
+impl std::fmt::Display for Dependents {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let counts = [
+            (self.accounts.len(), "accounts"),
+            (self.categories.len(), "categories"),
+            (self.flows.len(), "flows"),
+            (self.transactions.len(), "transactions"),
+            (self.statements.len(), "statements"),
+            (self.rules.len(), "rules"),
+            (self.budgets.len(), "budgets"),
+            (self.exchange_rates.len(), "exchange rate tables"),
+        ];
+        let mut parts: Vec<String> = counts
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, name)| format!("{} {}", count, name))
+            .collect();
+        if self.base_currency {
+            parts.push("the base currency setting".into());
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// `is_ancestor_or_self` returns whether `category_id` is `descendant_id` or one of its ancestors.
+fn is_ancestor_or_self(app_data: &AppData, category_id: u32, descendant_id: u32) -> bool {
+    let mut current = Some(descendant_id);
+    // Stop after visiting every category, in case the parents already form a cycle.
+    for _ in 0..=app_data.categories().len() {
+        match current {
+            Some(id) if id == category_id => return true,
+            Some(id) => current = app_data.categories().get(&id).and_then(|c| c.parent_id),
+            None => return false,
+        }
+    }
+    false
+}
+
+fn rule_uses_category(rule: &Rule, category_id: u32) -> bool {
+    rule.flows
+        .iter()
+        .any(|rule_flow| rule_flow.category_id == category_id)
+}
+
+pub fn dependents(app_data: &AppData, entity: EntityRef) -> Dependents {
+    let mut dependents = Dependents::default();
+    match entity {
+        EntityRef::Account(id) => {
+            dependents.transactions = app_data
+                .transactions()
+                .values()
+                .filter(|transaction| transaction.account_id == id)
+                .map(|transaction| transaction.id)
+                .collect();
+            dependents.statements = app_data
+                .statements()
+                .values()
+                .filter(|statement| statement.account_id == id)
+                .map(|statement| statement.id)
+                .collect();
+            dependents.rules = app_data
+                .rules()
+                .values()
+                .filter(|rule| rule.account_id == Some(id))
+                .map(|rule| rule.id)
+                .collect();
+        }
+        EntityRef::Category(id) => {
+            dependents.categories = app_data
+                .categories()
+                .values()
+                .filter(|category| category.parent_id == Some(id))
+                .map(|category| category.id)
+                .collect();
+            dependents.flows = app_data
+                .flows()
+                .values()
+                .filter(|flow| flow.category_id == id)
+                .map(|flow| flow.id)
+                .collect();
+            dependents.rules = app_data
+                .rules()
+                .values()
+                .filter(|rule| rule_uses_category(rule, id))
+                .map(|rule| rule.id)
+                .collect();
+            if app_data.budgets().contains_key(&id) {
+                dependents.budgets.push(id);
+            }
+        }
+        EntityRef::Currency(id) => {
+            dependents.accounts = app_data
+                .accounts()
+                .values()
+                .filter(|account| account.currency_id == id)
+                .map(|account| account.id)
+                .collect();
+            dependents.flows = app_data
+                .flows()
+                .values()
+                .filter(|flow| flow.currency_id == id)
+                .map(|flow| flow.id)
+                .collect();
+            dependents.transactions = app_data
+                .transactions()
+                .values()
+                .filter(|transaction| {
+                    transaction
+                        .original
+                        .is_some_and(|original| original.currency_id == id)
+                })
+                .map(|transaction| transaction.id)
+                .collect();
+            dependents.exchange_rates = app_data
+                .currencies()
+                .values()
+                .filter(|currency| currency.id != id && currency.exchange_rates.contains_key(&id))
+                .map(|currency| currency.id)
+                .collect();
+            dependents.base_currency = app_data.settings().base_currency_id == Some(id);
+        }
+    }
+    dependents
+}
+
+/// `check_integrity` fails if any of `referenced` doesn't exist, or any of `deleted` has been
+/// deleted but is still referred to.
+pub(super) fn check_integrity(
+    app_data: &AppData,
+    deleted: &[EntityRef],
+    referenced: &[EntityRef],
+) -> Result<()> {
+    if let Some(entity) = referenced.iter().find(|entity| !entity.exists(app_data)) {
+        return Err(Error::MissingEntity(*entity));
+    }
+    for entity in deleted {
+        if entity.exists(app_data) {
+            continue;
+        }
+        let dependents = dependents(app_data, *entity);
+        if !dependents.is_empty() {
+            return Err(Error::HasDependents(*entity, Box::new(dependents)));
+        }
+    }
+    Ok(())
+}
+
+/// A set of entities to delete together. Deleting transactions or flows also updates the groups
+/// they belong to; a group left without any transactions is deleted along with its flows.
+/// Deleting currencies removes the exchange rates against them and the original amounts in them,
+/// and unsets the base currency if it is one of them.
+#[derive(Default)]
+pub struct Deletion {
+    accounts: BTreeSet<u32>,
+    categories: BTreeSet<u32>,
+    currencies: BTreeSet<u32>,
+    flows: BTreeSet<u32>,
+    transactions: BTreeSet<u32>,
+    /// Transactions that only refer to a deleted currency through their original amounts, which
+    /// are cleared rather than the transactions deleted.
+    originals: BTreeSet<u32>,
+    statements: BTreeSet<u32>,
+    rules: BTreeSet<u32>,
+    budgets: BTreeSet<u32>,
+}
+
+impl Deletion {
+    pub fn transactions(transaction_ids: &[u32]) -> Self {
+        Self {
+            transactions: transaction_ids.iter().copied().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// `cascade` returns a deletion of `entity` and everything that depends on it, transitively.
+    pub fn cascade(app_data: &AppData, entity: EntityRef) -> Self {
+        let mut deletion = Self::default();
+        deletion.add_cascade(app_data, entity);
+        deletion
+    }
+
+    fn add_cascade(&mut self, app_data: &AppData, entity: EntityRef) {
+        let is_new = match entity {
+            EntityRef::Account(id) => self.accounts.insert(id),
+            EntityRef::Category(id) => self.categories.insert(id),
+            EntityRef::Currency(id) => self.currencies.insert(id),
+        };
+        if !is_new {
+            return;
+        }
+        let dependents = dependents(app_data, entity);
+        self.flows.extend(dependents.flows);
+        match entity {
+            EntityRef::Currency(_) => self.originals.extend(dependents.transactions),
+            EntityRef::Account(_) | EntityRef::Category(_) => {
+                self.transactions.extend(dependents.transactions)
+            }
+        }
+        self.statements.extend(dependents.statements);
+        self.rules.extend(dependents.rules);
+        self.budgets.extend(dependents.budgets);
+        for id in dependents.accounts {
+            self.add_cascade(app_data, EntityRef::Account(id));
+        }
+        for id in dependents.categories {
+            self.add_cascade(app_data, EntityRef::Category(id));
+        }
+    }
+
+    pub fn updates(self, app_data: &AppData) -> Vec<Update> {
+        let mut flows = self.flows;
+        let mut group_ids = BTreeSet::new();
+        for id in self.transactions.iter() {
+            if let Some(group_id) = app_data
+                .transactions()
+                .get(id)
+                .and_then(|transaction| transaction.transaction_group_id)
+            {
+                group_ids.insert(group_id);
+            }
+        }
+        for id in flows.iter() {
+            if let Some(flow) = app_data.flows().get(id) {
+                group_ids.insert(flow.transaction_group_id);
+            }
+        }
+
+        let mut updates = Vec::new();
+        let mut changed_transactions: BTreeMap<u32, Transaction> = BTreeMap::new();
+        // The other sides of transfers that are deleted are no longer transfers.
+        for id in self.transactions.iter() {
+            if let Some(other) = app_data
+                .transactions()
+                .get(id)
+                .and_then(|transaction| transaction.transfer_transaction_id)
+                .filter(|other_id| !self.transactions.contains(other_id))
+                .and_then(|other_id| app_data.transactions().get(&other_id))
+            {
+                changed_transactions
+                    .entry(other.id)
+                    .or_insert_with(|| other.clone())
+                    .transfer_transaction_id = None;
+            }
+        }
+        for id in self.originals.difference(&self.transactions) {
+            if let Some(transaction) = app_data.transactions().get(id) {
+                changed_transactions
+                    .entry(*id)
+                    .or_insert_with(|| transaction.clone())
+                    .original = None;
+            }
+        }
+        updates.extend(
+            changed_transactions
+                .into_values()
+                .map(Update::SetTransaction),
+        );
+        for group_id in group_ids {
+            let transaction_group = match app_data.transaction_groups().get(&group_id) {
+                Some(transaction_group) => transaction_group,
+                None => continue,
+            };
+            let transaction_ids: Vec<u32> = transaction_group
+                .transaction_ids
+                .iter()
+                .filter(|id| !self.transactions.contains(id))
+                .copied()
+                .collect();
+            if transaction_ids.is_empty() {
+                flows.extend(transaction_group.flow_ids.iter().copied());
+                updates.push(Update::DeleteTransactionGroup(group_id));
+            } else {
+                updates.push(Update::SetTransactionGroup(TransactionGroup {
+                    transaction_ids,
+                    flow_ids: transaction_group
+                        .flow_ids
+                        .iter()
+                        .filter(|id| !flows.contains(id))
+                        .copied()
+                        .collect(),
+                    ..transaction_group.clone()
+                }));
+            }
+        }
+        for currency in app_data.currencies().values() {
+            if self.currencies.contains(&currency.id)
+                || !self
+                    .currencies
+                    .iter()
+                    .any(|id| currency.exchange_rates.contains_key(id))
+            {
+                continue;
+            }
+            let mut currency = currency.clone();
+            currency
+                .exchange_rates
+                .retain(|id, _| !self.currencies.contains(id));
+            updates.push(Update::SetCurrency(currency));
+        }
+        if let Some(base_currency_id) = app_data.settings().base_currency_id {
+            if self.currencies.contains(&base_currency_id) {
+                updates.push(Update::SetSettings(Settings {
+                    base_currency_id: None,
+                    ..app_data.settings().clone()
+                }));
+            }
+        }
+        updates.extend(flows.into_iter().map(Update::DeleteFlow));
+        updates.extend(self.transactions.into_iter().map(Update::DeleteTransaction));
+        updates.extend(self.statements.into_iter().map(Update::DeleteStatement));
+        updates.extend(self.rules.into_iter().map(Update::DeleteRule));
+        updates.extend(self.budgets.into_iter().map(Update::DeleteBudget));
+        updates.extend(self.accounts.into_iter().map(Update::DeleteAccount));
+        updates.extend(self.categories.into_iter().map(Update::DeleteCategory));
+        updates.extend(self.currencies.into_iter().map(Update::DeleteCurrency));
+        updates
+    }
+}
+
+/// `reassign_updates` returns the updates that point everything that refers to `entity` at the
+/// entity of the same kind with id `to_id` instead, and then delete `entity`. Amounts aren't
+/// converted, so this fails with `Error::CannotReassign` if they would change currency: an account
+/// can only be reassigned to one in the same currency, and a currency only once no accounts, flows
+/// or original amounts are in it. It also fails if merging a category's budget allocations into
+/// those of `to_id` would overflow.
+pub fn reassign_updates(app_data: &AppData, entity: EntityRef, to_id: u32) -> Result<Vec<Update>> {
+    let dependents = dependents(app_data, entity);
+    let cannot_reassign = |reason: &str| Error::CannotReassign(entity, reason.into());
+    let mut updates = Vec::new();
+    match entity {
+        EntityRef::Account(id) => {
+            let currency_id = |id| app_data.accounts().get(&id).map(|a| a.currency_id);
+            if currency_id(id) != currency_id(to_id) {
+                return Err(cannot_reassign("the accounts are in different currencies"));
+            }
+            for transaction_id in dependents.transactions {
+                let mut transaction = app_data
+                    .transactions()
+                    .get(&transaction_id)
+                    .unwrap()
+                    .clone();
+                transaction.account_id = to_id;
+                // A transfer between the two accounts would be within one account, so it is
+                // unlinked.
+                if let Some(other) = transaction
+                    .transfer_transaction_id
+                    .and_then(|other_id| app_data.transactions().get(&other_id))
+                    .filter(|other| other.account_id == to_id)
+                {
+                    transaction.transfer_transaction_id = None;
+                    updates.push(Update::SetTransaction(Transaction {
+                        transfer_transaction_id: None,
+                        ..other.clone()
+                    }));
+                }
+                updates.push(Update::SetTransaction(transaction));
+            }
+            for statement_id in dependents.statements {
+                let mut statement = app_data.statements().get(&statement_id).unwrap().clone();
+                statement.account_id = to_id;
+                updates.push(Update::SetStatement(statement));
+            }
+            for rule_id in dependents.rules {
+                let mut rule = app_data.rules().get(&rule_id).unwrap().clone();
+                rule.account_id = Some(to_id);
+                updates.push(Update::SetRule(rule));
+            }
+            updates.push(Update::DeleteAccount(id));
+        }
+        EntityRef::Category(id) => {
+            let parent_id = app_data
+                .categories()
+                .get(&id)
+                .and_then(|category| category.parent_id);
+            for category_id in dependents.categories {
+                let mut category = app_data.categories().get(&category_id).unwrap().clone();
+                // Don't make the new category its own parent or descendant. The child it is under
+                // moves up instead.
+                category.parent_id = if is_ancestor_or_self(app_data, category_id, to_id) {
+                    parent_id
+                } else {
+                    Some(to_id)
+                };
+                updates.push(Update::SetCategory(category));
+            }
+            for flow_id in dependents.flows {
+                let mut flow = app_data.flows().get(&flow_id).unwrap().clone();
+                flow.category_id = to_id;
+                updates.push(Update::SetFlow(flow));
+            }
+            for rule_id in dependents.rules {
+                let mut rule = app_data.rules().get(&rule_id).unwrap().clone();
+                for rule_flow in rule.flows.iter_mut() {
+                    if rule_flow.category_id == id {
+                        rule_flow.category_id = to_id;
+                    }
+                }
+                updates.push(Update::SetRule(rule));
+            }
+            // The allocations move to the new category, adding to any it already has.
+            for budget_id in dependents.budgets {
+                let budget = app_data.budgets().get(&budget_id).unwrap();
+                let mut to_budget =
+                    app_data
+                        .budgets()
+                        .get(&to_id)
+                        .cloned()
+                        .unwrap_or_else(|| Budget {
+                            category_id: to_id,
+                            rollover: budget.rollover,
+                            allocations: Vec::new(),
+                        });
+                for allocation in budget.allocations.iter() {
+                    match to_budget
+                        .allocations
+                        .binary_search_by_key(&allocation.month, |a| a.month)
+                    {
+                        Ok(index) => {
+                            let amount = &mut to_budget.allocations[index].amount;
+                            *amount = amount.checked_add(allocation.amount).ok_or_else(|| {
+                                cannot_reassign("the merged budget allocations are too large")
+                            })?;
+                        }
+                        Err(index) => to_budget.allocations.insert(index, allocation.clone()),
+                    }
+                }
+                updates.push(Update::DeleteBudget(budget_id));
+                updates.push(Update::SetBudget(to_budget));
+            }
+            updates.push(Update::DeleteCategory(id));
+        }
+        EntityRef::Currency(id) => {
+            if !dependents.accounts.is_empty()
+                || !dependents.flows.is_empty()
+                || !dependents.transactions.is_empty()
+            {
+                return Err(cannot_reassign(
+                    "accounts, flows or original amounts are in it, and would need converting",
+                ));
+            }
+            // Budgets are in the base currency.
+            let has_allocations = app_data
+                .budgets()
+                .values()
+                .any(|budget| !budget.allocations.is_empty());
+            if dependents.base_currency && has_allocations {
+                return Err(cannot_reassign(
+                    "it is the base currency, which budgets are in, and they would need converting",
+                ));
+            }
+            // Exchange rates against the currency become rates against the new one, unless there
+            // already are some.
+            let mut currencies: BTreeMap<u32, Currency> = BTreeMap::new();
+            for currency_id in dependents.exchange_rates {
+                let mut currency = app_data.currencies().get(&currency_id).unwrap().clone();
+                let rates = currency.exchange_rates.remove(&id).unwrap();
+                if currency_id != to_id {
+                    currency.exchange_rates.entry(to_id).or_insert(rates);
+                }
+                currencies.insert(currency_id, currency);
+            }
+            // Rates relative to the base currency are re-expressed relative to the new one.
+            if dependents.base_currency {
+                updates.push(Update::SetSettings(Settings {
+                    base_currency_id: Some(to_id),
+                    ..app_data.settings().clone()
+                }));
+                let base_rate = app_data
+                    .currencies()
+                    .get(&to_id)
+                    .map_or(0.0, |currency| currency.equivalent_base);
+                if base_rate.is_finite() && base_rate > 0.0 {
+                    for currency in app_data.currencies().values() {
+                        currencies
+                            .entry(currency.id)
+                            .or_insert_with(|| currency.clone())
+                            .equivalent_base /= base_rate;
+                    }
+                }
+            }
+            currencies.remove(&id);
+            updates.extend(currencies.into_values().map(Update::SetCurrency));
+            updates.push(Update::DeleteCurrency(id));
+        }
+    }
+    Ok(updates)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::naive::NaiveDate as Date;
+
+    use super::*;
+    use crate::data::test_fixtures::{account, app_data_with, category, currency};
+    use crate::data::{ExchangeRate, Flow, ForeignAmount, Money};
+
+    fn transaction(id: u32, account_id: u32, original: Option<ForeignAmount>) -> Transaction {
+        Transaction {
+            id,
+            account_id,
+            date: Date::from_ymd_opt(2023, 5, 12).unwrap(),
+            description: format!("Transaction {}", id),
+            amount: Money::from_minor(-1000),
+            transaction_group_id: Some(id),
+            statement_id: None,
+            original,
+            transfer_transaction_id: None,
+        }
+    }
+
+    fn flow(id: u32, category_id: u32) -> Flow {
+        Flow {
+            id,
+            category_id,
+            date: Date::from_ymd_opt(2023, 5, 12).unwrap(),
+            description: None,
+            amount: Money::from_minor(-1000),
+            currency_id: 0,
+            amortization_type: None,
+            amortization_length: None,
+            transaction_group_id: id,
+        }
+    }
+
+    /// USD (0) and EUR (1); a USD account (0) and an EUR account (1); categories 0, 1 under 0 and
+    /// 2 under 1. Transaction 0 is in the USD account, originally in EUR, and transaction 1 is in
+    /// the EUR account. Each is in a group of its own with a flow in category 1.
+    fn sample() -> AppData {
+        let mut eur = currency(1, "EUR");
+        eur.exchange_rates.insert(
+            0,
+            vec![ExchangeRate {
+                date: Date::from_ymd_opt(2023, 5, 1).unwrap(),
+                rate: 1.1,
+            }],
+        );
+        let mut updates = vec![
+            Update::SetCurrency(currency(0, "USD")),
+            Update::SetCurrency(eur),
+            Update::SetSettings(Settings {
+                base_currency_id: Some(0),
+                ..Default::default()
+            }),
+            Update::SetCategory(category(0, "Category 0", None)),
+            Update::SetCategory(category(1, "Category 1", Some(0))),
+            Update::SetCategory(category(2, "Category 2", Some(1))),
+        ];
+        for (id, currency_id) in [(0, 0), (1, 1)] {
+            updates.push(Update::SetAccount(account(id, currency_id, true)));
+        }
+        let original = ForeignAmount {
+            amount: Money::from_minor(-900),
+            currency_id: 1,
+        };
+        for transaction in [transaction(0, 0, Some(original)), transaction(1, 1, None)] {
+            let id = transaction.id;
+            updates.push(Update::SetTransaction(transaction));
+            updates.push(Update::SetFlow(flow(id, 1)));
+            updates.push(Update::SetTransactionGroup(TransactionGroup {
+                id,
+                transaction_ids: vec![id],
+                flow_ids: vec![id],
+            }));
+        }
+        app_data_with(updates)
+    }
+
+    #[test]
+    fn rejects_deletes_that_leave_dependents() {
+        let mut app_data = sample();
+        let result = app_data.perform_update("Delete", vec![Update::DeleteCurrency(1)]);
+        match result {
+            Err(Error::HasDependents(EntityRef::Currency(1), dependents)) => {
+                assert_eq!(dependents.accounts, [1]);
+                assert_eq!(dependents.transactions, [0]);
+                assert!(dependents.flows.is_empty());
+                assert!(!dependents.base_currency);
+            }
+            _ => panic!("deleting a currency in use should fail"),
+        }
+        assert!(app_data.currencies().contains_key(&1));
+        assert!(matches!(
+            app_data.perform_update("Delete", vec![Update::DeleteCategory(1)]),
+            Err(Error::HasDependents(EntityRef::Category(1), _))
+        ));
+        app_data
+            .perform_update("Delete", vec![Update::DeleteCategory(2)])
+            .unwrap();
+    }
+
+    #[test]
+    fn rejects_updates_that_refer_to_missing_entities() {
+        let mut app_data = sample();
+        let mut moved = app_data.transactions()[&1].clone();
+        moved.account_id = 5;
+        assert!(matches!(
+            app_data.perform_update("Edit", vec![Update::SetTransaction(moved)]),
+            Err(Error::MissingEntity(EntityRef::Account(5)))
+        ));
+        assert_eq!(app_data.transactions()[&1].account_id, 1);
+        assert!(matches!(
+            app_data.perform_update("Edit", vec![Update::SetFlow(flow(0, 7))]),
+            Err(Error::MissingEntity(EntityRef::Category(7)))
+        ));
+        assert!(matches!(
+            app_data.perform_update(
+                "Edit",
+                vec![Update::SetCategory(category(3, "Category 3", Some(9)))]
+            ),
+            Err(Error::MissingEntity(EntityRef::Category(9)))
+        ));
+        assert!(!app_data.categories().contains_key(&3));
+    }
+
+    #[test]
+    fn cascading_a_currency_clears_original_amounts() {
+        let mut app_data = sample();
+        let updates = Deletion::cascade(&app_data, EntityRef::Currency(1)).updates(&app_data);
+        app_data.perform_update("Delete", updates).unwrap();
+        assert!(!app_data.currencies().contains_key(&1));
+        assert!(!app_data.accounts().contains_key(&1));
+        assert_eq!(app_data.transactions().keys().collect::<Vec<_>>(), [&0]);
+        assert_eq!(app_data.transactions()[&0].original, None);
+        // The group of the deleted transaction goes with it.
+        assert_eq!(
+            app_data.transaction_groups().keys().collect::<Vec<_>>(),
+            [&0]
+        );
+        assert_eq!(app_data.flows().keys().collect::<Vec<_>>(), [&0]);
+    }
+
+    #[test]
+    fn cascading_a_category_deletes_its_descendants_and_flows() {
+        let mut app_data = sample();
+        let updates = Deletion::cascade(&app_data, EntityRef::Category(1)).updates(&app_data);
+        app_data.perform_update("Delete", updates).unwrap();
+        assert_eq!(app_data.categories().keys().collect::<Vec<_>>(), [&0]);
+        assert!(app_data.flows().is_empty());
+        assert_eq!(app_data.transactions().len(), 2);
+        assert_eq!(
+            app_data.transaction_groups()[&0].flow_ids,
+            Vec::<u32>::new()
+        );
+    }
+
+    #[test]
+    fn reassigning_a_category_to_its_descendant_moves_the_child_up() {
+        let mut app_data = sample();
+        let updates = reassign_updates(&app_data, EntityRef::Category(1), 2).unwrap();
+        app_data.perform_update("Reassign", updates).unwrap();
+        assert!(!app_data.categories().contains_key(&1));
+        assert_eq!(app_data.categories()[&2].parent_id, Some(0));
+        assert!(app_data.flows().values().all(|flow| flow.category_id == 2));
+    }
+
+    #[test]
+    fn reassigning_only_moves_amounts_within_a_currency() {
+        let mut app_data = sample();
+        assert!(matches!(
+            reassign_updates(&app_data, EntityRef::Account(1), 0),
+            Err(Error::CannotReassign(EntityRef::Account(1), _))
+        ));
+        assert!(matches!(
+            reassign_updates(&app_data, EntityRef::Currency(0), 1),
+            Err(Error::CannotReassign(EntityRef::Currency(0), _))
+        ));
+
+        // Once nothing is in USD, its rates and the base currency setting can move.
+        let mut updates = Deletion::cascade(&app_data, EntityRef::Account(0)).updates(&app_data);
+        updates.push(Update::SetFlow(Flow {
+            currency_id: 1,
+            ..flow(1, 1)
+        }));
+        app_data.perform_update("Delete", updates).unwrap();
+        let updates = reassign_updates(&app_data, EntityRef::Currency(0), 1).unwrap();
+        app_data.perform_update("Reassign", updates).unwrap();
+        assert!(!app_data.currencies().contains_key(&0));
+        assert!(app_data.currencies()[&1].exchange_rates.is_empty());
+        assert_eq!(app_data.settings().base_currency_id, Some(1));
+    }
+
+    #[test]
+    fn reassigning_an_account_unlinks_transfers_with_the_new_one() {
+        let mut app_data = sample();
+        let mut updates = vec![Update::SetAccount(account(2, 0, true))];
+        for (id, account_id, other_id) in [(2, 0, 3), (3, 2, 2)] {
+            updates.push(Update::SetTransaction(Transaction {
+                transaction_group_id: None,
+                transfer_transaction_id: Some(other_id),
+                ..transaction(id, account_id, None)
+            }));
+        }
+        app_data.perform_update("Transfer", updates).unwrap();
+
+        let updates = reassign_updates(&app_data, EntityRef::Account(0), 2).unwrap();
+        app_data.perform_update("Reassign", updates).unwrap();
+        assert!(!app_data.accounts().contains_key(&0));
+        for id in [0, 2, 3] {
+            let transaction = &app_data.transactions()[&id];
+            assert_eq!(transaction.account_id, 2);
+            assert_eq!(transaction.transfer_transaction_id, None);
+        }
+    }
+}
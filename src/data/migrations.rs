@@ -0,0 +1,86 @@
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::result::Result;
+
+/// The version of the save format that this version of Ebbify writes. Changing the format means
+/// bumping this, adding a migration from the previous version and adding a fixture for it.
+pub const FORMAT_VERSION: u32 = 2;
+
+type Migration = fn(&mut Value) -> Result<()>;
+
+/// `MIGRATIONS[n]` converts file data from version `n` to version `n + 1`.
+const MIGRATIONS: [Migration; FORMAT_VERSION as usize] = [migrate_v0, migrate_v1];
+
+/// `migrate` converts file data written in format `version` to the current format. The migrations
+/// only convert the entities, so any saved undo history from an older format is dropped rather
+/// than replayed in a format it wasn't written in.
+pub fn migrate(data: &mut Value, version: u32) -> Result<()> {
+    if version > FORMAT_VERSION {
+        return Err(Error::NewerFormat(version));
+    }
+    for migration in MIGRATIONS[version as usize..].iter() {
+        migration(data)?;
+    }
+    if version < FORMAT_VERSION {
+        if let Some(data) = data.as_object_mut() {
+            data.remove("undo_stack");
+            data.remove("redo_stack");
+        }
+    }
+    Ok(())
+}
+
+/// The entities of one kind, or nothing if the file doesn't have any.
+fn entities<'a>(data: &'a mut Value, key: &str) -> Result<&'a mut [Value]> {
+    match data.get_mut(key) {
+        Some(Value::Array(entities)) => Ok(entities),
+        Some(Value::Null) | None => Ok(&mut []),
+        Some(_) => Err(Error::Migration(format!("{} is not a list", key))),
+    }
+}
+
+/// Version 0 files have no header. Currency equivalents were in USD, so USD becomes the base
+/// currency, and transaction group ids on transactions were signed.
+fn migrate_v0(data: &mut Value) -> Result<()> {
+    let mut usd_id = None;
+    for currency in entities(data, "currencies")? {
+        if let Some(currency) = currency.as_object_mut() {
+            if let Some(equivalent) = currency.remove("equivalent_usd") {
+                currency.entry("equivalent_base").or_insert(equivalent);
+            }
+            if currency.get("code").and_then(Value::as_str) == Some("USD") {
+                usd_id = currency.get("id").cloned();
+            }
+        }
+    }
+    if let (Some(usd_id), Some(data)) = (usd_id, data.as_object_mut()) {
+        let settings = data.entry("settings").or_insert(Value::Null);
+        if settings.is_null() {
+            *settings = Value::Object(Default::default());
+        }
+        settings
+            .as_object_mut()
+            .ok_or_else(|| Error::Migration("settings is not an object".into()))?
+            .entry("base_currency_id")
+            .or_insert(usd_id);
+    }
+    for transaction in entities(data, "transactions")? {
+        if let Some(transaction) = transaction.as_object_mut() {
+            let is_negative = transaction
+                .get("transaction_group_id")
+                .and_then(Value::as_i64)
+                .is_some_and(|id| id < 0);
+            if is_negative {
+                transaction.insert("transaction_group_id".into(), Value::Null);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Version 2 added the undo history, which version 1 files don't have, so there is nothing to
+/// convert.
+fn migrate_v1(_data: &mut Value) -> Result<()> {
+    Ok(())
+}
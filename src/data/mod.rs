@@ -1,14 +1,48 @@
+mod account_index;
+mod amortization;
+mod budgets;
 mod cached_value;
+mod derived_data;
+mod encryption;
+mod exchange_rates;
+mod integrity;
+mod migrations;
 mod models;
+mod money;
+mod net_worth;
 mod price;
+mod reports;
+mod rules;
 mod save_file;
+mod statement_import;
+#[cfg(test)]
+mod test_fixtures;
+mod transaction_groups;
+mod transfers;
 mod updates;
 
-pub use cached_value::CachedValue;
+pub use amortization::{default_amortization, AccrualPeriod, MAX_AMORTIZATION_LENGTH};
+pub use budgets::{month_start, BudgetReport};
+pub use cached_value::{CachedValue, Dependency};
+pub use exchange_rates::{base_currency_updates, convert, merge_rates, parse_rates_csv, Converter};
+pub use integrity::{reassign_updates, Deletion, Dependents, EntityRef};
 pub use models::{
-    next_id, Account, AmortizationType, AppData, Balance, Category, CategoryNode, Currency,
-    FileData, Flow, Transaction, TransactionGroup,
+    next_id, Account, AmortizationType, AppData, Balance, Budget, Category, CategoryNode, Currency,
+    ExchangeRate, FileData, Flow, ForeignAmount, NumberFormat, Rollover, Rule, RuleFlow, Settings,
+    Statement, SymbolPosition, Transaction, TransactionGroup,
+};
+pub use money::Money;
+pub use net_worth::{NetWorthOptions, NetWorthSeries, SampleInterval};
+pub use price::{parse_price, NumericPrice, Price};
+pub use reports::{PeriodLength, ReportBasis, ReportOptions, SpendingReport};
+pub use rules::{RuleEngine, RuleMatch};
+pub use save_file::{backups, SaveFile};
+pub use statement_import::{parse_statement, ParsedStatement, StatementImport};
+pub use transaction_groups::{
+    delete_transactions_updates, is_balanced, transaction_group_updates, unallocated_amounts,
+};
+pub use transfers::{
+    link_transfer_updates, net_worth_change, suggest_transfers, transfer_fee, transfer_rate,
+    unlink_transfer_updates, TransferSuggestion,
 };
-pub use price::{NumericPrice, Price};
-pub use save_file::SaveFile;
 pub use updates::{Update, Updates};
@@ -1,15 +1,19 @@
 use chrono::naive::NaiveDate as Date;
 use std::collections::{BTreeMap, BTreeSet};
-use std::ops::Bound::{Excluded, Included};
 
 use serde::{Deserialize, Serialize};
 
-use super::{Update, Updates};
+use super::account_index::AccountIndex;
+use super::cached_value::Versions;
+use super::derived_data::{Change, DerivedData};
+use super::integrity::check_integrity;
+use super::{convert, Money, Update, Updates};
+use crate::result::Result;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Balance {
     pub date: Date,
-    pub amount: i32,
+    pub amount: Money,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -27,41 +31,36 @@ impl Account {
         if index == 0 {
             Balance {
                 date: Date::MIN,
-                amount: 0,
+                amount: Money::ZERO,
             }
         } else {
             self.balances[index - 1].clone()
         }
     }
 
-    pub fn balance_on_date(&self, app_data: &AppData, date: Date) -> i32 {
+    /// `balance_on_date` returns `None` if the balance is too large to represent.
+    pub fn balance_on_date(&self, app_data: &AppData, date: Date) -> Option<Money> {
         // It would be faster to use the balance for the date if it exists, but then it would not
         // be possible to show the computed balance if it differs from the balance on that date
         // (since the computed balance would always just be the exact value on that date)
         let Balance {
             date: balance_date,
-            mut amount,
+            amount,
         } = self.latest_balance_before(date);
-        for (_, transactions_on_date) in app_data
-            .transactions_by_date()
-            .range((Excluded(balance_date), Included(date)))
-        {
-            for transaction_id in transactions_on_date {
-                let transaction = app_data.transactions().get(transaction_id).unwrap();
-                if transaction.account_id == self.id {
-                    amount += transaction.amount;
-                }
+        match app_data.account_index(self.id) {
+            Some(account_index) => {
+                amount.checked_add(account_index.total_between(balance_date, date)?)
             }
+            None => Some(amount),
         }
-        amount
     }
 
-    pub fn current_amount(&self, app_data: &AppData) -> i32 {
+    pub fn current_amount(&self, app_data: &AppData) -> Option<Money> {
         self.balance_on_date(app_data, Date::MAX)
     }
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum AmortizationType {
     Linear,
     Declining,
@@ -78,13 +77,74 @@ pub struct Category {
     pub default_amortization_length: Option<i32>,
 }
 
+/// How the digits of amounts are grouped and separated, which varies by locale.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum NumberFormat {
+    /// 1,234.56
+    #[default]
+    CommaPeriod,
+    /// 1.234,56
+    PeriodComma,
+    /// 1 234,56
+    SpaceComma,
+    /// 1'234.56
+    ApostrophePeriod,
+}
+
+impl NumberFormat {
+    pub fn thousands_separator(&self) -> char {
+        match self {
+            Self::CommaPeriod => ',',
+            Self::PeriodComma => '.',
+            Self::SpaceComma => ' ',
+            Self::ApostrophePeriod => '\'',
+        }
+    }
+
+    pub fn decimal_separator(&self) -> char {
+        match self {
+            Self::CommaPeriod | Self::ApostrophePeriod => '.',
+            Self::PeriodComma | Self::SpaceComma => ',',
+        }
+    }
+}
+
+/// Where a currency's symbol goes relative to the number.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum SymbolPosition {
+    /// $1.00
+    #[default]
+    Prefix,
+    /// $ 1.00
+    PrefixSpaced,
+    /// 1.00$
+    Suffix,
+    /// 1.00 $
+    SuffixSpaced,
+}
+
+/// One major unit of a currency was worth `rate` major units of another currency on `date`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct ExchangeRate {
+    pub date: Date,
+    pub rate: f64,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Currency {
     pub id: u32,
     pub code: String,
     pub major: i32,
-    pub equivalent_usd: f32,
+    /// The value of one major unit in the base currency, used when there are no exchange rates.
+    pub equivalent_base: f32,
     pub symbol: String,
+    #[serde(default)]
+    pub number_format: NumberFormat,
+    #[serde(default)]
+    pub symbol_position: SymbolPosition,
+    /// Rates against other currencies, keyed by their ids. Each table is sorted by date.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub exchange_rates: BTreeMap<u32, Vec<ExchangeRate>>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -94,10 +154,11 @@ pub struct Flow {
     pub date: Date,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub description: Option<String>,
-    pub amount: i32,
+    pub amount: Money,
     pub currency_id: u32,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub amortization_type: Option<AmortizationType>,
+    /// The number of months the flow is spread over, if it is amortized.
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub amortization_length: Option<i32>,
     pub transaction_group_id: u32,
@@ -110,14 +171,184 @@ pub struct TransactionGroup {
     pub flow_ids: Vec<u32>,
 }
 
+/// An amount in a currency other than the account's, such as the price of a purchase abroad.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct ForeignAmount {
+    pub amount: Money,
+    pub currency_id: u32,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Transaction {
     pub id: u32,
     pub account_id: u32,
     pub date: Date,
     pub description: String,
-    pub amount: i32,
-    pub transaction_group_id: Option<i32>,
+    /// The amount that settled in the account's currency.
+    pub amount: Money,
+    pub transaction_group_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub statement_id: Option<u32>,
+    /// The amount before it was converted to the account's currency, if it was.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub original: Option<ForeignAmount>,
+    /// The other side of the transfer between accounts that this is part of, if it is. Transfers
+    /// aren't spending or income, so they have no flows.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub transfer_transaction_id: Option<u32>,
+}
+
+impl Transaction {
+    /// The number of major units of the account's currency that each major unit of the original
+    /// currency settled at.
+    pub fn implied_rate(&self, app_data: &AppData) -> Option<f64> {
+        let original = self.original?;
+        let account_currency = app_data.account_currency(self.account_id)?;
+        let original_currency = app_data.currencies().get(&original.currency_id)?;
+        if original.amount == Money::ZERO {
+            return None;
+        }
+        Some(
+            (self.amount.minor() as f64 / account_currency.major as f64)
+                / (original.amount.minor() as f64 / original_currency.major as f64),
+        )
+    }
+
+    /// `fx_fee` is how much less the transaction settled at than the original amount would be
+    /// worth at the exchange rate on its date, i.e. the cost of the conversion.
+    pub fn fx_fee(&self, app_data: &AppData) -> Option<Money> {
+        let original = self.original?;
+        let account_currency = app_data.account_currency(self.account_id)?;
+        let original_currency = app_data.currencies().get(&original.currency_id)?;
+        convert(
+            app_data,
+            original.amount,
+            original_currency,
+            account_currency,
+            self.date,
+        )?
+        .checked_sub(self.amount)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Statement {
+    pub id: u32,
+    pub account_id: u32,
+    pub start_date: Date,
+    pub end_date: Date,
+    pub import_date: Date,
+    /// Hash of the parsed statement contents, used to detect reimports of the same statement.
+    #[serde(default)]
+    pub fingerprint: u64,
+}
+
+/// A flow that a `Rule` creates for each matching transaction.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RuleFlow {
+    pub category_id: u32,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub description: Option<String>,
+    /// The amount of the flow, in the currency of the transaction's account. At most one flow of
+    /// a rule may leave this empty, and that flow receives the rest of the transaction amount.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub amount: Option<Money>,
+    /// Leaving this empty gives the flow the default amortization of its category.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub amortization_type: Option<AmortizationType>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub amortization_length: Option<i32>,
+}
+
+/// A rule for automatically creating flows for transactions. Every condition that is set must
+/// match for the rule to apply. When several rules match, the one with the lowest id wins.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Rule {
+    pub id: u32,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub description_regex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub min_amount: Option<Money>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub max_amount: Option<Money>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub account_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub start_date: Option<Date>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub end_date: Option<Date>,
+    pub flows: Vec<RuleFlow>,
+}
+
+/// What happens to the money left in a budget at the end of a month.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Rollover {
+    /// Leftover money carries into the next month; overspending is covered from the money left to
+    /// be budgeted.
+    #[default]
+    CarrySurplus,
+    /// Overspending is taken from the next month; leftover money goes back to be budgeted.
+    CarryDeficit,
+    /// Every month starts from nothing, and the difference goes back to be budgeted.
+    Reset,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BudgetAllocation {
+    /// The first day of the month.
+    pub month: Date,
+    pub amount: Money,
+}
+
+/// The money set aside for a category each month, in the base currency. Flows count towards the
+/// budget of their category, or of its nearest ancestor that has one.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Budget {
+    pub category_id: u32,
+    pub rollover: Rollover,
+    /// Sorted by month.
+    pub allocations: Vec<BudgetAllocation>,
+}
+
+impl Budget {
+    pub fn allocation(&self, month: Date) -> Money {
+        match self
+            .allocations
+            .binary_search_by_key(&month, |allocation| allocation.month)
+        {
+            Ok(index) => self.allocations[index].amount,
+            Err(_) => Money::ZERO,
+        }
+    }
+
+    /// `set_allocation` sets the allocation for `month`, removing it if `amount` is zero.
+    pub fn set_allocation(&mut self, month: Date, amount: Money) {
+        match self
+            .allocations
+            .binary_search_by_key(&month, |allocation| allocation.month)
+        {
+            Ok(index) if amount == Money::ZERO => {
+                self.allocations.remove(index);
+            }
+            Ok(index) => self.allocations[index].amount = amount,
+            Err(_) if amount == Money::ZERO => (),
+            Err(index) => self
+                .allocations
+                .insert(index, BudgetAllocation { month, amount }),
+        }
+    }
+}
+
+/// Settings that are saved with the file.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Settings {
+    /// The currency that totals and reports are expressed in.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub base_currency_id: Option<u32>,
+    /// Whether the undo history is saved with the file, so edits can be undone after reopening it.
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub save_history: bool,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -128,6 +359,19 @@ pub struct FileData {
     flows: Vec<Flow>,
     transactions: Vec<Transaction>,
     transaction_groups: Vec<TransactionGroup>,
+    #[serde(default)]
+    statements: Vec<Statement>,
+    #[serde(default)]
+    rules: Vec<Rule>,
+    #[serde(default)]
+    budgets: Vec<Budget>,
+    #[serde(default)]
+    settings: Settings,
+    /// The undo and redo stacks, if `settings.save_history` is set.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    undo_stack: Vec<Updates>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    redo_stack: Vec<Updates>,
 }
 
 #[derive(Serialize)]
@@ -138,6 +382,14 @@ pub struct FileDataBorrowed<'a> {
     flows: Vec<&'a Flow>,
     transactions: Vec<&'a Transaction>,
     transaction_groups: Vec<&'a TransactionGroup>,
+    statements: Vec<&'a Statement>,
+    rules: Vec<&'a Rule>,
+    budgets: Vec<&'a Budget>,
+    settings: &'a Settings,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    undo_stack: &'a [Updates],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    redo_stack: &'a [Updates],
 }
 
 impl FileData {
@@ -204,20 +456,22 @@ impl FileData {
       "id": 0,
       "code": "USD",
       "major": 100,
-      "equivalent_usd": 1,
+      "equivalent_base": 1,
       "symbol": "$"
     },
     {
       "id": 1,
       "code": "CAD",
       "major": 100,
-      "equivalent_usd": 0.73214,
+      "equivalent_base": 0.73214,
       "symbol": "$"
     }
   ],
   "flows": [],
   "transactions": [],
-  "transaction_groups": []
+  "transaction_groups": [],
+  "statements": [],
+  "rules": []
 }
         "#;
 
@@ -228,9 +482,12 @@ impl FileData {
                 id,
                 account_id: id % 3,
                 date: Date::from_ymd_opt(2023, 5, id % 31 + 1).unwrap(),
-                description: format!("Transaction {}", id).into(),
-                amount: ((id as i32) % 10) * 10 - 20,
+                description: format!("Transaction {}", id),
+                amount: Money::from_minor(((id as i64) % 10) * 10 - 20),
                 transaction_group_id: None,
+                statement_id: None,
+                original: None,
+                transfer_transaction_id: None,
             });
         }
 
@@ -238,25 +495,22 @@ impl FileData {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct CategoryNode {
     pub id: u32,
     pub children: Vec<CategoryNode>,
 }
 
 impl CategoryNode {
-    fn new(id: u32, children_map: &BTreeMap<u32, Vec<u32>>) -> Self {
+    pub(super) fn new(id: u32, children_map: &BTreeMap<u32, Vec<u32>>) -> Self {
         Self {
             id,
-            children: children_map.get(&id).map_or_else(
-                || Vec::new(),
-                |children| {
-                    children
-                        .iter()
-                        .map(|id| CategoryNode::new(*id, children_map))
-                        .collect()
-                },
-            ),
+            children: children_map.get(&id).map_or_else(Vec::new, |children| {
+                children
+                    .iter()
+                    .map(|id| CategoryNode::new(*id, children_map))
+                    .collect()
+            }),
         }
     }
 }
@@ -269,14 +523,22 @@ pub struct AppData {
     pub(super) flows: BTreeMap<u32, Flow>,
     pub(super) transactions: BTreeMap<u32, Transaction>,
     pub(super) transaction_groups: BTreeMap<u32, TransactionGroup>,
+    pub(super) statements: BTreeMap<u32, Statement>,
+    pub(super) rules: BTreeMap<u32, Rule>,
+    /// Keyed by category id.
+    pub(super) budgets: BTreeMap<u32, Budget>,
+    pub(super) settings: Settings,
     // Undo and redo
     modification_count: u32,
     max_modification_count: u32,
     undo_stack: Vec<Updates>,
     redo_stack: Vec<Updates>,
+    // Serialized batches of updates applied since the journal was last written
+    journal: Vec<String>,
+    // Versions of the data, for invalidating caches
+    versions: Versions,
     // Derived data structures
-    category_trees: Vec<CategoryNode>,
-    transactions_by_date: BTreeMap<Date, BTreeSet<u32>>,
+    derived_data: DerivedData,
 }
 
 #[allow(dead_code)]
@@ -289,12 +551,17 @@ impl AppData {
             flows: Default::default(),
             transactions: Default::default(),
             transaction_groups: Default::default(),
+            statements: Default::default(),
+            rules: Default::default(),
+            budgets: Default::default(),
+            settings: Default::default(),
             modification_count: 0,
             max_modification_count: 0,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
-            category_trees: Vec::new(),
-            transactions_by_date: Default::default(),
+            journal: Vec::new(),
+            versions: Default::default(),
+            derived_data: Default::default(),
         }
     }
 
@@ -310,86 +577,54 @@ impl AppData {
                 .into_iter()
                 .map(|x| (x.id, x))
                 .collect(),
-            modification_count: 0,
-            max_modification_count: 0,
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
-            category_trees: Vec::new(),
-            transactions_by_date: Default::default(),
+            statements: data.statements.into_iter().map(|x| (x.id, x)).collect(),
+            rules: data.rules.into_iter().map(|x| (x.id, x)).collect(),
+            budgets: data
+                .budgets
+                .into_iter()
+                .map(|x| (x.category_id, x))
+                .collect(),
+            settings: data.settings,
+            // Every state in the saved history needs its own modification count.
+            modification_count: data.undo_stack.len() as u32,
+            max_modification_count: (data.undo_stack.len() + data.redo_stack.len()) as u32,
+            undo_stack: data.undo_stack,
+            redo_stack: data.redo_stack,
+            journal: Vec::new(),
+            versions: Default::default(),
+            derived_data: Default::default(),
         };
-        t.recompute_category_trees();
-        t.recompute_transactions_by_date();
+        t.derived_data = DerivedData::build(&t);
         t
     }
 
-    pub(super) fn recompute_category_trees(&mut self) {
-        let mut roots: Vec<u32> = Vec::new();
-        let mut children_map: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
-        for (id, category) in self.categories.iter() {
-            match category.parent_id {
-                Some(parent_id) => children_map
-                    .entry(parent_id)
-                    .or_insert_with(|| Vec::new())
-                    .push(*id),
-                None => roots.push(*id),
-            }
-        }
-        self.category_trees = roots
-            .into_iter()
-            .map(|id| CategoryNode::new(id, &children_map))
-            .collect();
-    }
-
-    pub(super) fn recompute_transactions_by_date(&mut self) {
-        self.transactions_by_date = BTreeMap::new();
-        for transaction in self.transactions.values() {
-            self.transactions_by_date
-                .entry(transaction.date)
-                .or_insert_with(|| BTreeSet::new())
-                .insert(transaction.id);
-        }
-    }
-
-    pub(super) fn insert_transaction(&mut self, transaction: Transaction) -> Option<Transaction> {
-        let id = transaction.id;
-        let date = transaction.date;
-        self.transactions_by_date
-            .entry(date)
-            .or_insert_with(|| BTreeSet::new())
-            .insert(id);
-        let old_transaction = self.transactions.insert(id, transaction);
-        match &old_transaction {
-            Some(old_transaction) => {
-                if old_transaction.date != date {
-                    self.transactions_by_date
-                        .get_mut(&old_transaction.date)
-                        .unwrap()
-                        .remove(&id);
-                }
-            }
-            None => (),
-        }
-        old_transaction
+    /// `apply_change` brings the derived data structures and versions up to date after `change`.
+    pub(super) fn apply_change(&mut self, change: Change) {
+        let dependencies = change.dependencies(self);
+        self.versions.bump(&dependencies);
+        let mut derived_data = std::mem::take(&mut self.derived_data);
+        derived_data.apply(self, &change);
+        self.derived_data = derived_data;
     }
 
-    pub(super) fn remove_transaction(&mut self, id: u32) -> Option<Transaction> {
-        let old_transaction = self.transactions.remove(&id);
-        match &old_transaction {
-            Some(old_transaction) => {
-                self.transactions_by_date
-                    .get_mut(&old_transaction.date)
-                    .unwrap()
-                    .remove(&id);
-            }
-            None => (),
-        }
-        old_transaction
+    /// In debug builds, `check_derived_data` panics if the derived data structures differ from
+    /// what a full rebuild would produce.
+    pub(super) fn check_derived_data(&self) {
+        debug_assert_eq!(
+            self.derived_data,
+            DerivedData::build(self),
+            "derived data is out of sync"
+        );
     }
 
     pub fn modification_count(&self) -> u32 {
         self.modification_count
     }
 
+    pub(super) fn versions(&self) -> &Versions {
+        &self.versions
+    }
+
     pub fn file_data<'a>(&'a self) -> FileDataBorrowed<'a> {
         FileDataBorrowed {
             accounts: self.accounts.values().collect(),
@@ -398,45 +633,103 @@ impl AppData {
             flows: self.flows.values().collect(),
             transactions: self.transactions.values().collect(),
             transaction_groups: self.transaction_groups.values().collect(),
+            statements: self.statements.values().collect(),
+            rules: self.rules.values().collect(),
+            budgets: self.budgets.values().collect(),
+            settings: &self.settings,
+            undo_stack: if self.settings.save_history {
+                &self.undo_stack
+            } else {
+                &[]
+            },
+            redo_stack: if self.settings.save_history {
+                &self.redo_stack
+            } else {
+                &[]
+            },
         }
     }
 
-    pub fn perform_update(&mut self, updates: Vec<Update>) {
-        let updates = Updates::new(updates);
+    /// `perform_update` applies `updates` as a single undoable step, which the undo history
+    /// describes with `label`. If they would leave anything referring to an account, category or
+    /// currency that doesn't exist, nothing is changed and an error is returned. For one that they
+    /// delete, the error lists its dependents.
+    pub fn perform_update(&mut self, label: impl Into<String>, updates: Vec<Update>) -> Result<()> {
+        self.perform_updates(Updates::new(label.into(), updates))
+    }
+
+    pub(super) fn perform_updates(&mut self, updates: Updates) -> Result<()> {
+        let journal_entry = serde_json::to_string(&updates)?;
+        let deleted_entities = updates.deleted_entities();
+        let referenced_entities = updates.referenced_entities();
         let reverse_updates = updates.apply(self);
+        if let Err(err) = check_integrity(self, &deleted_entities, &referenced_entities) {
+            reverse_updates.apply(self);
+            return Err(err);
+        }
         self.modification_count = self.max_modification_count + 1;
         self.max_modification_count = self.modification_count;
         self.undo_stack.push(reverse_updates);
         self.redo_stack.clear();
+        self.journal.push(journal_entry);
+        Ok(())
+    }
+
+    /// `take_journal` returns the batches of updates applied since it was last called, serialized
+    /// one per line, so that replaying them in order over the data as it was then reproduces the
+    /// data as it is now.
+    pub(super) fn take_journal(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.journal)
+    }
+
+    /// `journal` returns the batches of updates that `take_journal` would, without taking them.
+    pub(super) fn journal(&self) -> &[String] {
+        &self.journal
     }
 
     pub fn can_undo(&self) -> bool {
-        self.undo_stack.len() > 0
+        !self.undo_stack.is_empty()
     }
 
     pub fn undo(&mut self) {
-        match self.undo_stack.pop() {
-            Some(updates) => {
-                let reverse_updates = updates.apply(self);
-                self.redo_stack.push(reverse_updates);
-                self.modification_count -= 1;
-            }
-            None => (),
+        if let Some(updates) = self.undo_stack.pop() {
+            self.journal.push(serde_json::to_string(&updates).unwrap());
+            let reverse_updates = updates.apply(self);
+            self.redo_stack.push(reverse_updates);
+            self.modification_count -= 1;
+        }
+    }
+
+    /// The edits that can be undone, oldest first.
+    pub fn undo_history(&self) -> &[Updates] {
+        &self.undo_stack
+    }
+
+    /// The edits that can be redone, most recently undone first.
+    pub fn redo_history(&self) -> impl Iterator<Item = &Updates> {
+        self.redo_stack.iter().rev()
+    }
+
+    /// `go_to_history` undoes or redoes edits until exactly `undo_count` of them can be undone.
+    pub fn go_to_history(&mut self, undo_count: usize) {
+        while self.undo_stack.len() > undo_count {
+            self.undo();
+        }
+        while self.undo_stack.len() < undo_count && self.can_redo() {
+            self.redo();
         }
     }
 
     pub fn can_redo(&self) -> bool {
-        self.redo_stack.len() > 0
+        !self.redo_stack.is_empty()
     }
 
     pub fn redo(&mut self) {
-        match self.redo_stack.pop() {
-            Some(updates) => {
-                let reverse_updates = updates.apply(self);
-                self.undo_stack.push(reverse_updates);
-                self.modification_count += 1;
-            }
-            None => (),
+        if let Some(updates) = self.redo_stack.pop() {
+            self.journal.push(serde_json::to_string(&updates).unwrap());
+            let reverse_updates = updates.apply(self);
+            self.undo_stack.push(reverse_updates);
+            self.modification_count += 1;
         }
     }
 
@@ -452,6 +745,24 @@ impl AppData {
         &self.currencies
     }
 
+    pub fn account_currency(&self, account_id: u32) -> Option<&Currency> {
+        let account = self.accounts.get(&account_id)?;
+        self.currencies.get(&account.currency_id)
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// The currency that totals and reports are expressed in. If none has been chosen, or it has
+    /// been deleted, it is the first currency.
+    pub fn base_currency(&self) -> Option<&Currency> {
+        self.settings
+            .base_currency_id
+            .and_then(|id| self.currencies.get(&id))
+            .or_else(|| self.currencies.values().next())
+    }
+
     pub fn flows(&self) -> &BTreeMap<u32, Flow> {
         &self.flows
     }
@@ -464,12 +775,29 @@ impl AppData {
         &self.transaction_groups
     }
 
+    pub fn statements(&self) -> &BTreeMap<u32, Statement> {
+        &self.statements
+    }
+
+    pub fn rules(&self) -> &BTreeMap<u32, Rule> {
+        &self.rules
+    }
+
+    pub fn budgets(&self) -> &BTreeMap<u32, Budget> {
+        &self.budgets
+    }
+
     pub fn category_trees(&self) -> &Vec<CategoryNode> {
-        &self.category_trees
+        &self.derived_data.category_trees
     }
 
     pub fn transactions_by_date(&self) -> &BTreeMap<Date, BTreeSet<u32>> {
-        &self.transactions_by_date
+        &self.derived_data.transactions_by_date
+    }
+
+    /// The index of the transactions of an account, or `None` if it has no transactions.
+    pub fn account_index(&self, account_id: u32) -> Option<&AccountIndex> {
+        self.derived_data.transactions_by_account.get(&account_id)
     }
 }
 
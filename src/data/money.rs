@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+
+/// An amount of money in the minor units of its currency, e.g. cents. It is saved as a plain
+/// integer, so files from when amounts were `i32` load unchanged.
+///
+/// There are no arithmetic operators, since sums over arbitrarily many transactions can overflow,
+/// and even negating `i64::MIN` does; use the checked methods and treat `None` as an overflow.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub fn from_minor(minor: i64) -> Self {
+        Self(minor)
+    }
+
+    pub fn minor(self) -> i64 {
+        self.0
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        self.0.checked_neg().map(Self)
+    }
+
+    pub fn checked_abs(self) -> Option<Self> {
+        self.0.checked_abs().map(Self)
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    /// `parse` reads a decimal number like "-1234.56" exactly, in a currency with `major` minor
+    /// units per major unit. Returns `None` if `s` isn't a number or is more precise than the
+    /// currency allows.
+    pub fn parse(s: &str, major: i32) -> Option<Self> {
+        let s = s.trim();
+        let (is_negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if (whole.is_empty() && fraction.is_empty())
+            || !whole.chars().all(|c| c.is_ascii_digit())
+            || !fraction.chars().all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+        let major = major as i64;
+        let whole: i64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().ok()?
+        };
+        let fraction = fraction.trim_end_matches('0');
+        let numerator: i64 = if fraction.is_empty() {
+            0
+        } else {
+            fraction.parse().ok()?
+        };
+        let denominator = 10i64.checked_pow(fraction.len() as u32)?;
+        let scaled_fraction = numerator.checked_mul(major)?;
+        if scaled_fraction % denominator != 0 {
+            return None;
+        }
+        let minor = whole
+            .checked_mul(major)?
+            .checked_add(scaled_fraction / denominator)?;
+        Some(Self(if is_negative { -minor } else { minor }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str, major: i32) -> Option<i64> {
+        Money::parse(s, major).map(Money::minor)
+    }
+
+    #[test]
+    fn parses_exactly_without_rounding() {
+        assert_eq!(parse("1234.56", 100), Some(123456));
+        assert_eq!(parse("0.1", 100), Some(10));
+        assert_eq!(parse(".5", 100), Some(50));
+        assert_eq!(parse("7.", 100), Some(700));
+        assert_eq!(parse("1.50000", 100), Some(150));
+        assert_eq!(parse("1.005", 1000), Some(1005));
+        assert_eq!(parse("12", 1), Some(12));
+        // Amounts more precise than the currency are refused rather than rounded.
+        assert_eq!(parse("1.005", 100), None);
+        assert_eq!(parse("0.5", 1), None);
+    }
+
+    #[test]
+    fn parses_signs() {
+        assert_eq!(parse("-1.25", 100), Some(-125));
+        assert_eq!(parse("+1.25", 100), Some(125));
+        assert_eq!(parse("  -0.01 ", 100), Some(-1));
+        assert_eq!(parse("-0", 100), Some(0));
+        assert_eq!(parse("--1", 100), None);
+        assert_eq!(parse("+-1", 100), None);
+        assert_eq!(parse("1-", 100), None);
+    }
+
+    #[test]
+    fn refuses_separators_and_other_characters() {
+        assert_eq!(parse("1,234.56", 100), None);
+        assert_eq!(parse("1 234", 100), None);
+        assert_eq!(parse("1.2.3", 100), None);
+        assert_eq!(parse("12,50", 100), None);
+        assert_eq!(parse("$5", 100), None);
+        assert_eq!(parse("", 100), None);
+        assert_eq!(parse(".", 100), None);
+        assert_eq!(parse("-", 100), None);
+    }
+
+    #[test]
+    fn refuses_amounts_that_overflow() {
+        assert_eq!(parse("92233720368547758.07", 100), Some(i64::MAX));
+        assert_eq!(parse("-92233720368547758.07", 100), Some(-i64::MAX));
+        assert_eq!(parse("92233720368547758.08", 100), None);
+        assert_eq!(parse("9223372036854775808", 1), None);
+        assert_eq!(parse("0.00000000000000000001", 100), None);
+        assert_eq!(parse("0.100000000000000000000", 100), Some(10));
+    }
+
+    #[test]
+    fn checked_arithmetic_fails_on_overflow() {
+        let min = Money::from_minor(i64::MIN);
+        let max = Money::from_minor(i64::MAX);
+        assert_eq!(min.checked_neg(), None);
+        assert_eq!(min.checked_abs(), None);
+        assert_eq!(max.checked_neg(), Some(Money::from_minor(-i64::MAX)));
+        assert_eq!(
+            Money::from_minor(-5).checked_abs(),
+            Some(Money::from_minor(5))
+        );
+        assert_eq!(max.checked_add(Money::from_minor(1)), None);
+        assert_eq!(min.checked_sub(Money::from_minor(1)), None);
+        assert_eq!(
+            max.checked_sub(Money::from_minor(1)),
+            Some(Money::from_minor(i64::MAX - 1))
+        );
+    }
+
+    #[test]
+    fn loads_any_saved_integer() {
+        let money: Money = serde_json::from_str(&i64::MIN.to_string()).unwrap();
+        assert_eq!(money, Money::from_minor(i64::MIN));
+        assert_eq!(serde_json::to_string(&money).unwrap(), i64::MIN.to_string());
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use chrono::naive::NaiveDate as Date;
+use chrono::{Days, Months};
+
+use super::exchange_rates::Converter;
+use super::{AppData, Money};
+
+/// How often a net worth series is sampled.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SampleInterval {
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct NetWorthOptions {
+    pub start_date: Date,
+    pub end_date: Date,
+    pub interval: SampleInterval,
+    pub currency_id: u32,
+}
+
+impl NetWorthOptions {
+    fn sample_dates(&self) -> Vec<Date> {
+        let mut dates = Vec::new();
+        let mut date = Some(self.start_date);
+        while let Some(d) = date.filter(|d| *d <= self.end_date) {
+            dates.push(d);
+            date = match self.interval {
+                SampleInterval::Day => d.checked_add_days(Days::new(1)),
+                SampleInterval::Week => d.checked_add_days(Days::new(7)),
+                SampleInterval::Month => self
+                    .start_date
+                    .checked_add_months(Months::new(dates.len() as u32)),
+            };
+        }
+        dates
+    }
+}
+
+/// The balances of every account at a series of dates, in a single currency. The balances of
+/// credit accounts are liabilities, so they are negated.
+pub struct NetWorthSeries {
+    pub options: NetWorthOptions,
+    pub dates: Vec<Date>,
+    /// The balance of each account at each of `dates`.
+    pub accounts: Vec<(u32, Vec<Money>)>,
+    /// The sum of the account balances at each of `dates`.
+    pub totals: Vec<Money>,
+}
+
+impl NetWorthSeries {
+    /// `new` computes the series in a single pass over the transactions and balances, giving the
+    /// same balances as `Account::balance_on_date` would for each sample. Returns `None` if any of
+    /// the balances can't be converted into the currency, or any of the balances or totals is too
+    /// large to represent.
+    pub fn new(app_data: &AppData, options: NetWorthOptions) -> Option<Self> {
+        let dates = options.sample_dates();
+        let mut series = Self {
+            options,
+            dates,
+            accounts: Vec::new(),
+            totals: Vec::new(),
+        };
+        let currency = match app_data.currencies().get(&series.options.currency_id) {
+            Some(currency) => currency,
+            None => return Some(series),
+        };
+
+        // From the day after a balance is recorded, an account's amount starts over from it.
+        let mut resets: Vec<(Date, u32, Money)> = Vec::new();
+        for account in app_data.accounts().values() {
+            for balance in account.balances.iter() {
+                if let Some(date) = balance.date.succ_opt() {
+                    resets.push((date, account.id, balance.amount));
+                }
+            }
+        }
+        resets.sort_by_key(|(date, _, _)| *date);
+        let mut resets = resets.into_iter().peekable();
+        let mut transactions_by_date = app_data.transactions_by_date().iter().peekable();
+
+        let mut amounts: BTreeMap<u32, Money> = app_data
+            .accounts()
+            .keys()
+            .map(|id| (*id, Money::ZERO))
+            .collect();
+        let mut account_series: BTreeMap<u32, Vec<Money>> = app_data
+            .accounts()
+            .keys()
+            .map(|id| (*id, Vec::with_capacity(series.dates.len())))
+            .collect();
+        let mut converter = Converter::new(app_data);
+        for sample_date in series.dates.iter() {
+            loop {
+                let reset_date = resets.peek().map(|(date, _, _)| *date);
+                let transaction_date = transactions_by_date.peek().map(|(date, _)| **date);
+                match (reset_date, transaction_date) {
+                    // Resets come before the transactions on the same date.
+                    (Some(reset_date), _)
+                        if reset_date <= *sample_date
+                            && transaction_date.map_or(true, |date| reset_date <= date) =>
+                    {
+                        let (_, account_id, amount) = resets.next().unwrap();
+                        amounts.insert(account_id, amount);
+                    }
+                    (_, Some(transaction_date)) if transaction_date <= *sample_date => {
+                        let (_, transaction_ids) = transactions_by_date.next().unwrap();
+                        for transaction_id in transaction_ids {
+                            let transaction = app_data.transactions().get(transaction_id).unwrap();
+                            if let Some(amount) = amounts.get_mut(&transaction.account_id) {
+                                *amount = amount.checked_add(transaction.amount)?;
+                            }
+                        }
+                    }
+                    _ => break,
+                }
+            }
+            let mut total = Money::ZERO;
+            for (account_id, amount) in amounts.iter() {
+                let account = app_data.accounts().get(account_id).unwrap();
+                let amount = match app_data.currencies().get(&account.currency_id) {
+                    Some(account_currency) => {
+                        converter.convert(*amount, account_currency, currency, *sample_date)?
+                    }
+                    None => Money::ZERO,
+                };
+                let amount = if account.debit_account {
+                    amount
+                } else {
+                    amount.checked_neg()?
+                };
+                total = total.checked_add(amount)?;
+                account_series.get_mut(account_id).unwrap().push(amount);
+            }
+            series.totals.push(total);
+        }
+        series.accounts = account_series.into_iter().collect();
+        Some(series)
+    }
+}
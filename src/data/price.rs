@@ -1,55 +1,324 @@
-use super::Currency;
+use super::{Currency, Money, SymbolPosition};
 
 pub struct Price<'a> {
-    pub amount: i32,
+    pub amount: Money,
     pub currency: &'a Currency,
 }
 
 impl<'a> Price<'a> {
-    pub fn new(amount: i32, currency: &'a Currency) -> Self {
+    pub fn new(amount: Money, currency: &'a Currency) -> Self {
         Self { amount, currency }
     }
 
-    pub fn scaled_amount(&self) -> f32 {
-        (self.amount as f32) / (self.currency.major as f32)
+    pub fn scaled_amount(&self) -> f64 {
+        (self.amount.minor() as f64) / (self.currency.major as f64)
     }
 }
 
 impl<'a> std::fmt::Display for Price<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let number = format_number(self.amount, self.currency);
+        let symbol = &self.currency.symbol;
+        let (prefix, suffix) = match self.currency.symbol_position {
+            _ if symbol.is_empty() => (String::new(), String::new()),
+            SymbolPosition::Prefix => (symbol.clone(), String::new()),
+            SymbolPosition::PrefixSpaced => (format!("{} ", symbol), String::new()),
+            SymbolPosition::Suffix => (String::new(), symbol.clone()),
+            SymbolPosition::SuffixSpaced => (String::new(), format!(" {}", symbol)),
+        };
         write!(
             f,
-            "{}{}{:.2} {}",
-            if self.amount < 0 { "-" } else { "" },
-            self.currency.symbol,
-            self.scaled_amount().abs(),
+            "{}{}{}{} {}",
+            if self.amount.is_negative() { "-" } else { "" },
+            prefix,
+            number,
+            suffix,
             self.currency.code
         )
     }
 }
 
 pub struct NumericPrice<'a> {
-    pub amount: i32,
+    pub amount: Money,
     pub currency: &'a Currency,
 }
 
 impl<'a> NumericPrice<'a> {
-    pub fn new(amount: i32, currency: &'a Currency) -> Self {
+    pub fn new(amount: Money, currency: &'a Currency) -> Self {
         Self { amount, currency }
     }
-
-    pub fn scaled_amount(&self) -> f32 {
-        (self.amount as f32) / (self.currency.major as f32)
-    }
 }
 
 impl<'a> std::fmt::Display for NumericPrice<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "{}{:.2}",
-            if self.amount < 0 { "-" } else { "" },
-            self.scaled_amount().abs(),
+            "{}{}",
+            if self.amount.is_negative() { "-" } else { "" },
+            format_number(self.amount, self.currency),
         )
     }
 }
+
+/// The number of decimal places needed to show every amount in a currency with `major` minor
+/// units per major unit, e.g. 0 for JPY and 3 for KWD.
+fn decimal_places(major: i32) -> u32 {
+    let mut places = 0;
+    while 10i64.pow(places) < major as i64 {
+        places += 1;
+    }
+    places
+}
+
+/// `format_number` formats the absolute value of `amount` with the currency's separators.
+fn format_number(amount: Money, currency: &Currency) -> String {
+    let major = currency.major.max(1) as u64;
+    let places = decimal_places(currency.major);
+    let minor = amount.minor().unsigned_abs();
+    let whole = (minor / major).to_string();
+    // Exact when `major` is a power of ten, which it is for real currencies. The product can
+    // overflow a u64 when `major` is above 10^9.
+    let fraction = (minor % major) as u128 * 10u128.pow(places) / major as u128;
+
+    let mut number = String::new();
+    for (index, digit) in whole.chars().enumerate() {
+        if index > 0 && (whole.len() - index) % 3 == 0 {
+            number.push(currency.number_format.thousands_separator());
+        }
+        number.push(digit);
+    }
+    if places > 0 {
+        number.push(currency.number_format.decimal_separator());
+        number.push_str(&format!("{:0width$}", fraction, width = places as usize));
+    }
+    number
+}
+
+fn strip_sign(s: &str) -> (bool, &str) {
+    match s.strip_prefix('-') {
+        Some(s) => (true, s.trim_start()),
+        None => (false, s),
+    }
+}
+
+/// `parse_price` reads an amount written the way `Price` or `NumericPrice` would write it, with
+/// or without the thousands separators, symbol and code. Amounts that don't follow the
+/// currency's number format, like "12.5" for a currency written "12,50", are read as plain
+/// decimals if they can be.
+pub fn parse_price(s: &str, currency: &Currency) -> Option<Money> {
+    let mut s = s.trim();
+    if !currency.code.is_empty() {
+        s = s
+            .strip_suffix(currency.code.as_str())
+            .unwrap_or(s)
+            .trim_end();
+    }
+    let (is_negative, mut s) = strip_sign(s);
+    if !currency.symbol.is_empty() {
+        s = s
+            .strip_prefix(currency.symbol.as_str())
+            .unwrap_or(s)
+            .trim_start();
+        s = s
+            .strip_suffix(currency.symbol.as_str())
+            .unwrap_or(s)
+            .trim_end();
+    }
+    let (is_negative_after_symbol, s) = strip_sign(s);
+    let sign = if is_negative || is_negative_after_symbol {
+        "-"
+    } else {
+        ""
+    };
+
+    let format = currency.number_format;
+    let (whole, fraction) = match s.split_once(format.decimal_separator()) {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (s, None),
+    };
+    let groups: Vec<&str> = match format.thousands_separator() {
+        // Spaces are often written as non-breaking spaces.
+        ' ' => whole.split(char::is_whitespace).collect(),
+        separator => whole.split(separator).collect(),
+    };
+    let is_grouped_correctly = groups.len() == 1
+        || ((1..=3).contains(&groups[0].len()) && groups[1..].iter().all(|g| g.len() == 3));
+    let parsed = if is_grouped_correctly {
+        let number = match fraction {
+            Some(fraction) => format!("{}{}.{}", sign, groups.concat(), fraction),
+            None => format!("{}{}", sign, groups.concat()),
+        };
+        Money::parse(&number, currency.major)
+    } else {
+        None
+    };
+    parsed.or_else(|| Money::parse(&format!("{}{}", sign, s), currency.major))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::NumberFormat;
+
+    fn currency(
+        code: &str,
+        major: i32,
+        symbol: &str,
+        number_format: NumberFormat,
+        symbol_position: SymbolPosition,
+    ) -> Currency {
+        Currency {
+            id: 0,
+            code: code.into(),
+            major,
+            equivalent_base: 1.0,
+            symbol: symbol.into(),
+            number_format,
+            symbol_position,
+            exchange_rates: Default::default(),
+        }
+    }
+
+    fn usd() -> Currency {
+        currency(
+            "USD",
+            100,
+            "$",
+            NumberFormat::CommaPeriod,
+            SymbolPosition::Prefix,
+        )
+    }
+
+    fn eur() -> Currency {
+        currency(
+            "EUR",
+            100,
+            "€",
+            NumberFormat::PeriodComma,
+            SymbolPosition::SuffixSpaced,
+        )
+    }
+
+    #[test]
+    fn formats_with_the_currency_conventions() {
+        let amount = Money::from_minor(-123456789);
+        assert_eq!(Price::new(amount, &usd()).to_string(), "-$1,234,567.89 USD");
+        assert_eq!(
+            Price::new(amount, &eur()).to_string(),
+            "-1.234.567,89 € EUR"
+        );
+        let chf = currency(
+            "CHF",
+            100,
+            "Fr.",
+            NumberFormat::ApostrophePeriod,
+            SymbolPosition::PrefixSpaced,
+        );
+        assert_eq!(
+            Price::new(amount, &chf).to_string(),
+            "-Fr. 1'234'567.89 CHF"
+        );
+        let sek = currency(
+            "SEK",
+            100,
+            "kr",
+            NumberFormat::SpaceComma,
+            SymbolPosition::Suffix,
+        );
+        assert_eq!(Price::new(amount, &sek).to_string(), "-1 234 567,89kr SEK");
+        assert_eq!(
+            NumericPrice::new(amount, &eur()).to_string(),
+            "-1.234.567,89"
+        );
+    }
+
+    #[test]
+    fn formats_with_the_currency_precision() {
+        let jpy = currency(
+            "JPY",
+            1,
+            "¥",
+            NumberFormat::CommaPeriod,
+            SymbolPosition::Prefix,
+        );
+        assert_eq!(
+            Price::new(Money::from_minor(1500), &jpy).to_string(),
+            "¥1,500 JPY"
+        );
+        let kwd = currency(
+            "KWD",
+            1000,
+            "",
+            NumberFormat::CommaPeriod,
+            SymbolPosition::Prefix,
+        );
+        assert_eq!(
+            Price::new(Money::from_minor(1005), &kwd).to_string(),
+            "1.005 KWD"
+        );
+        assert_eq!(
+            Price::new(Money::from_minor(5), &usd()).to_string(),
+            "$0.05 USD"
+        );
+        assert_eq!(
+            Price::new(Money::from_minor(i64::MIN), &usd()).to_string(),
+            "-$92,233,720,368,547,758.08 USD"
+        );
+        let huge = currency(
+            "XXX",
+            i32::MAX,
+            "",
+            NumberFormat::CommaPeriod,
+            SymbolPosition::Prefix,
+        );
+        assert_eq!(
+            Price::new(Money::from_minor(i32::MAX as i64 - 1), &huge).to_string(),
+            "0.9999999995 XXX"
+        );
+    }
+
+    #[test]
+    fn parses_what_it_formats() {
+        for currency in [usd(), eur()] {
+            for minor in [0, 7, -7, 100, -123456789, i64::MAX] {
+                let amount = Money::from_minor(minor);
+                for formatted in [
+                    Price::new(amount, &currency).to_string(),
+                    NumericPrice::new(amount, &currency).to_string(),
+                ] {
+                    assert_eq!(
+                        parse_price(&formatted, &currency),
+                        Some(amount),
+                        "{}",
+                        formatted
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parses_loosely_written_amounts() {
+        let parse = |s| parse_price(s, &eur()).map(Money::minor);
+        assert_eq!(parse("1234,5"), Some(123450));
+        assert_eq!(parse("€ - 3,00"), Some(-300));
+        assert_eq!(parse("- 3 €"), Some(-300));
+        // Misplaced separators fall back to a plain decimal.
+        assert_eq!(parse("12.5"), Some(1250));
+        assert_eq!(parse("1.2345,00"), None);
+        assert_eq!(parse("1,005"), None);
+        assert_eq!(parse("abc"), None);
+
+        let sek = currency(
+            "SEK",
+            100,
+            "kr",
+            NumberFormat::SpaceComma,
+            SymbolPosition::Suffix,
+        );
+        assert_eq!(
+            parse_price("1\u{a0}234,50 kr", &sek),
+            Some(Money::from_minor(123450))
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::BTreeMap;
+
+use chrono::naive::NaiveDate as Date;
+use chrono::{Datelike, Months};
+
+use super::exchange_rates::Converter;
+use super::{AccrualPeriod, AppData, CategoryNode, Flow, Money};
+
+/// The length of the columns of a report.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PeriodLength {
+    Month,
+    Quarter,
+    Year,
+    /// A single column covering the whole range of the report.
+    Custom,
+}
+
+/// Whether a report counts flows on their date, or spread over their amortization schedule.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReportBasis {
+    Cash,
+    Accrual,
+}
+
+/// A range of dates, including both ends.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Period {
+    pub start: Date,
+    pub end: Date,
+}
+
+impl Period {
+    pub fn contains(&self, date: Date) -> bool {
+        self.start <= date && date <= self.end
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ReportOptions {
+    pub period_length: PeriodLength,
+    pub start_date: Date,
+    pub end_date: Date,
+    pub currency_id: u32,
+    pub basis: ReportBasis,
+}
+
+impl ReportOptions {
+    /// `periods` splits the range of the report into calendar periods. The first and last periods
+    /// are cut short to the range, so that they only cover dates in the report.
+    pub fn periods(&self) -> Vec<Period> {
+        let months = match self.period_length {
+            PeriodLength::Month => 1,
+            PeriodLength::Quarter => 3,
+            PeriodLength::Year => 12,
+            PeriodLength::Custom => {
+                return vec![Period {
+                    start: self.start_date,
+                    end: self.end_date,
+                }]
+            }
+        };
+        let month0 = self.start_date.month0() / months * months;
+        let mut start = Date::from_ymd_opt(self.start_date.year(), month0 + 1, 1).unwrap();
+        let mut periods = Vec::new();
+        while start <= self.end_date {
+            let next_start = start.checked_add_months(Months::new(months));
+            periods.push(Period {
+                start: start.max(self.start_date),
+                end: next_start
+                    .map_or(Date::MAX, |next_start| next_start.pred_opt().unwrap())
+                    .min(self.end_date),
+            });
+            start = match next_start {
+                Some(next_start) => next_start,
+                None => break,
+            };
+        }
+        periods
+    }
+
+    pub fn period_label(&self, period: &Period) -> String {
+        match self.period_length {
+            PeriodLength::Month => period.start.format("%Y-%m").to_string(),
+            PeriodLength::Quarter => {
+                format!("{} Q{}", period.start.year(), period.start.month0() / 3 + 1)
+            }
+            PeriodLength::Year => period.start.year().to_string(),
+            PeriodLength::Custom => format!("{} to {}", period.start, period.end),
+        }
+    }
+}
+
+/// Flow totals by category and period, in a single currency.
+pub struct SpendingReport {
+    pub options: ReportOptions,
+    pub periods: Vec<Period>,
+    /// The totals of each category for each period, including the flows of its descendants.
+    totals: BTreeMap<u32, Vec<Money>>,
+    /// The totals of each category over all the periods in each of the flows' own currencies,
+    /// including the flows of its descendants.
+    currency_totals: BTreeMap<u32, BTreeMap<u32, Money>>,
+    /// The ids of the flows directly in each category that contribute to each period.
+    flow_ids: BTreeMap<u32, Vec<Vec<u32>>>,
+}
+
+impl SpendingReport {
+    /// `new` returns `None` if any of the flows can't be converted into the currency, or any of
+    /// the totals is too large to represent.
+    pub fn new(app_data: &AppData, options: ReportOptions) -> Option<Self> {
+        let periods = options.periods();
+        let mut report = Self {
+            options,
+            periods,
+            totals: BTreeMap::new(),
+            currency_totals: BTreeMap::new(),
+            flow_ids: BTreeMap::new(),
+        };
+        let currency = match app_data.currencies().get(&report.options.currency_id) {
+            Some(currency) => currency,
+            None => return Some(report),
+        };
+        let mut direct_totals: BTreeMap<u32, Vec<Money>> = BTreeMap::new();
+        let mut direct_currency_totals: BTreeMap<u32, BTreeMap<u32, Money>> = BTreeMap::new();
+        let mut converter = Converter::new(app_data);
+        for flow in app_data.flows().values() {
+            let flow_currency = match app_data.currencies().get(&flow.currency_id) {
+                Some(flow_currency) => flow_currency,
+                None => continue,
+            };
+            let original_amounts = match report.options.basis {
+                ReportBasis::Cash => vec![(flow.date, flow.amount)],
+                ReportBasis::Accrual => flow.accrued_amounts(AccrualPeriod::Day),
+            };
+            for (date, amount) in original_amounts {
+                if report.period_index(date).is_some() {
+                    let total = direct_currency_totals
+                        .entry(flow.category_id)
+                        .or_default()
+                        .entry(flow.currency_id)
+                        .or_default();
+                    *total = total.checked_add(amount)?;
+                }
+            }
+
+            // Amortized flows are converted before they are accrued, so that the accrued amounts
+            // add up to exactly the converted amount.
+            let accrued_amounts = match report.options.basis {
+                ReportBasis::Cash => vec![(flow.date, flow.amount)],
+                ReportBasis::Accrual => Flow {
+                    amount: converter.convert(flow.amount, flow_currency, currency, flow.date)?,
+                    ..flow.clone()
+                }
+                .accrued_amounts(AccrualPeriod::Day),
+            };
+            for (date, amount) in accrued_amounts {
+                let index = match report.period_index(date) {
+                    Some(index) => index,
+                    None => continue,
+                };
+                let amount = match report.options.basis {
+                    ReportBasis::Cash => {
+                        converter.convert(amount, flow_currency, currency, date)?
+                    }
+                    ReportBasis::Accrual => amount,
+                };
+                let total = &mut direct_totals
+                    .entry(flow.category_id)
+                    .or_insert_with(|| vec![Money::ZERO; report.periods.len()])[index];
+                *total = total.checked_add(amount)?;
+                let flow_ids = &mut report
+                    .flow_ids
+                    .entry(flow.category_id)
+                    .or_insert_with(|| vec![Vec::new(); report.periods.len()])[index];
+                if flow_ids.last() != Some(&flow.id) {
+                    flow_ids.push(flow.id);
+                }
+            }
+        }
+        for node in app_data.category_trees().iter() {
+            report.roll_up(node, &direct_totals, &direct_currency_totals)?;
+        }
+        Some(report)
+    }
+
+    /// The index of the period containing `date`, if any.
+    fn period_index(&self, date: Date) -> Option<usize> {
+        let index = self.periods.partition_point(|period| period.end < date);
+        self.periods
+            .get(index)
+            .filter(|period| period.contains(date))
+            .map(|_| index)
+    }
+
+    fn roll_up(
+        &mut self,
+        node: &CategoryNode,
+        direct_totals: &BTreeMap<u32, Vec<Money>>,
+        direct_currency_totals: &BTreeMap<u32, BTreeMap<u32, Money>>,
+    ) -> Option<()> {
+        let mut totals = direct_totals
+            .get(&node.id)
+            .cloned()
+            .unwrap_or_else(|| vec![Money::ZERO; self.periods.len()]);
+        let mut currency_totals = direct_currency_totals
+            .get(&node.id)
+            .cloned()
+            .unwrap_or_default();
+        for child in node.children.iter() {
+            self.roll_up(child, direct_totals, direct_currency_totals)?;
+            for (total, child_total) in totals.iter_mut().zip(self.totals[&child.id].iter()) {
+                *total = total.checked_add(*child_total)?;
+            }
+            for (currency_id, child_total) in self.currency_totals[&child.id].iter() {
+                let total = currency_totals.entry(*currency_id).or_default();
+                *total = total.checked_add(*child_total)?;
+            }
+        }
+        self.totals.insert(node.id, totals);
+        self.currency_totals.insert(node.id, currency_totals);
+        Some(())
+    }
+
+    /// The total of a category and its descendants in each period.
+    pub fn totals(&self, category_id: u32) -> &[Money] {
+        self.totals
+            .get(&category_id)
+            .map_or(&[], |totals| totals.as_slice())
+    }
+
+    /// The total of a category and its descendants over all the periods in each currency that
+    /// the flows were in, before conversion.
+    pub fn currency_totals(&self, category_id: u32) -> Option<&BTreeMap<u32, Money>> {
+        self.currency_totals.get(&category_id)
+    }
+
+    /// `transaction_ids` returns the transactions behind the total of the category `node` in the
+    /// period at `period_index`, or in every period if it is `None`, ordered by date.
+    pub fn transaction_ids(
+        &self,
+        app_data: &AppData,
+        node: &CategoryNode,
+        period_index: Option<usize>,
+    ) -> Vec<u32> {
+        let mut flow_ids = Vec::new();
+        self.collect_flow_ids(node, period_index, &mut flow_ids);
+        let mut transaction_ids: Vec<u32> = flow_ids
+            .into_iter()
+            .filter_map(|flow_id| app_data.flows().get(&flow_id))
+            .filter_map(|flow| {
+                app_data
+                    .transaction_groups()
+                    .get(&flow.transaction_group_id)
+            })
+            .flat_map(|transaction_group| transaction_group.transaction_ids.iter().copied())
+            .collect();
+        transaction_ids.sort_by_key(|id| {
+            (
+                app_data
+                    .transactions()
+                    .get(id)
+                    .map(|transaction| transaction.date),
+                *id,
+            )
+        });
+        transaction_ids.dedup();
+        transaction_ids
+    }
+
+    fn collect_flow_ids(
+        &self,
+        node: &CategoryNode,
+        period_index: Option<usize>,
+        flow_ids: &mut Vec<u32>,
+    ) {
+        if let Some(periods) = self.flow_ids.get(&node.id) {
+            match period_index {
+                Some(index) => flow_ids.extend(periods[index].iter()),
+                None => flow_ids.extend(periods.iter().flatten()),
+            }
+        }
+        for child in node.children.iter() {
+            self.collect_flow_ids(child, period_index, flow_ids);
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use regex::Regex;
+
+use super::{
+    default_amortization, next_id, AppData, Flow, Money, Rule, Transaction, TransactionGroup,
+    Update,
+};
+
+impl Rule {
+    fn matches(&self, description_regex: &Option<Regex>, transaction: &Transaction) -> bool {
+        if let Some(description_regex) = description_regex {
+            if !description_regex.is_match(&transaction.description) {
+                return false;
+            }
+        }
+        self.min_amount
+            .map_or(true, |min| transaction.amount >= min)
+            && self
+                .max_amount
+                .map_or(true, |max| transaction.amount <= max)
+            && self
+                .account_id
+                .map_or(true, |id| transaction.account_id == id)
+            && self
+                .start_date
+                .map_or(true, |date| transaction.date >= date)
+            && self.end_date.map_or(true, |date| transaction.date <= date)
+    }
+
+    /// `flow_amounts` splits `amount` between the flows of the rule. Fixed flow amounts take the
+    /// sign of `amount`. Returns `None` if the flows can't add up to `amount`.
+    fn flow_amounts(&self, amount: Money) -> Option<Vec<Money>> {
+        let mut amounts: Vec<Money> = self
+            .flows
+            .iter()
+            .map(|flow| match flow.amount {
+                Some(flow_amount) if amount.is_negative() => {
+                    flow_amount.checked_abs()?.checked_neg()
+                }
+                Some(flow_amount) if amount != Money::ZERO => flow_amount.checked_abs(),
+                _ => Some(Money::ZERO),
+            })
+            .collect::<Option<_>>()?;
+        let fixed_total = amounts
+            .iter()
+            .try_fold(Money::ZERO, |total, amount| total.checked_add(*amount))?;
+        match self.flows.iter().position(|flow| flow.amount.is_none()) {
+            Some(index) => amounts[index] = amount.checked_sub(fixed_total)?,
+            None => {
+                if fixed_total != amount {
+                    return None;
+                }
+            }
+        }
+        Some(amounts)
+    }
+}
+
+/// The transaction group and flows that a rule would create for a transaction.
+pub struct RuleMatch {
+    pub rule_id: u32,
+    /// The matched transaction, with its group set to `transaction_group`.
+    pub transaction: Transaction,
+    pub transaction_group: TransactionGroup,
+    pub flows: Vec<Flow>,
+}
+
+impl RuleMatch {
+    pub fn updates(self) -> Vec<Update> {
+        let mut updates = vec![Update::SetTransactionGroup(self.transaction_group)];
+        for flow in self.flows.into_iter() {
+            updates.push(Update::SetFlow(flow));
+        }
+        updates.push(Update::SetTransaction(self.transaction));
+        updates
+    }
+}
+
+pub struct RuleEngine<'a> {
+    rules: Vec<(&'a Rule, Option<Regex>)>,
+    /// Ids of the rules that are left out because their description regex doesn't compile.
+    pub invalid_rule_ids: Vec<u32>,
+}
+
+impl<'a> RuleEngine<'a> {
+    pub fn new(app_data: &'a AppData) -> Self {
+        let mut rules = Vec::new();
+        let mut invalid_rule_ids = Vec::new();
+        for rule in app_data.rules().values() {
+            let description_regex = match rule.description_regex.as_deref().map(Regex::new) {
+                Some(Ok(description_regex)) => Some(description_regex),
+                Some(Err(_)) => {
+                    invalid_rule_ids.push(rule.id);
+                    continue;
+                }
+                None => None,
+            };
+            rules.push((rule, description_regex));
+        }
+        Self {
+            rules,
+            invalid_rule_ids,
+        }
+    }
+
+    /// `plan` works out which rule, if any, applies to each of `transactions`, without modifying
+    /// `app_data`. Transactions that already belong to a group are skipped. Ids for the new groups
+    /// and flows are allocated after the ones in `app_data`.
+    pub fn plan<'b>(
+        &self,
+        app_data: &AppData,
+        transactions: impl Iterator<Item = &'b Transaction>,
+    ) -> Vec<RuleMatch> {
+        let mut next_transaction_group_id = next_id(app_data.transaction_groups());
+        let mut next_flow_id = next_id(app_data.flows());
+        let mut rule_matches = Vec::new();
+        for transaction in transactions {
+            if transaction.transaction_group_id.is_some()
+                || transaction.transfer_transaction_id.is_some()
+            {
+                continue;
+            }
+            let currency_id = match app_data.accounts().get(&transaction.account_id) {
+                Some(account) => account.currency_id,
+                None => continue,
+            };
+            let rule_and_amounts = self.rules.iter().find_map(|(rule, description_regex)| {
+                if rule.matches(description_regex, transaction) {
+                    rule.flow_amounts(transaction.amount)
+                        .map(|amounts| (rule, amounts))
+                } else {
+                    None
+                }
+            });
+            let (rule, amounts) = match rule_and_amounts {
+                Some(rule_and_amounts) => rule_and_amounts,
+                None => continue,
+            };
+            let transaction_group_id = next_transaction_group_id;
+            next_transaction_group_id += 1;
+            let flows: Vec<Flow> = rule
+                .flows
+                .iter()
+                .zip(amounts)
+                .map(|(rule_flow, amount)| {
+                    let id = next_flow_id;
+                    next_flow_id += 1;
+                    let (amortization_type, amortization_length) = match rule_flow.amortization_type
+                    {
+                        Some(_) => (rule_flow.amortization_type, rule_flow.amortization_length),
+                        None => default_amortization(app_data, rule_flow.category_id),
+                    };
+                    Flow {
+                        id,
+                        category_id: rule_flow.category_id,
+                        date: transaction.date,
+                        description: rule_flow.description.clone(),
+                        amount,
+                        currency_id,
+                        amortization_type,
+                        amortization_length,
+                        transaction_group_id,
+                    }
+                })
+                .collect();
+            rule_matches.push(RuleMatch {
+                rule_id: rule.id,
+                transaction: Transaction {
+                    transaction_group_id: Some(transaction_group_id),
+                    ..transaction.clone()
+                },
+                transaction_group: TransactionGroup {
+                    id: transaction_group_id,
+                    transaction_ids: vec![transaction.id],
+                    flow_ids: flows.iter().map(|flow| flow.id).collect(),
+                },
+                flows,
+            });
+        }
+        rule_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::naive::NaiveDate as Date;
+
+    use super::*;
+    use crate::data::test_fixtures::{self, account, category, currency};
+    use crate::data::{AmortizationType, Category, RuleFlow};
+
+    fn rule_flow(category_id: u32, amount: Option<i64>) -> RuleFlow {
+        RuleFlow {
+            category_id,
+            description: None,
+            amount: amount.map(Money::from_minor),
+            amortization_type: None,
+            amortization_length: None,
+        }
+    }
+
+    fn rule(id: u32, description_regex: Option<&str>, flows: Vec<RuleFlow>) -> Rule {
+        Rule {
+            id,
+            name: format!("Rule {}", id),
+            description_regex: description_regex.map(|regex| regex.into()),
+            min_amount: None,
+            max_amount: None,
+            account_id: None,
+            start_date: None,
+            end_date: None,
+            flows,
+        }
+    }
+
+    fn transaction(id: u32, description: &str, amount: i64) -> Transaction {
+        Transaction {
+            id,
+            account_id: 0,
+            date: Date::from_ymd_opt(2023, 5, 12).unwrap(),
+            description: description.into(),
+            amount: Money::from_minor(amount),
+            transaction_group_id: None,
+            statement_id: None,
+            original: None,
+            transfer_transaction_id: None,
+        }
+    }
+
+    fn app_data_with(rules: Vec<Rule>) -> AppData {
+        let mut updates = vec![
+            Update::SetCurrency(currency(0, "USD")),
+            Update::SetAccount(account(0, 0, true)),
+        ];
+        for (id, name) in ["Groceries", "Household", "Insurance"].iter().enumerate() {
+            updates.push(Update::SetCategory(Category {
+                default_amortization_type: (id == 2).then_some(AmortizationType::Linear),
+                default_amortization_length: (id == 2).then_some(12),
+                ..category(id as u32, name, None)
+            }));
+        }
+        updates.extend(rules.into_iter().map(Update::SetRule));
+        test_fixtures::app_data_with(updates)
+    }
+
+    fn amounts(amounts: &[i64]) -> Option<Vec<Money>> {
+        Some(amounts.iter().copied().map(Money::from_minor).collect())
+    }
+
+    #[test]
+    fn flow_amounts_give_the_rest_to_the_open_flow() {
+        let rule = rule(0, None, vec![rule_flow(0, None), rule_flow(1, Some(1500))]);
+        assert_eq!(
+            rule.flow_amounts(Money::from_minor(-5000)),
+            amounts(&[-3500, -1500])
+        );
+        assert_eq!(
+            rule.flow_amounts(Money::from_minor(5000)),
+            amounts(&[3500, 1500])
+        );
+        assert_eq!(
+            rule.flow_amounts(Money::from_minor(-1000)),
+            amounts(&[500, -1500])
+        );
+    }
+
+    #[test]
+    fn fixed_flow_amounts_must_add_up() {
+        let rule = rule(
+            0,
+            None,
+            vec![rule_flow(0, Some(-2000)), rule_flow(1, Some(3000))],
+        );
+        assert_eq!(
+            rule.flow_amounts(Money::from_minor(-5000)),
+            amounts(&[-2000, -3000])
+        );
+        assert_eq!(rule.flow_amounts(Money::from_minor(-4999)), None);
+        assert_eq!(rule.flow_amounts(Money::ZERO), amounts(&[0, 0]));
+    }
+
+    #[test]
+    fn flow_amounts_fail_on_overflow() {
+        let too_large = rule(
+            0,
+            None,
+            vec![
+                rule_flow(0, None),
+                rule_flow(1, Some(i64::MAX)),
+                rule_flow(2, Some(i64::MAX)),
+            ],
+        );
+        assert_eq!(too_large.flow_amounts(Money::from_minor(-2)), None);
+        // A fixed amount of `i64::MIN` has no absolute value.
+        let too_small = rule(
+            0,
+            None,
+            vec![rule_flow(0, None), rule_flow(1, Some(i64::MIN))],
+        );
+        assert_eq!(too_small.flow_amounts(Money::from_minor(2)), None);
+        assert_eq!(too_small.flow_amounts(Money::from_minor(-2)), None);
+    }
+
+    #[test]
+    fn lowest_matching_rule_wins() {
+        let app_data = app_data_with(vec![
+            rule(0, Some("(?i)^grocer"), vec![rule_flow(0, None)]),
+            rule(1, Some("(?i)grocer|hardware"), vec![rule_flow(1, None)]),
+            rule(2, Some("(?i)insurance"), vec![rule_flow(2, None)]),
+        ]);
+        let transactions = [
+            transaction(0, "GROCERY OUTLET", -4200),
+            transaction(1, "Corner grocer", -1800),
+            transaction(2, "Home insurance", -60000),
+            transaction(3, "Paycheque", 250000),
+        ];
+        let rule_matches = RuleEngine::new(&app_data).plan(&app_data, transactions.iter());
+        let matched: Vec<(u32, u32)> = rule_matches
+            .iter()
+            .map(|rule_match| (rule_match.transaction.id, rule_match.rule_id))
+            .collect();
+        assert_eq!(matched, [(0, 0), (1, 1), (2, 2)]);
+
+        let insurance = &rule_matches[2];
+        assert_eq!(insurance.transaction.transaction_group_id, Some(2));
+        assert_eq!(insurance.transaction_group.transaction_ids, [2]);
+        assert_eq!(insurance.flows[0].amount, Money::from_minor(-60000));
+        assert_eq!(
+            insurance.flows[0].amortization_type,
+            Some(AmortizationType::Linear)
+        );
+        assert_eq!(insurance.flows[0].amortization_length, Some(12));
+    }
+
+    #[test]
+    fn checks_every_condition() {
+        let mut bounded = rule(0, None, vec![rule_flow(0, None)]);
+        bounded.min_amount = Some(Money::from_minor(-5000));
+        bounded.max_amount = Some(Money::from_minor(-1000));
+        bounded.start_date = Date::from_ymd_opt(2023, 5, 1);
+        bounded.end_date = Date::from_ymd_opt(2023, 5, 31);
+        let app_data = app_data_with(vec![bounded]);
+        let mut in_june = transaction(3, "June", -2000);
+        in_june.date = Date::from_ymd_opt(2023, 6, 1).unwrap();
+        let transactions = [
+            transaction(0, "Too much", -5001),
+            transaction(1, "In range", -5000),
+            transaction(2, "Too little", -999),
+            in_june,
+        ];
+        let matched: Vec<u32> = RuleEngine::new(&app_data)
+            .plan(&app_data, transactions.iter())
+            .iter()
+            .map(|rule_match| rule_match.transaction.id)
+            .collect();
+        assert_eq!(matched, [1]);
+    }
+
+    #[test]
+    fn skips_grouped_transactions_and_invalid_regexes() {
+        let app_data = app_data_with(vec![rule(0, None, vec![rule_flow(0, None)])]);
+        let mut grouped = transaction(0, "Grouped", -100);
+        grouped.transaction_group_id = Some(0);
+        let rule_matches = RuleEngine::new(&app_data).plan(&app_data, [grouped].iter());
+        assert!(rule_matches.is_empty());
+
+        let app_data = app_data_with(vec![
+            rule(0, Some("("), vec![rule_flow(0, None)]),
+            rule(1, None, vec![rule_flow(1, None)]),
+        ]);
+        let rule_engine = RuleEngine::new(&app_data);
+        assert_eq!(rule_engine.invalid_rule_ids, [0]);
+        let rule_matches = rule_engine.plan(&app_data, [transaction(0, "Any", -100)].iter());
+        assert_eq!(rule_matches[0].rule_id, 1);
+    }
+}
@@ -1,17 +1,274 @@
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
 
+use serde_json::Value;
 use zstd::stream::{read::Decoder, write::Encoder};
 
-use super::{AppData, FileData};
+use super::encryption::Encryption;
+use super::migrations::{migrate, FORMAT_VERSION};
+use super::{AppData, FileData, Updates};
+use crate::error::Error;
 use crate::result::Result;
 
+/// Save files start with these bytes and the format version as a little-endian `u32`, followed by
+/// the zstd-compressed JSON of the file data. Files from before the header was added are version
+/// 0. The compressed data includes a checksum, so corruption is detected when it is read.
+const MAGIC: &[u8; 4] = b"EBBF";
+
+/// Encrypted save files start with these bytes and the format version instead, followed by the
+/// encryption header and the sealed compressed data. The sealed data authenticates everything
+/// before it too.
+const ENCRYPTED_MAGIC: &[u8; 4] = b"EBBE";
+
+/// Encrypted journal entries are sealed with this as associated data, so that they can't be
+/// passed off as a save file. See `journal_aad`.
+const JOURNAL_AAD: &[u8] = b"journal";
+
+/// The number of backups kept next to each save file.
+const BACKUP_COUNT: usize = 5;
+
+fn corrupt(err: impl std::fmt::Display) -> Error {
+    Error::Corrupt(err.to_string())
+}
+
+/// `read_file_data` parses the contents of a save file, migrating it from older formats. If the
+/// file is encrypted, it is decrypted with a key derived from `passphrase`, and the key is returned
+/// too.
+fn read_file_data(
+    bytes: &[u8],
+    passphrase: Option<&str>,
+) -> Result<(FileData, Option<Encryption>)> {
+    let (is_encrypted, version, body) = match bytes.get(..MAGIC.len()) {
+        Some(magic) if magic == MAGIC || magic == ENCRYPTED_MAGIC => {
+            let version = bytes.get(4..8).ok_or_else(|| corrupt("truncated header"))?;
+            (
+                magic == ENCRYPTED_MAGIC,
+                u32::from_le_bytes(version.try_into().unwrap()),
+                &bytes[8..],
+            )
+        }
+        _ => (false, 0, bytes),
+    };
+    // Check the version first, since a newer format might not even be JSON.
+    if version > FORMAT_VERSION {
+        return Err(Error::NewerFormat(version));
+    }
+    let (compressed, encryption) = if is_encrypted {
+        let passphrase = passphrase.ok_or(Error::PassphraseRequired)?;
+        let (encryption, sealed) = Encryption::from_header(body, passphrase)?;
+        let header = &bytes[..bytes.len() - sealed.len()];
+        (encryption.open(sealed, header)?, Some(encryption))
+    } else {
+        (body.to_vec(), None)
+    };
+    let mut data: Value =
+        serde_json::from_reader(Decoder::new(compressed.as_slice()).map_err(corrupt)?)
+            .map_err(corrupt)?;
+    migrate(&mut data, version)?;
+    Ok((serde_json::from_value(data).map_err(corrupt)?, encryption))
+}
+
+fn write_file_data(app_data: &AppData, encryption: Option<&Encryption>) -> Result<Vec<u8>> {
+    let mut encoder = Encoder::new(Vec::new(), 10)?;
+    encoder.include_checksum(true)?;
+    serde_json::to_writer(&mut encoder, &app_data.file_data())?;
+    let compressed = encoder.finish()?;
+    let mut bytes = Vec::new();
+    match encryption {
+        Some(encryption) => {
+            bytes.extend_from_slice(ENCRYPTED_MAGIC);
+            bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+            bytes.extend_from_slice(&encryption.header());
+            let sealed = encryption.seal(&compressed, &bytes);
+            bytes.extend_from_slice(&sealed);
+        }
+        None => {
+            bytes.extend_from_slice(MAGIC);
+            bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+            bytes.extend_from_slice(&compressed);
+        }
+    }
+    Ok(bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// `sibling_path` returns the path of a file in the same directory as `path`, named after it.
+fn sibling_path(path: &Path, name: impl FnOnce(&str) -> String) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(name(&file_name))
+}
+
+/// `write_atomically` writes `bytes` to a temporary file next to `path`, syncs it to disk and then
+/// renames it over `path`, so that a crash leaves either the old or the new contents in place.
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<()> {
+    let temp_path = sibling_path(path, |name| format!(".{}.tmp", name));
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        // Sync the directory too, so that the rename itself survives a crash.
+        #[cfg(unix)]
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// `backups` returns the backups of the save file at `path`, newest first.
+pub fn backups(path: &Path) -> Vec<PathBuf> {
+    let prefix = sibling_path(path, |name| format!("{}.", name));
+    let prefix = prefix.file_name().unwrap_or_default().to_string_lossy();
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut backups: Vec<PathBuf> = match fs::read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|backup| {
+                backup.file_name().is_some_and(|name| {
+                    let name = name.to_string_lossy();
+                    name.starts_with(prefix.as_ref()) && name.ends_with(".bak")
+                })
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    // The timestamps in the names sort chronologically.
+    backups.sort();
+    backups.reverse();
+    backups
+}
+
+/// `back_up` copies the save file at `path`, if there is one, to a timestamped backup next to it,
+/// and removes the oldest backups beyond `BACKUP_COUNT`.
+fn back_up(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let timestamp = chrono::offset::Local::now().format("%Y%m%d-%H%M%S");
+    let backup_path = sibling_path(path, |name| format!("{}.{}.bak", name, timestamp));
+    write_atomically(&backup_path, &fs::read(path)?)?;
+    for old_backup in backups(path).into_iter().skip(BACKUP_COUNT) {
+        fs::remove_file(old_backup)?;
+    }
+    Ok(())
+}
+
+/// `corrupt_path` returns the path that the save file at `path` is kept at when a backup is
+/// restored over it.
+fn corrupt_path(path: &Path) -> PathBuf {
+    sibling_path(path, |name| format!("{}.corrupt", name))
+}
+
+/// `remove_copies` removes the backups of the save file at `path` and the copy of it kept when a
+/// backup was restored.
+fn remove_copies(path: &Path) -> Result<()> {
+    for copy in backups(path).into_iter().chain([corrupt_path(path)]) {
+        match fs::remove_file(copy) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// `journal_aad` returns the associated data that the journal entry at `index` is sealed with. It
+/// binds the entry to the key's header and to its place in the journal, so that entries can't be
+/// reordered or carried over from a journal written with another key.
+fn journal_aad(encryption: &Encryption, index: usize) -> Vec<u8> {
+    let mut aad = JOURNAL_AAD.to_vec();
+    aad.extend_from_slice(&encryption.header());
+    aad.extend_from_slice(&(index as u64).to_le_bytes());
+    aad
+}
+
+/// `read_journal` returns the batches of updates in the journal at `journal_path`, or nothing if
+/// the journal isn't newer than the save file at `path` or was written in another format version.
+/// The journal starts with a line holding the format version, followed by one batch per line; a
+/// batch cut off by a crash is missing its newline and is left out. If the save file is encrypted,
+/// so is each batch, and a batch that can't be decrypted fails with `Error::Tampered`.
+fn read_journal(
+    path: &Path,
+    journal_path: &Path,
+    encryption: Option<&Encryption>,
+) -> Result<Vec<Updates>> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    let is_newer = match (modified(journal_path), modified(path)) {
+        (Ok(journal_modified), Ok(modified)) => journal_modified > modified,
+        (Ok(_), Err(_)) => true,
+        (Err(_), _) => false,
+    };
+    let contents = match fs::read_to_string(journal_path) {
+        Ok(contents) if is_newer => contents,
+        _ => return Ok(Vec::new()),
+    };
+    let mut lines = contents.split_inclusive('\n');
+    if lines.next() != Some(format!("{}\n", FORMAT_VERSION).as_str()) {
+        return Ok(Vec::new());
+    }
+    let mut batches = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let line = match line.strip_suffix('\n') {
+            Some(line) => line,
+            None => break,
+        };
+        let updates = match encryption {
+            Some(encryption) => {
+                let sealed = from_hex(line).ok_or(Error::Tampered)?;
+                let entry = encryption.open(&sealed, &journal_aad(encryption, index))?;
+                serde_json::from_slice(&entry).map_err(corrupt)?
+            }
+            None => serde_json::from_str(line).map_err(corrupt)?,
+        };
+        batches.push(updates);
+    }
+    Ok(batches)
+}
+
 pub struct SaveFile {
     pub path: PathBuf,
     pub app_data: AppData,
     pub is_sample: bool,
     pub saved_modification: u32,
+    /// Whether the journal has been written to since the file was opened. The first write
+    /// replaces any journal left over from before.
+    journal_started: bool,
+    /// The number of batches in the journal, which is the index of the next one.
+    journal_len: usize,
+    /// The number of batches at the start of the journal that were replayed before a later one
+    /// failed to. They are kept in the journal until the file is saved, but not offered again.
+    replayed: usize,
+    /// The key the file will be encrypted with when it is next saved, if it is encrypted.
+    encryption: Option<Encryption>,
+    /// The key the file on disk is encrypted with, which is also used for the journal, so that
+    /// it can be recovered with the passphrase the file is opened with.
+    saved_encryption: Option<Encryption>,
+    passphrase_changed: bool,
 }
 
 impl SaveFile {
@@ -23,36 +280,174 @@ impl SaveFile {
             app_data,
             is_sample: false,
             saved_modification,
+            journal_started: false,
+            journal_len: 0,
+            replayed: 0,
+            encryption: None,
+            saved_encryption: None,
+            passphrase_changed: false,
         }
     }
 
-    pub fn load(path: PathBuf) -> Result<Self> {
-        let file = File::open(&path)?;
-        let reader = BufReader::new(file);
-        let decompressed_reader = Decoder::new(reader)?;
-        let file_data: FileData = serde_json::from_reader(decompressed_reader)?;
+    fn from_file_data(path: PathBuf, file_data: FileData, encryption: Option<Encryption>) -> Self {
         let app_data = AppData::from_file(file_data);
         let saved_modification = app_data.modification_count();
-        Ok(Self {
+        Self {
             path,
             app_data,
             is_sample: false,
             saved_modification,
-        })
+            journal_started: false,
+            journal_len: 0,
+            replayed: 0,
+            saved_encryption: encryption.clone(),
+            encryption,
+            passphrase_changed: false,
+        }
+    }
+
+    /// `load` loads the save file at `path`. Encrypted files need a `passphrase`; without one,
+    /// loading them fails with `Error::PassphraseRequired`.
+    pub fn load(path: PathBuf, passphrase: Option<&str>) -> Result<Self> {
+        let (file_data, encryption) = read_file_data(&std::fs::read(&path)?, passphrase)?;
+        Ok(Self::from_file_data(path, file_data, encryption))
+    }
+
+    /// `restore_backup` replaces the save file at `path` with the backup at `backup_path` and
+    /// loads it. The file being replaced is kept next to it with a `.corrupt` extension.
+    pub fn restore_backup(
+        path: PathBuf,
+        backup_path: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<Self> {
+        let bytes = fs::read(backup_path)?;
+        let (file_data, encryption) = read_file_data(&bytes, passphrase)?;
+        if path.exists() {
+            fs::copy(&path, corrupt_path(&path))?;
+        }
+        write_atomically(&path, &bytes)?;
+        Ok(Self::from_file_data(path, file_data, encryption))
     }
 
     pub fn save(&mut self) -> Result<()> {
         if !self.is_sample {
-            let file = File::create(&self.path)?;
-            let writer = BufWriter::new(file);
-            let mut compressed_writer = Encoder::new(writer, 10)?;
-            serde_json::to_writer(&mut compressed_writer, &self.app_data.file_data())?;
-            compressed_writer.finish()?;
+            let bytes = write_file_data(&self.app_data, self.encryption.as_ref())?;
+            // Copies in the old format would stay readable without the new passphrase, so the
+            // old file isn't backed up, and the copies already made are removed.
+            let encryption_changed = self.passphrase_changed
+                && (self.encryption.is_some() || self.saved_encryption.is_some());
+            if !encryption_changed {
+                back_up(&self.path)?;
+            }
+            write_atomically(&self.path, &bytes)?;
+            if encryption_changed {
+                remove_copies(&self.path)?;
+            }
             self.saved_modification = self.app_data.modification_count();
+            self.saved_encryption = self.encryption.clone();
+            self.passphrase_changed = false;
+            // Everything in the journal is in the file now.
+            self.app_data.take_journal();
+            self.discard_journal()?;
         }
         Ok(())
     }
 
+    fn journal_path(&self) -> PathBuf {
+        sibling_path(&self.path, |name| format!("{}.journal", name))
+    }
+
+    /// `write_journal` appends the updates made since it was last called to the journal next to
+    /// the file and syncs it to disk, so that they can be recovered if the app quits without
+    /// saving.
+    pub fn write_journal(&mut self) -> Result<()> {
+        let entries = self.app_data.take_journal();
+        if self.is_sample || entries.is_empty() {
+            return Ok(());
+        }
+        let mut file = if self.journal_started {
+            OpenOptions::new().append(true).open(self.journal_path())?
+        } else {
+            File::create(self.journal_path())?
+        };
+        let mut contents = String::new();
+        if !self.journal_started {
+            contents.push_str(&format!("{}\n", FORMAT_VERSION));
+            self.journal_started = true;
+            self.journal_len = 0;
+        }
+        contents.push_str(&self.journal_lines(&entries, self.journal_len));
+        file.write_all(contents.as_bytes())?;
+        file.sync_data()?;
+        self.journal_len += entries.len();
+        Ok(())
+    }
+
+    /// `journal_lines` returns `entries` as they are written to the journal, one per line,
+    /// starting at index `first_index`.
+    fn journal_lines(&self, entries: &[String], first_index: usize) -> String {
+        let mut lines = String::new();
+        for (index, entry) in (first_index..).zip(entries) {
+            match &self.saved_encryption {
+                Some(encryption) => lines.push_str(&to_hex(
+                    &encryption.seal(entry.as_bytes(), &journal_aad(encryption, index)),
+                )),
+                None => lines.push_str(entry),
+            }
+            lines.push('\n');
+        }
+        lines
+    }
+
+    /// `recoverable_updates` returns the batches of updates in the journal that were made after
+    /// the file was last saved.
+    pub fn recoverable_updates(&self) -> Result<Vec<Updates>> {
+        if self.is_sample {
+            return Ok(Vec::new());
+        }
+        Ok(read_journal(
+            &self.path,
+            &self.journal_path(),
+            self.saved_encryption.as_ref(),
+        )?
+        .into_iter()
+        .skip(self.replayed)
+        .collect())
+    }
+
+    /// `replay_journal` performs the recoverable updates again. They go back into the journal as
+    /// they are performed. The old journal is only discarded once they have all been performed;
+    /// if one of them fails, it is rewritten with everything performed since the file was saved,
+    /// followed by that batch and the ones after it, which are all that is left to recover.
+    pub fn replay_journal(&mut self) -> Result<()> {
+        let mut recoverable_updates = self.recoverable_updates()?.into_iter();
+        while let Some(updates) = recoverable_updates.next() {
+            let entry = serde_json::to_string(&updates)?;
+            if let Err(err) = self.app_data.perform_updates(updates) {
+                let mut entries = self.app_data.journal().to_vec();
+                let replayed = entries.len();
+                entries.push(entry);
+                for updates in recoverable_updates {
+                    entries.push(serde_json::to_string(&updates)?);
+                }
+                let contents = format!("{}\n{}", FORMAT_VERSION, self.journal_lines(&entries, 0));
+                write_atomically(&self.journal_path(), contents.as_bytes())?;
+                self.replayed = replayed;
+                return Err(err);
+            }
+        }
+        self.discard_journal()
+    }
+
+    pub fn discard_journal(&mut self) -> Result<()> {
+        self.journal_started = false;
+        self.replayed = 0;
+        match fs::remove_file(self.journal_path()) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
     pub fn load_sample() -> Self {
         let file_data = FileData::sample_data();
         let app_data = AppData::from_file(file_data);
@@ -62,10 +457,366 @@ impl SaveFile {
             app_data,
             is_sample: true,
             saved_modification,
+            journal_started: false,
+            journal_len: 0,
+            replayed: 0,
+            encryption: None,
+            saved_encryption: None,
+            passphrase_changed: false,
         }
     }
 
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
+
+    /// `set_passphrase` changes the passphrase the file is encrypted with when it is next saved.
+    /// Without a passphrase, the file is saved unencrypted. Saving it then removes its backups.
+    pub fn set_passphrase(&mut self, passphrase: Option<&str>) -> Result<()> {
+        self.encryption = passphrase.map(Encryption::new).transpose()?;
+        self.passphrase_changed = true;
+        Ok(())
+    }
+
     pub fn is_modified(&self) -> bool {
-        self.app_data.modification_count() != self.saved_modification
+        self.app_data.modification_count() != self.saved_modification || self.passphrase_changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Settings, Update};
+
+    fn fixture_path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name)
+    }
+
+    /// A fresh directory for a test's files, named after the test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ebbify-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read_fixture(name: &str) -> AppData {
+        let bytes = fs::read(fixture_path(name)).unwrap();
+        let (file_data, encryption) = read_file_data(&bytes, None).unwrap();
+        assert!(encryption.is_none());
+        AppData::from_file(file_data)
+    }
+
+    #[test]
+    fn migrates_v0_equivalents_and_group_ids() {
+        let app_data = read_fixture("v0.ebb");
+        let cad = app_data.currencies().get(&1).unwrap();
+        assert_eq!(cad.equivalent_base, 0.74);
+        assert_eq!(app_data.settings().base_currency_id, Some(0));
+        assert_eq!(app_data.transactions()[&4].transaction_group_id, None);
+        assert_eq!(app_data.transactions()[&0].transaction_group_id, Some(0));
+    }
+
+    #[test]
+    fn loads_v1() {
+        let app_data = read_fixture("v1.ebb");
+        assert_eq!(app_data.currencies()[&1].equivalent_base, 0.74);
+        assert!(app_data.undo_history().is_empty());
+    }
+
+    #[test]
+    fn loads_v2_history() {
+        let save_file = SaveFile::load(fixture_path("v2.ebb"), None).unwrap();
+        let app_data = &save_file.app_data;
+        let undo_labels: Vec<&str> = app_data
+            .undo_history()
+            .iter()
+            .map(|updates| updates.label())
+            .collect();
+        assert_eq!(
+            undo_labels,
+            ["Save history with the file", "Edit account 'Checking'"]
+        );
+        let redo_labels: Vec<&str> = app_data
+            .redo_history()
+            .map(|updates| updates.label())
+            .collect();
+        assert_eq!(redo_labels, ["Edit transaction 'ATM WITHDRAWAL'"]);
+        assert!(!save_file.is_modified());
+    }
+
+    #[test]
+    fn drops_history_from_older_formats() {
+        let mut bytes = fs::read(fixture_path("v2.ebb")).unwrap();
+        bytes[4..8].copy_from_slice(&(FORMAT_VERSION - 1).to_le_bytes());
+        let (file_data, _) = read_file_data(&bytes, None).unwrap();
+        let app_data = AppData::from_file(file_data);
+        assert!(app_data.undo_history().is_empty());
+        assert_eq!(app_data.redo_history().count(), 0);
+        assert_eq!(app_data.transactions().len(), 7);
+    }
+
+    #[test]
+    fn refuses_newer_formats() {
+        let bytes = fs::read(fixture_path("v2.ebb")).unwrap();
+        let mut newer = MAGIC.to_vec();
+        newer.extend_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        newer.extend_from_slice(&bytes[8..]);
+        assert!(matches!(
+            read_file_data(&newer, None),
+            Err(Error::NewerFormat(version)) if version == FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let mut bytes = fs::read(fixture_path("v2.ebb")).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0xff;
+        assert!(matches!(
+            read_file_data(&bytes, None),
+            Err(Error::Corrupt(_))
+        ));
+        assert!(matches!(
+            read_file_data(&bytes[..6], None),
+            Err(Error::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn restores_backups() {
+        let dir = temp_dir("restores-backups");
+        let path = dir.join("budget.ebb");
+        let mut save_file = SaveFile::new(path.clone());
+        save_file.save().unwrap();
+        // The second save backs up the first.
+        save_file.save().unwrap();
+        let backup_paths = backups(&path);
+        assert_eq!(backup_paths.len(), 1);
+
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&path, &bytes).unwrap();
+        assert!(matches!(
+            SaveFile::load(path.clone(), None),
+            Err(Error::Corrupt(_))
+        ));
+
+        SaveFile::restore_backup(path.clone(), &backup_paths[0], None).unwrap();
+        assert_eq!(fs::read(dir.join("budget.ebb.corrupt")).unwrap(), bytes);
+        SaveFile::load(path, None).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_journal_batches_that_fail_to_replay() {
+        let dir = temp_dir("keeps-journal-batches");
+        let path = dir.join("budget.ebb");
+        let mut save_file = SaveFile::from_file_data(path.clone(), FileData::sample_data(), None);
+        save_file.save().unwrap();
+        let settings = save_file.app_data.settings().clone();
+        let currency_id = save_file.app_data.accounts()[&0].currency_id;
+        let set_save_history = |save_history| {
+            Updates::new(
+                format!("Set save_history to {}", save_history),
+                vec![Update::SetSettings(Settings {
+                    save_history,
+                    ..settings.clone()
+                })],
+            )
+        };
+        save_file
+            .app_data
+            .perform_updates(set_save_history(true))
+            .unwrap();
+        save_file.write_journal().unwrap();
+        // Deleting a currency that an account is in fails, as would anything that no longer
+        // applies to the file.
+        let entries = [
+            Updates::new(
+                "Delete currency".into(),
+                vec![Update::DeleteCurrency(currency_id)],
+            ),
+            set_save_history(false),
+        ]
+        .map(|updates| serde_json::to_string(&updates).unwrap());
+        let mut journal = OpenOptions::new()
+            .append(true)
+            .open(save_file.journal_path())
+            .unwrap();
+        journal
+            .write_all(save_file.journal_lines(&entries, 1).as_bytes())
+            .unwrap();
+        // Make sure the journal is newer than the file, however coarse the timestamps are.
+        journal
+            .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(1))
+            .unwrap();
+
+        let mut save_file = SaveFile::load(path.clone(), None).unwrap();
+        assert_eq!(save_file.recoverable_updates().unwrap().len(), 3);
+        assert!(matches!(
+            save_file.replay_journal(),
+            Err(Error::HasDependents(..))
+        ));
+        assert!(save_file.app_data.settings().save_history);
+        let labels: Vec<String> = save_file
+            .recoverable_updates()
+            .unwrap()
+            .iter()
+            .map(|updates| updates.label().to_string())
+            .collect();
+        assert_eq!(labels, ["Delete currency", "Set save_history to false"]);
+        // The batch that was replayed stays in the journal, in case the app quits before saving.
+        let reloaded = SaveFile::load(path, None).unwrap();
+        let labels: Vec<String> = reloaded
+            .recoverable_updates()
+            .unwrap()
+            .iter()
+            .map(|updates| updates.label().to_string())
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                "Set save_history to true",
+                "Delete currency",
+                "Set save_history to false"
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn encrypted_sample() -> Vec<u8> {
+        let app_data = AppData::from_file(FileData::sample_data());
+        write_file_data(&app_data, Some(&Encryption::cheap("correct horse"))).unwrap()
+    }
+
+    #[test]
+    fn needs_the_right_passphrase() {
+        let bytes = encrypted_sample();
+        let (file_data, encryption) = read_file_data(&bytes, Some("correct horse")).unwrap();
+        assert!(encryption.is_some());
+        assert_eq!(
+            AppData::from_file(file_data).accounts().len(),
+            AppData::from_file(FileData::sample_data()).accounts().len()
+        );
+        assert!(matches!(
+            read_file_data(&bytes, None),
+            Err(Error::PassphraseRequired)
+        ));
+        assert!(matches!(
+            read_file_data(&bytes, Some("battery staple")),
+            Err(Error::WrongPassphrase)
+        ));
+    }
+
+    #[test]
+    fn detects_tampering() {
+        let mut bytes = encrypted_sample();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(matches!(
+            read_file_data(&bytes, Some("correct horse")),
+            Err(Error::Tampered)
+        ));
+    }
+
+    #[test]
+    fn detects_truncation() {
+        let bytes = encrypted_sample();
+        // Cut off in the encryption header, then in the sealed data.
+        assert!(matches!(
+            read_file_data(&bytes[..20], Some("correct horse")),
+            Err(Error::Corrupt(_))
+        ));
+        assert!(matches!(
+            read_file_data(&bytes[..bytes.len() - 10], Some("correct horse")),
+            Err(Error::Tampered)
+        ));
+    }
+
+    #[test]
+    fn detects_reordered_journal_entries() {
+        let dir = temp_dir("reordered-journal-entries");
+        let path = dir.join("budget.ebb");
+        let encryption = Encryption::cheap("correct horse");
+        let mut save_file =
+            SaveFile::from_file_data(path.clone(), FileData::sample_data(), Some(encryption));
+        save_file.save().unwrap();
+        for save_history in [true, false] {
+            let settings = Settings {
+                save_history,
+                ..save_file.app_data.settings().clone()
+            };
+            save_file
+                .app_data
+                .perform_update("Set save_history", vec![Update::SetSettings(settings)])
+                .unwrap();
+            save_file.write_journal().unwrap();
+        }
+        let journal_path = save_file.journal_path();
+        let write_journal = |contents: &str| {
+            fs::write(&journal_path, contents).unwrap();
+            // Make sure the journal is newer than the file, however coarse the timestamps are.
+            File::options()
+                .append(true)
+                .open(&journal_path)
+                .unwrap()
+                .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(1))
+                .unwrap();
+        };
+        let contents = fs::read_to_string(&journal_path).unwrap();
+        write_journal(&contents);
+        let load = || SaveFile::load(path.clone(), Some("correct horse")).unwrap();
+        assert_eq!(load().recoverable_updates().unwrap().len(), 2);
+
+        let lines: Vec<&str> = contents.lines().collect();
+        write_journal(&format!("{}\n{}\n{}\n", lines[0], lines[2], lines[1]));
+        assert!(matches!(load().recoverable_updates(), Err(Error::Tampered)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn setting_a_passphrase_leaves_no_plaintext_copies() {
+        let dir = temp_dir("no-plaintext-copies");
+        let path = dir.join("budget.ebb");
+        let mut save_file = SaveFile::from_file_data(path.clone(), FileData::sample_data(), None);
+        save_file.save().unwrap();
+        save_file.save().unwrap();
+        // Restoring the backup keeps a plaintext copy of the file it replaces.
+        SaveFile::restore_backup(path.clone(), &backups(&path)[0], None).unwrap();
+        assert!(corrupt_path(&path).exists());
+        let label = "Set save_history to true";
+        let settings = Settings {
+            save_history: true,
+            ..save_file.app_data.settings().clone()
+        };
+        save_file
+            .app_data
+            .perform_updates(Updates::new(
+                label.into(),
+                vec![Update::SetSettings(settings)],
+            ))
+            .unwrap();
+        save_file.write_journal().unwrap();
+
+        // `set_passphrase` derives the key with the full parameters, which is slow.
+        save_file.encryption = Some(Encryption::cheap("correct horse"));
+        save_file.passphrase_changed = true;
+        save_file.save().unwrap();
+        // The next save backs up the encrypted file.
+        save_file.save().unwrap();
+        assert_eq!(backups(&path).len(), 1);
+        for entry in fs::read_dir(&dir).unwrap() {
+            let bytes = fs::read(entry.unwrap().path()).unwrap();
+            assert!(!bytes.windows(MAGIC.len()).any(|window| window == MAGIC));
+            assert!(!bytes
+                .windows(label.len())
+                .any(|window| window == label.as_bytes()));
+        }
+        SaveFile::load(path, Some("correct horse")).unwrap();
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,376 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Bound::{Excluded, Included};
+use std::ops::RangeBounds;
+use std::path::Path;
+
+use chrono::naive::NaiveDate as Date;
+
+use super::{
+    next_id, AppData, Balance, Money, RuleEngine, RuleMatch, Statement, Transaction, Update,
+};
+use crate::error::Error;
+use crate::result::Result;
+
+pub use bank_statement_parser::Statement as ParsedStatement;
+
+pub fn parse_statement(path: &Path) -> Result<ParsedStatement> {
+    bank_statement_parser::parse_file(path)
+        .map_err(|err| Error::StatementParse(format!("{:?}", err)))
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher`, the result is stable across builds, so it can be saved.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ (*byte as u64)).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn statement_fingerprint(parsed_statement: &ParsedStatement) -> u64 {
+    let mut hash = 0xcbf29ce484222325;
+    hash = fnv1a(hash, parsed_statement.start_date.to_string().as_bytes());
+    hash = fnv1a(hash, parsed_statement.end_date.to_string().as_bytes());
+    for transaction in parsed_statement.transactions.iter() {
+        hash = fnv1a(hash, transaction.date.to_string().as_bytes());
+        hash = fnv1a(hash, transaction.description.as_bytes());
+        hash = fnv1a(hash, &transaction.amount.to_le_bytes());
+    }
+    hash
+}
+
+/// Identifies transactions that are likely to be the same real-world transaction.
+#[derive(PartialEq, Eq, Hash)]
+struct TransactionKey {
+    account_id: u32,
+    date: Date,
+    amount: Money,
+    description: String,
+}
+
+impl TransactionKey {
+    fn of_transaction(transaction: &Transaction) -> Self {
+        Self {
+            account_id: transaction.account_id,
+            date: transaction.date,
+            amount: transaction.amount,
+            description: Self::normalize_description(&transaction.description),
+        }
+    }
+
+    /// Lowercases the description and strips punctuation and repeated whitespace, which tend to
+    /// vary between statement formats.
+    fn normalize_description(description: &str) -> String {
+        description
+            .split_whitespace()
+            .map(|word| {
+                word.chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .flat_map(|c| c.to_lowercase())
+                    .collect::<String>()
+            })
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// A parsed statement converted into a `Statement` and its `Transaction`s, staged for review
+/// before being added to `AppData`. The staged ids only identify the transactions while they are
+/// reviewed; the ids they are added with are allocated by `updates`.
+pub struct StatementImport {
+    pub statement: Statement,
+    pub transactions: Vec<Transaction>,
+    /// A previously imported statement with the same fingerprint.
+    pub repeated_statement_id: Option<u32>,
+    /// Ids of the staged transactions that match a transaction already in the account.
+    pub duplicate_ids: HashSet<u32>,
+}
+
+impl StatementImport {
+    pub fn new(
+        app_data: &AppData,
+        account_id: u32,
+        parsed_statement: &ParsedStatement,
+        import_date: Date,
+    ) -> Self {
+        let statement_id = next_id(app_data.statements());
+        let first_transaction_id = next_id(app_data.transactions());
+        let transactions = parsed_statement
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(index, parsed_transaction)| Transaction {
+                id: first_transaction_id + index as u32,
+                account_id,
+                date: parsed_transaction.date,
+                description: parsed_transaction.description.clone(),
+                amount: Money::from_minor(parsed_transaction.amount.into()),
+                transaction_group_id: None,
+                statement_id: Some(statement_id),
+                original: None,
+                transfer_transaction_id: None,
+            })
+            .collect();
+        let fingerprint = statement_fingerprint(parsed_statement);
+        let mut statement_import = Self {
+            statement: Statement {
+                id: statement_id,
+                account_id,
+                start_date: parsed_statement.start_date,
+                end_date: parsed_statement.end_date,
+                import_date,
+                fingerprint,
+            },
+            transactions,
+            repeated_statement_id: app_data
+                .statements()
+                .values()
+                .find(|statement| statement.fingerprint == fingerprint)
+                .map(|statement| statement.id),
+            duplicate_ids: HashSet::new(),
+        };
+        statement_import.find_duplicates(app_data);
+        statement_import
+    }
+
+    pub fn set_account(&mut self, app_data: &AppData, account_id: u32) {
+        self.statement.account_id = account_id;
+        for transaction in self.transactions.iter_mut() {
+            transaction.account_id = account_id;
+        }
+        self.find_duplicates(app_data);
+    }
+
+    /// Marks staged transactions that match existing transactions in the account. Since
+    /// statement periods can overlap, this compares against every existing transaction in the
+    /// date range of the staged transactions, not just those from a repeated statement. Each
+    /// existing transaction can only account for one staged transaction.
+    pub fn find_duplicates(&mut self, app_data: &AppData) {
+        self.duplicate_ids.clear();
+        let first_date = self.transactions.iter().map(|t| t.date).min();
+        let last_date = self.transactions.iter().map(|t| t.date).max();
+        let (first_date, last_date) = match (first_date, last_date) {
+            (Some(first_date), Some(last_date)) => (first_date, last_date),
+            _ => return,
+        };
+        let mut existing_keys: HashMap<TransactionKey, usize> = HashMap::new();
+        if let Some(account_index) = app_data.account_index(self.statement.account_id) {
+            for transaction_id in account_index.transaction_ids(first_date, last_date) {
+                let transaction = app_data.transactions().get(&transaction_id).unwrap();
+                *existing_keys
+                    .entry(TransactionKey::of_transaction(transaction))
+                    .or_insert(0) += 1;
+            }
+        }
+        for transaction in self.transactions.iter() {
+            if let Some(count) = existing_keys.get_mut(&TransactionKey::of_transaction(transaction))
+            {
+                if *count > 0 {
+                    *count -= 1;
+                    self.duplicate_ids.insert(transaction.id);
+                }
+            }
+        }
+    }
+
+    fn included_transactions<'a>(
+        &'a self,
+        included_ids: &'a HashSet<u32>,
+    ) -> impl Iterator<Item = &'a Transaction> + Clone {
+        self.transactions
+            .iter()
+            .filter(|transaction| included_ids.contains(&transaction.id))
+    }
+
+    /// `balance_checks` returns each of the account's balances that the included transactions
+    /// affect, paired with the balance that would be computed on that date after the import. The
+    /// final entry is dated `Date::MAX` and holds the latest computed balance. Computed balances
+    /// that are too large to represent are `None`.
+    pub fn balance_checks(
+        &self,
+        app_data: &AppData,
+        included_ids: &HashSet<u32>,
+    ) -> Vec<(Balance, Option<Money>)> {
+        let account = match app_data.accounts().get(&self.statement.account_id) {
+            Some(account) => account,
+            None => return Vec::new(),
+        };
+        let first_date = match self
+            .included_transactions(included_ids)
+            .map(|transaction| transaction.date)
+            .min()
+        {
+            Some(first_date) => first_date,
+            None => self.statement.start_date,
+        };
+        let mut balance_checks: Vec<(Balance, Option<Money>)> = account
+            .balances
+            .iter()
+            .filter(|balance| balance.date >= first_date)
+            .map(|balance| {
+                (
+                    balance.clone(),
+                    account.balance_on_date(app_data, balance.date),
+                )
+            })
+            .collect();
+        balance_checks.push((
+            Balance {
+                date: Date::MAX,
+                amount: Money::ZERO,
+            },
+            account.current_amount(app_data),
+        ));
+        for (balance, computed) in balance_checks.iter_mut() {
+            let previous_date = account.latest_balance_before(balance.date).date;
+            *computed = computed.and_then(|computed| {
+                self.included_transactions(included_ids)
+                    .filter(|transaction| {
+                        (Excluded(previous_date), Included(balance.date))
+                            .contains(&transaction.date)
+                    })
+                    .try_fold(computed, |total, transaction| {
+                        total.checked_add(transaction.amount)
+                    })
+            });
+        }
+        balance_checks
+    }
+
+    /// `updates` returns the updates that add the statement, along with the transactions whose
+    /// ids are in `included_ids`, to `AppData`. Rules are applied to the added transactions,
+    /// except for those with an invalid description regex. Ids are allocated from `app_data` as it
+    /// is now, since other entities may have been added while the import was being reviewed.
+    pub fn updates(&self, app_data: &AppData, included_ids: &HashSet<u32>) -> Vec<Update> {
+        let statement_id = next_id(app_data.statements());
+        let first_transaction_id = next_id(app_data.transactions());
+        let mut updates = vec![Update::SetStatement(Statement {
+            id: statement_id,
+            ..self.statement.clone()
+        })];
+        let included_transactions: Vec<Transaction> = self
+            .included_transactions(included_ids)
+            .enumerate()
+            .map(|(index, transaction)| Transaction {
+                id: first_transaction_id + index as u32,
+                statement_id: Some(statement_id),
+                ..transaction.clone()
+            })
+            .collect();
+        let mut rule_matches: HashMap<u32, RuleMatch> = RuleEngine::new(app_data)
+            .plan(app_data, included_transactions.iter())
+            .into_iter()
+            .map(|rule_match| (rule_match.transaction.id, rule_match))
+            .collect();
+        for transaction in included_transactions {
+            match rule_matches.remove(&transaction.id) {
+                Some(rule_match) => updates.extend(rule_match.updates()),
+                None => updates.push(Update::SetTransaction(transaction)),
+            }
+        }
+        updates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_fixtures::{self, account, currency};
+
+    fn date(day: u32) -> Date {
+        Date::from_ymd_opt(2023, 5, day).unwrap()
+    }
+
+    fn transaction(
+        id: u32,
+        account_id: u32,
+        day: u32,
+        description: &str,
+        amount: i64,
+    ) -> Transaction {
+        Transaction {
+            id,
+            account_id,
+            date: date(day),
+            description: description.into(),
+            amount: Money::from_minor(amount),
+            transaction_group_id: None,
+            statement_id: None,
+            original: None,
+            transfer_transaction_id: None,
+        }
+    }
+
+    /// Two accounts, the first of which has the given transactions.
+    fn app_data_with(transactions: Vec<Transaction>) -> AppData {
+        let mut updates = vec![
+            Update::SetCurrency(currency(0, "USD")),
+            Update::SetAccount(account(0, 0, true)),
+            Update::SetAccount(account(1, 0, true)),
+        ];
+        updates.extend(transactions.into_iter().map(Update::SetTransaction));
+        test_fixtures::app_data_with(updates)
+    }
+
+    fn staged(
+        app_data: &AppData,
+        account_id: u32,
+        transactions: Vec<Transaction>,
+    ) -> StatementImport {
+        let mut statement_import = StatementImport {
+            statement: Statement {
+                id: 0,
+                account_id,
+                start_date: date(1),
+                end_date: date(31),
+                import_date: date(31),
+                fingerprint: 0,
+            },
+            transactions,
+            repeated_statement_id: None,
+            duplicate_ids: HashSet::new(),
+        };
+        statement_import.set_account(app_data, account_id);
+        statement_import
+    }
+
+    #[test]
+    fn finds_duplicates_despite_punctuation_and_case() {
+        let app_data = app_data_with(vec![transaction(0, 0, 3, "COFFEE SHOP #12", -450)]);
+        let statement_import = staged(
+            &app_data,
+            0,
+            vec![
+                transaction(10, 0, 3, "Coffee  shop 12", -450),
+                transaction(11, 0, 3, "Coffee shop 12", -451),
+                transaction(12, 0, 4, "Coffee shop 12", -450),
+            ],
+        );
+        assert_eq!(statement_import.duplicate_ids, HashSet::from([10]));
+    }
+
+    #[test]
+    fn matches_each_existing_transaction_once() {
+        let app_data = app_data_with(vec![
+            transaction(0, 0, 3, "Bus fare", -275),
+            transaction(1, 0, 3, "Bus fare", -275),
+        ]);
+        let statement_import = staged(
+            &app_data,
+            0,
+            (10..13)
+                .map(|id| transaction(id, 0, 3, "BUS FARE", -275))
+                .collect(),
+        );
+        assert_eq!(statement_import.duplicate_ids, HashSet::from([10, 11]));
+    }
+
+    #[test]
+    fn only_compares_against_the_statement_account() {
+        let app_data = app_data_with(vec![transaction(0, 0, 3, "Rent", -120000)]);
+        let mut statement_import =
+            staged(&app_data, 1, vec![transaction(10, 1, 3, "Rent", -120000)]);
+        assert!(statement_import.duplicate_ids.is_empty());
+        statement_import.set_account(&app_data, 0);
+        assert_eq!(statement_import.duplicate_ids, HashSet::from([10]));
+    }
+}
@@ -0,0 +1,42 @@
+use super::{Account, AppData, Category, Currency, Update};
+
+/// `app_data_with` returns `AppData` that `updates` have been performed on.
+pub fn app_data_with(updates: Vec<Update>) -> AppData {
+    let mut app_data = AppData::new();
+    app_data.perform_update("Set up", updates).unwrap();
+    app_data
+}
+
+/// A currency written like dollars, with cents and an `equivalent_base` of 1.
+pub fn currency(id: u32, code: &str) -> Currency {
+    Currency {
+        id,
+        code: code.into(),
+        major: 100,
+        equivalent_base: 1.0,
+        symbol: "$".into(),
+        number_format: Default::default(),
+        symbol_position: Default::default(),
+        exchange_rates: Default::default(),
+    }
+}
+
+pub fn account(id: u32, currency_id: u32, debit_account: bool) -> Account {
+    Account {
+        id,
+        name: format!("Account {}", id),
+        currency_id,
+        debit_account,
+        balances: Vec::new(),
+    }
+}
+
+pub fn category(id: u32, name: &str, parent_id: Option<u32>) -> Category {
+    Category {
+        id,
+        name: name.into(),
+        parent_id,
+        default_amortization_type: None,
+        default_amortization_length: None,
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{AppData, Deletion, Flow, Money, Transaction, TransactionGroup, Update};
+
+/// `unallocated_amounts` returns, for each currency, the total of the transactions minus the
+/// total of the flows. A group is balanced when every amount is zero. A transaction with an
+/// original amount counts in the original currency if any of the flows are in that currency, and
+/// in the account's currency otherwise. Transfers need no flows, so they don't count at all.
+/// Transactions that no longer exist are skipped. Amounts that are too large to represent are
+/// `None`.
+pub fn unallocated_amounts(
+    app_data: &AppData,
+    transaction_ids: &[u32],
+    flows: &[Flow],
+) -> BTreeMap<u32, Option<Money>> {
+    // No number of `i64` amounts can overflow an `i128`.
+    let mut unallocated: BTreeMap<u32, i128> = BTreeMap::new();
+    for transaction_id in transaction_ids {
+        let transaction = match app_data.transactions().get(transaction_id) {
+            Some(transaction) => transaction,
+            None => continue,
+        };
+        if transaction.transfer_transaction_id.is_some() {
+            continue;
+        }
+        let account = match app_data.accounts().get(&transaction.account_id) {
+            Some(account) => account,
+            None => continue,
+        };
+        match transaction.original {
+            Some(original)
+                if flows
+                    .iter()
+                    .any(|flow| flow.currency_id == original.currency_id) =>
+            {
+                *unallocated.entry(original.currency_id).or_default() +=
+                    original.amount.minor() as i128;
+            }
+            _ => {
+                *unallocated.entry(account.currency_id).or_default() +=
+                    transaction.amount.minor() as i128
+            }
+        }
+    }
+    for flow in flows {
+        *unallocated.entry(flow.currency_id).or_default() -= flow.amount.minor() as i128;
+    }
+    unallocated
+        .into_iter()
+        .map(|(currency_id, amount)| {
+            let amount = i64::try_from(amount).ok().map(Money::from_minor);
+            (currency_id, amount)
+        })
+        .collect()
+}
+
+pub fn is_balanced(app_data: &AppData, transaction_ids: &[u32], flows: &[Flow]) -> bool {
+    unallocated_amounts(app_data, transaction_ids, flows)
+        .values()
+        .all(|amount| *amount == Some(Money::ZERO))
+}
+
+/// `transaction_group_updates` returns the updates that make `transaction_ids` and `flows` the
+/// entire contents of the group `group_id`. Transactions and flows previously in the group (or in
+/// any of `merged_group_ids`, which are deleted) but not in the new contents are unassigned and
+/// deleted respectively. If `transaction_ids` is empty, the group itself is deleted. Transactions
+/// that no longer exist are left out.
+pub fn transaction_group_updates(
+    app_data: &AppData,
+    group_id: u32,
+    merged_group_ids: &[u32],
+    transaction_ids: &[u32],
+    flows: Vec<Flow>,
+) -> Vec<Update> {
+    let transaction_ids: Vec<u32> = transaction_ids
+        .iter()
+        .copied()
+        .filter(|id| app_data.transactions().contains_key(id))
+        .collect();
+    let mut old_transaction_ids = BTreeSet::new();
+    let mut old_flow_ids = BTreeSet::new();
+    for id in std::iter::once(&group_id).chain(merged_group_ids.iter()) {
+        if let Some(transaction_group) = app_data.transaction_groups().get(id) {
+            old_transaction_ids.extend(transaction_group.transaction_ids.iter().copied());
+            old_flow_ids.extend(transaction_group.flow_ids.iter().copied());
+        }
+    }
+    // Flows can't exist outside of a group, so deleting the group deletes all of them.
+    let flows = if transaction_ids.is_empty() {
+        Vec::new()
+    } else {
+        flows
+    };
+    let new_transaction_ids: BTreeSet<u32> = transaction_ids.iter().copied().collect();
+    let new_flow_ids: BTreeSet<u32> = flows.iter().map(|flow| flow.id).collect();
+
+    let mut updates = Vec::new();
+    for id in old_flow_ids.difference(&new_flow_ids) {
+        updates.push(Update::DeleteFlow(*id));
+    }
+    for id in old_transaction_ids.difference(&new_transaction_ids) {
+        let transaction = match app_data.transactions().get(id) {
+            Some(transaction) => transaction,
+            None => continue,
+        };
+        updates.push(Update::SetTransaction(Transaction {
+            transaction_group_id: None,
+            ..transaction.clone()
+        }));
+    }
+    for id in merged_group_ids.iter().filter(|id| **id != group_id) {
+        updates.push(Update::DeleteTransactionGroup(*id));
+    }
+    if transaction_ids.is_empty() {
+        if app_data.transaction_groups().contains_key(&group_id) {
+            updates.push(Update::DeleteTransactionGroup(group_id));
+        }
+        return updates;
+    }
+    for id in transaction_ids.iter() {
+        let transaction = app_data.transactions().get(id).unwrap();
+        if transaction.transaction_group_id != Some(group_id) {
+            updates.push(Update::SetTransaction(Transaction {
+                transaction_group_id: Some(group_id),
+                ..transaction.clone()
+            }));
+        }
+    }
+    let flow_ids = flows.iter().map(|flow| flow.id).collect();
+    for flow in flows.into_iter() {
+        updates.push(Update::SetFlow(Flow {
+            transaction_group_id: group_id,
+            ..flow
+        }));
+    }
+    updates.push(Update::SetTransactionGroup(TransactionGroup {
+        id: group_id,
+        transaction_ids,
+        flow_ids,
+    }));
+    updates
+}
+
+/// `delete_transactions_updates` returns the updates that delete `transaction_ids`, removing them
+/// from their groups. Groups left without any transactions are deleted along with their flows.
+pub fn delete_transactions_updates(app_data: &AppData, transaction_ids: &[u32]) -> Vec<Update> {
+    Deletion::transactions(transaction_ids).updates(app_data)
+}
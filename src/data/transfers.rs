@@ -0,0 +1,579 @@
+use std::collections::BTreeSet;
+
+use chrono::naive::NaiveDate as Date;
+use chrono::Days;
+
+use super::{
+    convert, transaction_group_updates, unallocated_amounts, AppData, Flow, Money, Transaction,
+    Update,
+};
+
+/// How far apart the amounts of a suggested transfer between currencies may be, as a fraction of
+/// the amount, to allow for fees and rates that differ from ours.
+const CROSS_CURRENCY_TOLERANCE: f64 = 0.05;
+
+/// A transfer suggested by `suggest_transfers`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TransferSuggestion {
+    /// The transaction that money left from.
+    pub from_id: u32,
+    /// The transaction that money arrived in.
+    pub to_id: u32,
+}
+
+/// `net_worth_change` is how much `transaction` changes net worth by, in its account's currency.
+/// Credit account balances are owed, so a positive amount there is money going out.
+pub fn net_worth_change(app_data: &AppData, transaction: &Transaction) -> Option<Money> {
+    let account = app_data.accounts().get(&transaction.account_id)?;
+    if account.debit_account {
+        Some(transaction.amount)
+    } else {
+        transaction.amount.checked_neg()
+    }
+}
+
+/// A transaction on one side of a transfer, with the amount that it changes net worth by.
+struct TransferSide<'a> {
+    transaction: &'a Transaction,
+    amount: Money,
+}
+
+impl<'a> TransferSide<'a> {
+    fn new(app_data: &AppData, transaction: &'a Transaction) -> Option<Self> {
+        Some(Self {
+            transaction,
+            amount: net_worth_change(app_data, transaction)?,
+        })
+    }
+}
+
+/// The two sides of the transfer that `transaction` is part of, with the side that money left
+/// from first.
+fn transfer_sides<'a>(
+    app_data: &'a AppData,
+    transaction: &'a Transaction,
+) -> Option<(TransferSide<'a>, TransferSide<'a>)> {
+    let other = app_data
+        .transactions()
+        .get(&transaction.transfer_transaction_id?)?;
+    let side = TransferSide::new(app_data, transaction)?;
+    let other = TransferSide::new(app_data, other)?;
+    if side.amount.is_negative() && !other.amount.is_negative() {
+        Some((side, other))
+    } else if other.amount.is_negative() && !side.amount.is_negative() {
+        Some((other, side))
+    } else {
+        None
+    }
+}
+
+/// The number of major units of the receiving account's currency that each major unit sent
+/// became.
+pub fn transfer_rate(app_data: &AppData, transaction: &Transaction) -> Option<f64> {
+    let (from, to) = transfer_sides(app_data, transaction)?;
+    let from_currency = app_data.account_currency(from.transaction.account_id)?;
+    let to_currency = app_data.account_currency(to.transaction.account_id)?;
+    if from.amount == Money::ZERO {
+        return None;
+    }
+    Some(
+        (to.amount.minor() as f64 / to_currency.major as f64)
+            / (-(from.amount.minor() as f64) / from_currency.major as f64),
+    )
+}
+
+/// `transfer_fee` is how much of the amount sent didn't arrive, valuing the amount received at the
+/// exchange rate on the date it was sent. It is in the sending account's currency.
+pub fn transfer_fee(app_data: &AppData, transaction: &Transaction) -> Option<Money> {
+    let (from, to) = transfer_sides(app_data, transaction)?;
+    let from_currency = app_data.account_currency(from.transaction.account_id)?;
+    let to_currency = app_data.account_currency(to.transaction.account_id)?;
+    let received = convert(
+        app_data,
+        to.amount,
+        to_currency,
+        from_currency,
+        from.transaction.date,
+    )?;
+    from.amount.checked_neg()?.checked_sub(received)
+}
+
+/// `scale_amounts` scales `amounts` in proportion so that their total changes by `difference`,
+/// rounding so that the new total is exact. Returns `None` if they add up to zero, or any of the
+/// new amounts is too large to represent.
+fn scale_amounts(amounts: &[Money], difference: Money) -> Option<Vec<Money>> {
+    let total: i128 = amounts.iter().map(|amount| amount.minor() as i128).sum();
+    if total == 0 {
+        return None;
+    }
+    let target = total + difference.minor() as i128;
+    let mut cumulative = 0;
+    let mut allocated = 0;
+    amounts
+        .iter()
+        .map(|amount| {
+            cumulative += amount.minor() as i128;
+            // Rounds `cumulative * target / total` to the nearest integer.
+            let scaled = cumulative
+                .checked_mul(target)?
+                .checked_mul(2 * total.signum())?
+                .checked_add(total.abs())?
+                .div_euclid(2 * total.abs());
+            let part = i64::try_from(scaled - allocated).ok()?;
+            allocated = scaled;
+            Some(Money::from_minor(part))
+        })
+        .collect()
+}
+
+/// `rebalanced_flows` scales the flows in each currency so that they add up to the transactions
+/// `transaction_ids` again. Flows that scale to zero are left out. Flows in a currency that add up
+/// to zero, or that would become too large to represent, are left as they are.
+fn rebalanced_flows(
+    app_data: &AppData,
+    transaction_ids: &[u32],
+    mut flows: Vec<Flow>,
+) -> Vec<Flow> {
+    let mut scaled_ids = BTreeSet::new();
+    for (currency_id, difference) in unallocated_amounts(app_data, transaction_ids, &flows) {
+        let difference = match difference {
+            Some(difference) if difference != Money::ZERO => difference,
+            _ => continue,
+        };
+        let amounts: Vec<Money> = flows
+            .iter()
+            .filter(|flow| flow.currency_id == currency_id)
+            .map(|flow| flow.amount)
+            .collect();
+        let scaled_amounts = match scale_amounts(&amounts, difference) {
+            Some(scaled_amounts) => scaled_amounts,
+            None => continue,
+        };
+        for (flow, amount) in flows
+            .iter_mut()
+            .filter(|flow| flow.currency_id == currency_id)
+            .zip(scaled_amounts)
+        {
+            flow.amount = amount;
+            scaled_ids.insert(flow.id);
+        }
+    }
+    flows.retain(|flow| flow.amount != Money::ZERO || !scaled_ids.contains(&flow.id));
+    flows
+}
+
+/// `link_transfer_updates` returns the updates that link the transactions `id` and `other_id` as
+/// the two sides of a transfer. Transfers don't belong in any category, so both are removed from
+/// their groups, whose flows are scaled to balance the transactions left in them. Any transfers
+/// they were already part of are unlinked.
+pub fn link_transfer_updates(app_data: &AppData, id: u32, other_id: u32) -> Vec<Update> {
+    let ids = [id, other_id];
+    let transactions: Vec<&Transaction> = ids
+        .iter()
+        .filter_map(|id| app_data.transactions().get(id))
+        .collect();
+    if transactions.len() != 2 || id == other_id {
+        return Vec::new();
+    }
+
+    let mut updates = Vec::new();
+    let group_ids: BTreeSet<u32> = transactions
+        .iter()
+        .filter_map(|transaction| transaction.transaction_group_id)
+        .collect();
+    for group_id in group_ids {
+        let transaction_group = match app_data.transaction_groups().get(&group_id) {
+            Some(transaction_group) => transaction_group,
+            None => continue,
+        };
+        let remaining_ids: Vec<u32> = transaction_group
+            .transaction_ids
+            .iter()
+            .filter(|id| !ids.contains(id))
+            .copied()
+            .collect();
+        let flows = transaction_group
+            .flow_ids
+            .iter()
+            .filter_map(|id| app_data.flows().get(id))
+            .cloned()
+            .collect();
+        let flows = rebalanced_flows(app_data, &remaining_ids, flows);
+        // The transfer transactions are set below, with their groups cleared.
+        updates.extend(
+            transaction_group_updates(app_data, group_id, &[], &remaining_ids, flows)
+                .into_iter()
+                .filter(|update| {
+                    !matches!(update, Update::SetTransaction(transaction)
+                        if ids.contains(&transaction.id))
+                }),
+        );
+    }
+
+    for (transaction, other) in [
+        (transactions[0], transactions[1]),
+        (transactions[1], transactions[0]),
+    ] {
+        if let Some(old_other) = transaction
+            .transfer_transaction_id
+            .filter(|old_other_id| *old_other_id != other.id)
+            .and_then(|old_other_id| app_data.transactions().get(&old_other_id))
+        {
+            if !ids.contains(&old_other.id) {
+                updates.push(Update::SetTransaction(Transaction {
+                    transfer_transaction_id: None,
+                    ..old_other.clone()
+                }));
+            }
+        }
+        updates.push(Update::SetTransaction(Transaction {
+            transaction_group_id: None,
+            transfer_transaction_id: Some(other.id),
+            ..transaction.clone()
+        }));
+    }
+    updates
+}
+
+/// `unlink_transfer_updates` returns the updates that unlink the transfer that the transaction
+/// `id` is part of.
+pub fn unlink_transfer_updates(app_data: &AppData, id: u32) -> Vec<Update> {
+    let transaction = match app_data.transactions().get(&id) {
+        Some(transaction) => transaction,
+        None => return Vec::new(),
+    };
+    std::iter::once(transaction)
+        .chain(
+            transaction
+                .transfer_transaction_id
+                .and_then(|other_id| app_data.transactions().get(&other_id)),
+        )
+        .filter(|transaction| transaction.transfer_transaction_id.is_some())
+        .map(|transaction| {
+            Update::SetTransaction(Transaction {
+                transfer_transaction_id: None,
+                ..transaction.clone()
+            })
+        })
+        .collect()
+}
+
+/// How closely `to` matches `from` as the other side of a transfer, lower being closer, or `None`
+/// if it doesn't match at all.
+fn match_score(app_data: &AppData, from: &TransferSide, to: &TransferSide) -> Option<(i64, f64)> {
+    let from_currency = app_data.account_currency(from.transaction.account_id)?;
+    let to_currency = app_data.account_currency(to.transaction.account_id)?;
+    let days_apart = (to.transaction.date - from.transaction.date)
+        .num_days()
+        .abs();
+    if from_currency.id == to_currency.id {
+        return (to.amount == from.amount.checked_neg()?).then_some((days_apart, 0.0));
+    }
+    let expected = convert(
+        app_data,
+        from.amount.checked_neg()?,
+        from_currency,
+        to_currency,
+        from.transaction.date,
+    )?;
+    if expected == Money::ZERO {
+        return None;
+    }
+    let difference =
+        (to.amount.checked_sub(expected)?.minor() as f64 / expected.minor() as f64).abs();
+    (difference <= CROSS_CURRENCY_TOLERANCE).then_some((days_apart, difference))
+}
+
+/// `suggest_transfers` pairs up transactions that look like the two sides of a transfer: money
+/// leaving one account and the same amount, converted if need be, arriving in another within
+/// `max_days` days. Paying off a credit account counts as money arriving in it. Only transactions
+/// that aren't grouped or already transfers are considered, and each is suggested at most once,
+/// closest matches first.
+pub fn suggest_transfers(app_data: &AppData, max_days: u64) -> Vec<TransferSuggestion> {
+    let is_candidate = |transaction: &Transaction| {
+        transaction.transaction_group_id.is_none() && transaction.transfer_transaction_id.is_none()
+    };
+    let mut matches = Vec::new();
+    for from in app_data.transactions().values() {
+        if !is_candidate(from) {
+            continue;
+        }
+        let from = match TransferSide::new(app_data, from) {
+            Some(from) if from.amount.is_negative() => from,
+            _ => continue,
+        };
+        let start = from
+            .transaction
+            .date
+            .checked_sub_days(Days::new(max_days))
+            .unwrap_or(Date::MIN);
+        let end = from
+            .transaction
+            .date
+            .checked_add_days(Days::new(max_days))
+            .unwrap_or(Date::MAX);
+        for to_id in app_data
+            .transactions_by_date()
+            .range(start..=end)
+            .flat_map(|(_, ids)| ids.iter())
+        {
+            let to = app_data.transactions().get(to_id).unwrap();
+            if to.account_id == from.transaction.account_id || !is_candidate(to) {
+                continue;
+            }
+            let to = match TransferSide::new(app_data, to) {
+                Some(to) if !to.amount.is_negative() => to,
+                _ => continue,
+            };
+            if let Some(score) = match_score(app_data, &from, &to) {
+                matches.push((score, from.transaction.id, to.transaction.id));
+            }
+        }
+    }
+    matches.sort_by(|((a_days, a), _, _), ((b_days, b), _, _)| {
+        a_days.cmp(b_days).then(a.total_cmp(b))
+    });
+
+    let mut used = BTreeSet::new();
+    let mut suggestions = Vec::new();
+    for (_, from_id, to_id) in matches {
+        if used.contains(&from_id) || used.contains(&to_id) {
+            continue;
+        }
+        used.insert(from_id);
+        used.insert(to_id);
+        suggestions.push(TransferSuggestion { from_id, to_id });
+    }
+    suggestions.sort_by_key(|suggestion| {
+        (
+            app_data
+                .transactions()
+                .get(&suggestion.from_id)
+                .unwrap()
+                .date,
+            suggestion.from_id,
+        )
+    });
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_fixtures::{self, account, category, currency};
+    use crate::data::{is_balanced, Currency, TransactionGroup};
+
+    fn date(day: u32) -> Date {
+        Date::from_ymd_opt(2023, 5, day).unwrap()
+    }
+
+    fn transaction(id: u32, account_id: u32, day: u32, amount: i64) -> Transaction {
+        Transaction {
+            id,
+            account_id,
+            date: date(day),
+            description: format!("Transaction {}", id),
+            amount: Money::from_minor(amount),
+            transaction_group_id: None,
+            statement_id: None,
+            original: None,
+            transfer_transaction_id: None,
+        }
+    }
+
+    fn flow(id: u32, category_id: u32, amount: i64, transaction_group_id: u32) -> Flow {
+        Flow {
+            id,
+            category_id,
+            date: date(1),
+            description: None,
+            amount: Money::from_minor(amount),
+            currency_id: 0,
+            amortization_type: None,
+            amortization_length: None,
+            transaction_group_id,
+        }
+    }
+
+    /// USD and CAD, with USD checking (0) and savings (1) accounts, a USD credit card (2) and a
+    /// CAD checking account (3), and then `updates`.
+    fn app_data_with(updates: Vec<Update>) -> AppData {
+        let mut setup = vec![
+            Update::SetCurrency(currency(0, "USD")),
+            Update::SetCurrency(currency(1, "CAD")),
+            Update::SetAccount(account(0, 0, true)),
+            Update::SetAccount(account(1, 0, true)),
+            Update::SetAccount(account(2, 0, false)),
+            Update::SetAccount(account(3, 1, true)),
+        ];
+        for (id, name) in ["Groceries", "Household"].iter().enumerate() {
+            setup.push(Update::SetCategory(category(id as u32, name, None)));
+        }
+        setup.extend(updates);
+        test_fixtures::app_data_with(setup)
+    }
+
+    fn grouped(transaction: Transaction, transaction_group_id: u32) -> Transaction {
+        Transaction {
+            transaction_group_id: Some(transaction_group_id),
+            ..transaction
+        }
+    }
+
+    #[test]
+    fn linking_rebalances_the_groups_left_behind() {
+        let mut app_data = app_data_with(vec![
+            Update::SetTransaction(grouped(transaction(0, 0, 1, -10000), 0)),
+            Update::SetTransaction(grouped(transaction(1, 0, 1, -5000), 0)),
+            Update::SetTransaction(transaction(2, 1, 2, 5000)),
+            Update::SetFlow(flow(0, 0, -9001, 0)),
+            Update::SetFlow(flow(1, 1, -5999, 0)),
+            Update::SetTransactionGroup(TransactionGroup {
+                id: 0,
+                transaction_ids: vec![0, 1],
+                flow_ids: vec![0, 1],
+            }),
+        ]);
+        let updates = link_transfer_updates(&app_data, 1, 2);
+        app_data.perform_update("Link transfer", updates).unwrap();
+
+        let transaction_group = &app_data.transaction_groups()[&0];
+        assert_eq!(transaction_group.transaction_ids, [0]);
+        assert_eq!(transaction_group.flow_ids, [0, 1]);
+        assert_eq!(app_data.flows()[&0].amount, Money::from_minor(-6001));
+        assert_eq!(app_data.flows()[&1].amount, Money::from_minor(-3999));
+        let flows: Vec<Flow> = app_data.flows().values().cloned().collect();
+        assert!(is_balanced(
+            &app_data,
+            &transaction_group.transaction_ids,
+            &flows
+        ));
+        assert_eq!(app_data.transactions()[&1].transaction_group_id, None);
+        assert_eq!(app_data.transactions()[&1].transfer_transaction_id, Some(2));
+        assert_eq!(app_data.transactions()[&2].transfer_transaction_id, Some(1));
+    }
+
+    #[test]
+    fn linking_drops_flows_and_groups_that_are_left_empty() {
+        let mut app_data = app_data_with(vec![
+            Update::SetTransaction(grouped(transaction(0, 0, 1, -10000), 0)),
+            Update::SetTransaction(grouped(transaction(1, 0, 1, 5000), 0)),
+            Update::SetTransaction(grouped(transaction(2, 1, 1, -5000), 1)),
+            Update::SetFlow(flow(0, 0, -10000, 0)),
+            Update::SetFlow(flow(1, 1, 5000, 0)),
+            Update::SetFlow(flow(2, 1, -5000, 1)),
+            Update::SetTransactionGroup(TransactionGroup {
+                id: 0,
+                transaction_ids: vec![0, 1],
+                flow_ids: vec![0, 1],
+            }),
+            Update::SetTransactionGroup(TransactionGroup {
+                id: 1,
+                transaction_ids: vec![2],
+                flow_ids: vec![2],
+            }),
+        ]);
+        let updates = link_transfer_updates(&app_data, 1, 2);
+        app_data.perform_update("Link transfer", updates).unwrap();
+
+        // Group 0 is left with just the transaction of -100.00, so its flows are doubled.
+        assert_eq!(app_data.transaction_groups()[&0].flow_ids, [0, 1]);
+        assert_eq!(app_data.flows()[&0].amount, Money::from_minor(-20000));
+        assert_eq!(app_data.flows()[&1].amount, Money::from_minor(10000));
+        assert!(!app_data.transaction_groups().contains_key(&1));
+        assert!(!app_data.flows().contains_key(&2));
+    }
+
+    #[test]
+    fn scales_amounts_exactly() {
+        let minors = |amounts: Option<Vec<Money>>| {
+            amounts.map(|amounts| {
+                amounts
+                    .iter()
+                    .map(|amount| amount.minor())
+                    .collect::<Vec<_>>()
+            })
+        };
+        let amounts = [
+            Money::from_minor(-1),
+            Money::from_minor(-1),
+            Money::from_minor(-1),
+        ];
+        assert_eq!(
+            minors(scale_amounts(&amounts, Money::from_minor(1))),
+            Some(vec![-1, 0, -1])
+        );
+        assert_eq!(
+            minors(scale_amounts(&amounts, Money::from_minor(3))),
+            Some(vec![0, 0, 0])
+        );
+        let mixed = [Money::from_minor(300), Money::from_minor(-100)];
+        assert_eq!(
+            minors(scale_amounts(&mixed, Money::from_minor(-100))),
+            Some(vec![150, -50])
+        );
+        let cancelling = [Money::from_minor(100), Money::from_minor(-100)];
+        assert_eq!(scale_amounts(&cancelling, Money::from_minor(1)), None);
+        let large = [Money::from_minor(1), Money::from_minor(i64::MAX - 1)];
+        assert_eq!(scale_amounts(&large, Money::from_minor(i64::MAX)), None);
+    }
+
+    #[test]
+    fn suggests_transfers_in_the_same_currency() {
+        let app_data = app_data_with(vec![
+            Update::SetTransaction(transaction(0, 0, 1, -5000)),
+            Update::SetTransaction(transaction(1, 1, 3, 5000)),
+            Update::SetTransaction(transaction(2, 1, 3, 4999)),
+            Update::SetTransaction(transaction(3, 1, 20, 5000)),
+        ]);
+        assert_eq!(
+            suggest_transfers(&app_data, 5),
+            vec![TransferSuggestion {
+                from_id: 0,
+                to_id: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn suggests_transfers_between_currencies_within_tolerance() {
+        let app_data = app_data_with(vec![
+            Update::SetCurrency(Currency {
+                equivalent_base: 0.75,
+                ..currency(1, "CAD")
+            }),
+            Update::SetTransaction(transaction(0, 0, 1, -10000)),
+            Update::SetTransaction(transaction(1, 3, 2, 13000)),
+            Update::SetTransaction(transaction(2, 0, 1, -20000)),
+            Update::SetTransaction(transaction(3, 3, 2, 20000)),
+        ]);
+        assert_eq!(
+            suggest_transfers(&app_data, 5),
+            vec![TransferSuggestion {
+                from_id: 0,
+                to_id: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn paying_a_credit_card_is_a_transfer_to_it() {
+        let mut app_data = app_data_with(vec![
+            Update::SetTransaction(transaction(0, 0, 1, -5000)),
+            Update::SetTransaction(transaction(1, 2, 2, -5000)),
+        ]);
+        assert_eq!(
+            suggest_transfers(&app_data, 5),
+            vec![TransferSuggestion {
+                from_id: 0,
+                to_id: 1
+            }]
+        );
+
+        let updates = link_transfer_updates(&app_data, 0, 1);
+        app_data.perform_update("Link transfer", updates).unwrap();
+        let payment = app_data.transactions().get(&1).unwrap();
+        assert_eq!(transfer_rate(&app_data, payment), Some(1.0));
+        assert_eq!(transfer_fee(&app_data, payment), Some(Money::ZERO));
+    }
+}
@@ -1,7 +1,12 @@
 use std::time::SystemTime;
 
+use serde::{Deserialize, Serialize};
+
+use super::derived_data::Change;
+use super::integrity::EntityRef;
 use super::models::*;
 
+#[derive(Serialize, Deserialize)]
 pub enum Update {
     SetAccount(Account),
     DeleteAccount(u32),
@@ -15,6 +20,14 @@ pub enum Update {
     DeleteTransactionGroup(u32),
     SetTransaction(Transaction),
     DeleteTransaction(u32),
+    SetStatement(Statement),
+    DeleteStatement(u32),
+    SetRule(Rule),
+    DeleteRule(u32),
+    SetBudget(Budget),
+    /// Deletes the budget of the category with this id.
+    DeleteBudget(u32),
+    SetSettings(Settings),
 }
 
 macro_rules! set_or_delete_option {
@@ -27,49 +40,125 @@ macro_rules! set_or_delete_option {
 }
 
 impl Update {
+    /// The entity that this update deletes, if it deletes one that others can refer to.
+    fn deleted_entity(&self) -> Option<EntityRef> {
+        match self {
+            Self::DeleteAccount(id) => Some(EntityRef::Account(*id)),
+            Self::DeleteCategory(id) => Some(EntityRef::Category(*id)),
+            Self::DeleteCurrency(id) => Some(EntityRef::Currency(*id)),
+            _ => None,
+        }
+    }
+
+    /// The entities that the entity set by this update refers to, which must exist.
+    fn referenced_entities(&self) -> Vec<EntityRef> {
+        match self {
+            Self::SetAccount(account) => vec![EntityRef::Currency(account.currency_id)],
+            Self::SetCategory(category) => category
+                .parent_id
+                .map(EntityRef::Category)
+                .into_iter()
+                .collect(),
+            Self::SetCurrency(currency) => currency
+                .exchange_rates
+                .keys()
+                .map(|id| EntityRef::Currency(*id))
+                .collect(),
+            Self::SetFlow(flow) => vec![
+                EntityRef::Category(flow.category_id),
+                EntityRef::Currency(flow.currency_id),
+            ],
+            Self::SetTransaction(transaction) => {
+                std::iter::once(EntityRef::Account(transaction.account_id))
+                    .chain(
+                        transaction
+                            .original
+                            .map(|original| EntityRef::Currency(original.currency_id)),
+                    )
+                    .collect()
+            }
+            Self::SetStatement(statement) => vec![EntityRef::Account(statement.account_id)],
+            Self::SetRule(rule) => rule
+                .account_id
+                .map(EntityRef::Account)
+                .into_iter()
+                .chain(
+                    rule.flows
+                        .iter()
+                        .map(|rule_flow| EntityRef::Category(rule_flow.category_id)),
+                )
+                .collect(),
+            Self::SetBudget(budget) => vec![EntityRef::Category(budget.category_id)],
+            Self::SetSettings(settings) => settings
+                .base_currency_id
+                .map(EntityRef::Currency)
+                .into_iter()
+                .collect(),
+            Self::DeleteAccount(_)
+            | Self::DeleteCategory(_)
+            | Self::DeleteCurrency(_)
+            | Self::DeleteFlow(_)
+            | Self::SetTransactionGroup(_)
+            | Self::DeleteTransactionGroup(_)
+            | Self::DeleteTransaction(_)
+            | Self::DeleteStatement(_)
+            | Self::DeleteRule(_)
+            | Self::DeleteBudget(_) => Vec::new(),
+        }
+    }
+
     /// `apply` performs the update on `app_data` and returns the reverse update.
     pub fn apply(self, app_data: &mut AppData) -> Self {
         match self {
             Self::SetAccount(account) => {
                 let id = account.id;
                 let old_account = app_data.accounts.insert(id, account);
+                app_data.apply_change(Change::Account(id));
                 set_or_delete_option!(old_account, id, SetAccount, DeleteAccount)
             }
             Self::DeleteAccount(id) => {
                 let old_account = app_data.accounts.remove(&id);
+                app_data.apply_change(Change::Account(id));
                 set_or_delete_option!(old_account, id, SetAccount, DeleteAccount)
             }
             Self::SetCategory(category) => {
                 let id = category.id;
                 let old_category = app_data.categories.insert(id, category);
+                app_data.apply_change(Change::Category);
                 set_or_delete_option!(old_category, id, SetCategory, DeleteCategory)
             }
             Self::DeleteCategory(id) => {
                 let old_category = app_data.categories.remove(&id);
+                app_data.apply_change(Change::Category);
                 set_or_delete_option!(old_category, id, SetCategory, DeleteCategory)
             }
             Self::SetCurrency(currency) => {
                 let id = currency.id;
                 let old_currency = app_data.currencies.insert(id, currency);
+                app_data.apply_change(Change::Currency);
                 set_or_delete_option!(old_currency, id, SetCurrency, DeleteCurrency)
             }
             Self::DeleteCurrency(id) => {
                 let old_currency = app_data.currencies.remove(&id);
+                app_data.apply_change(Change::Currency);
                 set_or_delete_option!(old_currency, id, SetCurrency, DeleteCurrency)
             }
             Self::SetFlow(flow) => {
                 let id = flow.id;
                 let old_flow = app_data.flows.insert(id, flow);
+                app_data.apply_change(Change::Flow);
                 set_or_delete_option!(old_flow, id, SetFlow, DeleteFlow)
             }
             Self::DeleteFlow(id) => {
                 let old_flow = app_data.flows.remove(&id);
+                app_data.apply_change(Change::Flow);
                 set_or_delete_option!(old_flow, id, SetFlow, DeleteFlow)
             }
             Self::SetTransactionGroup(transaction_group) => {
                 let id = transaction_group.id;
                 let old_transaction_group =
                     app_data.transaction_groups.insert(id, transaction_group);
+                app_data.apply_change(Change::TransactionGroup);
                 set_or_delete_option!(
                     old_transaction_group,
                     id,
@@ -79,6 +168,7 @@ impl Update {
             }
             Self::DeleteTransactionGroup(id) => {
                 let old_transaction_group = app_data.transaction_groups.remove(&id);
+                app_data.apply_change(Change::TransactionGroup);
                 set_or_delete_option!(
                     old_transaction_group,
                     id,
@@ -88,31 +178,100 @@ impl Update {
             }
             Self::SetTransaction(transaction) => {
                 let id = transaction.id;
-                let old_transaction = app_data.insert_transaction(transaction);
+                let old_transaction = app_data.transactions.insert(id, transaction);
+                app_data.apply_change(Change::Transaction(id, old_transaction.as_ref()));
                 set_or_delete_option!(old_transaction, id, SetTransaction, DeleteTransaction)
             }
             Self::DeleteTransaction(id) => {
-                let old_transaction = app_data.remove_transaction(id);
+                let old_transaction = app_data.transactions.remove(&id);
+                app_data.apply_change(Change::Transaction(id, old_transaction.as_ref()));
                 set_or_delete_option!(old_transaction, id, SetTransaction, DeleteTransaction)
             }
+            Self::SetStatement(statement) => {
+                let id = statement.id;
+                let old_statement = app_data.statements.insert(id, statement);
+                app_data.apply_change(Change::Statement);
+                set_or_delete_option!(old_statement, id, SetStatement, DeleteStatement)
+            }
+            Self::DeleteStatement(id) => {
+                let old_statement = app_data.statements.remove(&id);
+                app_data.apply_change(Change::Statement);
+                set_or_delete_option!(old_statement, id, SetStatement, DeleteStatement)
+            }
+            Self::SetRule(rule) => {
+                let id = rule.id;
+                let old_rule = app_data.rules.insert(id, rule);
+                app_data.apply_change(Change::Rule);
+                set_or_delete_option!(old_rule, id, SetRule, DeleteRule)
+            }
+            Self::DeleteRule(id) => {
+                let old_rule = app_data.rules.remove(&id);
+                app_data.apply_change(Change::Rule);
+                set_or_delete_option!(old_rule, id, SetRule, DeleteRule)
+            }
+            Self::SetBudget(budget) => {
+                let id = budget.category_id;
+                let old_budget = app_data.budgets.insert(id, budget);
+                app_data.apply_change(Change::Budget);
+                set_or_delete_option!(old_budget, id, SetBudget, DeleteBudget)
+            }
+            Self::DeleteBudget(id) => {
+                let old_budget = app_data.budgets.remove(&id);
+                app_data.apply_change(Change::Budget);
+                set_or_delete_option!(old_budget, id, SetBudget, DeleteBudget)
+            }
+            Self::SetSettings(settings) => {
+                let old_settings = std::mem::replace(&mut app_data.settings, settings);
+                app_data.apply_change(Change::Settings);
+                Self::SetSettings(old_settings)
+            }
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
+/// A batch of updates that is done and undone as a single step. The reverse of a batch keeps its
+/// label and time, so the undo and redo stacks describe the edits they would undo or redo.
 pub struct Updates {
-    #[allow(dead_code)]
     time: SystemTime,
+    #[serde(default)]
+    label: String,
     updates: Vec<Update>,
 }
 
 impl Updates {
-    pub fn new(updates: Vec<Update>) -> Self {
+    pub fn new(label: String, updates: Vec<Update>) -> Self {
         Self {
             time: SystemTime::now(),
+            label,
             updates,
         }
     }
 
+    /// When the updates were made.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// A description of the edit, such as "Edit account 'Checking'".
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn deleted_entities(&self) -> Vec<EntityRef> {
+        self.updates
+            .iter()
+            .filter_map(|update| update.deleted_entity())
+            .collect()
+    }
+
+    pub fn referenced_entities(&self) -> Vec<EntityRef> {
+        self.updates
+            .iter()
+            .flat_map(|update| update.referenced_entities())
+            .collect()
+    }
+
     /// `apply` performs the updates on `app_data` and returns the reverse updates.
     pub fn apply(self, app_data: &mut AppData) -> Self {
         let mut reverse_updates = Vec::new();
@@ -120,8 +279,10 @@ impl Updates {
             reverse_updates.push(update.apply(app_data));
         }
         reverse_updates.reverse();
+        app_data.check_derived_data();
         Self {
-            time: SystemTime::now(),
+            time: self.time,
+            label: self.label,
             updates: reverse_updates,
         }
     }
@@ -1,9 +1,37 @@
 use thiserror::Error;
 
+use crate::data::{Dependents, EntityRef};
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("io error")]
     Io(#[from] std::io::Error),
     #[error("serde error")]
     Serde(#[from] serde_json::Error),
+    #[error("statement parse error: {0}")]
+    StatementParse(String),
+    #[error("exchange rate parse error: {0}")]
+    RatesParse(String),
+    #[error("regex error")]
+    Regex(#[from] regex::Error),
+    #[error(
+        "the file was saved in format version {0}, which is newer than this version of Ebbify"
+    )]
+    NewerFormat(u32),
+    #[error("the file is truncated or corrupt: {0}")]
+    Corrupt(String),
+    #[error("the file is encrypted and needs a passphrase")]
+    PassphraseRequired,
+    #[error("the passphrase is wrong")]
+    WrongPassphrase,
+    #[error("the file's contents don't match its authentication tag; it was modified or damaged")]
+    Tampered,
+    #[error("migration error: {0}")]
+    Migration(String),
+    #[error("{0:?} is still used by {1}")]
+    HasDependents(EntityRef, Box<Dependents>),
+    #[error("{0:?} doesn't exist")]
+    MissingEntity(EntityRef),
+    #[error("{0:?} can't be reassigned: {1}")]
+    CannotReassign(EntityRef, String),
 }
@@ -2,7 +2,10 @@ use std::collections::HashSet;
 
 use egui::{Context, Ui};
 
-use crate::components::{AccountManager, CategoryManager, CurrencyManager, TransactionList};
+use crate::components::{
+    AccountManager, BudgetManager, CategoryManager, CurrencyManager, HistoryManager, NetWorthChart,
+    ReportManager, RuleManager, StatementImporter, TransactionManager,
+};
 use crate::data::AppData;
 
 #[derive(Default, PartialEq, Eq, Clone, Copy)]
@@ -12,6 +15,11 @@ pub enum Tab {
     CurrencyManager,
     AccountManager,
     Transactions,
+    RuleManager,
+    Reports,
+    Budget,
+    NetWorth,
+    History,
 }
 
 #[derive(Default)]
@@ -20,7 +28,14 @@ pub struct UiState {
     pub category_manager: CategoryManager,
     pub currency_manager: CurrencyManager,
     pub account_manager: AccountManager,
+    pub transaction_manager: TransactionManager,
+    pub rule_manager: RuleManager,
+    pub report_manager: ReportManager,
+    pub budget_manager: BudgetManager,
+    pub net_worth_chart: NetWorthChart,
+    pub history_manager: HistoryManager,
     pub transaction_selection: HashSet<u32>,
+    pub statement_importer: Option<StatementImporter>,
 }
 
 impl UiState {
@@ -31,6 +46,11 @@ impl UiState {
                 (Tab::CurrencyManager, "Currencies"),
                 (Tab::AccountManager, "Accounts"),
                 (Tab::Transactions, "Transactions"),
+                (Tab::RuleManager, "Rules"),
+                (Tab::Reports, "Reports"),
+                (Tab::Budget, "Budget"),
+                (Tab::NetWorth, "Net Worth"),
+                (Tab::History, "History"),
             ] {
                 ui.selectable_value(&mut self.current_tab, *tab, *name);
             }
@@ -43,9 +63,21 @@ impl UiState {
             Tab::CurrencyManager => self.currency_manager.add(ui, ctx, app_data),
             Tab::AccountManager => self.account_manager.add(ui, ctx, app_data),
             Tab::Transactions => {
-                TransactionList::new(&app_data.transactions().keys().map(|i| *i).collect())
-                    .selection(&mut self.transaction_selection)
-                    .add(ui, app_data);
+                self.transaction_manager
+                    .add(ui, ctx, app_data, &mut self.transaction_selection)
+            }
+            Tab::RuleManager => self.rule_manager.add(ui, ctx, app_data),
+            Tab::Reports => self.report_manager.add(ui, ctx, app_data),
+            Tab::Budget => self.budget_manager.add(ui, ctx, app_data),
+            Tab::NetWorth => self.net_worth_chart.add(ui, ctx, app_data),
+            Tab::History => self.history_manager.add(ui, app_data),
+        }
+    }
+
+    pub fn add_windows(&mut self, ctx: &Context, app_data: &mut AppData) {
+        if let Some(statement_importer) = &mut self.statement_importer {
+            if !statement_importer.add(ctx, app_data) {
+                self.statement_importer = None;
             }
         }
     }
@@ -0,0 +1,64 @@
+use egui::{ComboBox, Response, Ui, Widget};
+
+use crate::data::AppData;
+
+pub struct AccountPicker<'a> {
+    id_source: &'a str,
+    selected: &'a mut Option<u32>,
+    null_allowed: bool,
+    currency_id: Option<u32>,
+    app_data: &'a AppData,
+}
+
+impl<'a> AccountPicker<'a> {
+    pub fn new(
+        id_source: &'a str,
+        selected: &'a mut Option<u32>,
+        null_allowed: bool,
+        app_data: &'a AppData,
+    ) -> Self {
+        Self {
+            id_source,
+            selected,
+            null_allowed,
+            currency_id: None,
+            app_data,
+        }
+    }
+
+    /// Only offers accounts in the currency with id `currency_id`.
+    pub fn currency(mut self, currency_id: u32) -> Self {
+        self.currency_id = Some(currency_id);
+        self
+    }
+
+    fn selected_text(&self) -> &str {
+        match &self.selected {
+            Some(selected) => self
+                .app_data
+                .accounts()
+                .get(selected)
+                .map_or("", |account| account.name.as_str()),
+            None => "",
+        }
+    }
+}
+
+impl<'a> Widget for AccountPicker<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        ComboBox::from_id_source(self.id_source)
+            .width(200.0)
+            .selected_text(self.selected_text())
+            .show_ui(ui, |ui| {
+                if self.selected.is_none() || self.null_allowed {
+                    ui.selectable_value(self.selected, None, "");
+                }
+                for account in self.app_data.accounts().values().filter(|account| {
+                    self.currency_id.is_none() || self.currency_id == Some(account.currency_id)
+                }) {
+                    ui.selectable_value(self.selected, Some(account.id), &account.name);
+                }
+            })
+            .response
+    }
+}
@@ -0,0 +1,46 @@
+use egui::{ComboBox, Response, Ui, Widget};
+
+use crate::data::AmortizationType;
+
+pub struct AmortizationPicker<'a> {
+    id_source: &'a str,
+    selected: &'a mut Option<AmortizationType>,
+}
+
+impl<'a> AmortizationPicker<'a> {
+    pub fn new(id_source: &'a str, selected: &'a mut Option<AmortizationType>) -> Self {
+        Self {
+            id_source,
+            selected,
+        }
+    }
+
+    fn text(amortization_type: &Option<AmortizationType>) -> &'static str {
+        match amortization_type {
+            Some(AmortizationType::Linear) => "Linear",
+            Some(AmortizationType::Declining) => "Declining",
+            None => "None",
+        }
+    }
+}
+
+impl<'a> Widget for AmortizationPicker<'a> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        ComboBox::from_id_source(self.id_source)
+            .selected_text(Self::text(self.selected))
+            .show_ui(ui, |ui| {
+                for amortization_type in [
+                    None,
+                    Some(AmortizationType::Linear),
+                    Some(AmortizationType::Declining),
+                ] {
+                    ui.selectable_value(
+                        self.selected,
+                        amortization_type,
+                        Self::text(&amortization_type),
+                    );
+                }
+            })
+            .response
+    }
+}
@@ -28,20 +28,15 @@ impl<'a> CategoryPicker<'a> {
     }
 
     fn show_node(&mut self, node: &CategoryNode, ui: &mut Ui) {
-        match self.exclude_id {
-            Some(id) => {
-                if node.id == *id {
-                    return;
-                }
-            }
-            None => (),
+        if *self.exclude_id == Some(node.id) {
+            return;
         }
         ui.selectable_value(
             self.selected,
             Some(node.id),
             &self.app_data.categories().get(&node.id).unwrap().name,
         );
-        if node.children.len() > 0 {
+        if !node.children.is_empty() {
             ui.indent(node.id, |ui| {
                 for child in node.children.iter() {
                     self.show_node(child, ui);
@@ -1,13 +1,19 @@
+mod account_picker;
+mod amortization_picker;
 mod category_picker;
 mod currency_picker;
 mod date_input;
+mod months_input;
 mod price_input;
 mod stringable_input;
 mod validated_text_edit;
 
+pub use account_picker::AccountPicker;
+pub use amortization_picker::AmortizationPicker;
 pub use category_picker::CategoryPicker;
 pub use currency_picker::CurrencyPicker;
 pub use date_input::date_input;
+pub use months_input::months_input;
 pub use price_input::price_input;
 pub use stringable_input::stringable_input;
 pub use validated_text_edit::ValidatedTextEdit;
@@ -0,0 +1,15 @@
+use super::ValidatedTextEdit;
+use crate::data::MAX_AMORTIZATION_LENGTH;
+
+/// An input for an amortization length, which reverts if the text isn't a number of months from
+/// 1 to `MAX_AMORTIZATION_LENGTH`.
+pub fn months_input(value: &mut i32) -> ValidatedTextEdit<'_, i32> {
+    ValidatedTextEdit::new(value)
+        .display_formatter(|months: &i32| months.to_string())
+        .parser(|s: &str, _months: &i32| {
+            s.trim()
+                .parse()
+                .ok()
+                .filter(|months| (1..=MAX_AMORTIZATION_LENGTH).contains(months))
+        })
+}
@@ -1,12 +1,12 @@
 use super::ValidatedTextEdit;
-use crate::data::{Currency, NumericPrice, Price};
+use crate::data::{parse_price, Currency, Money, NumericPrice, Price};
 
-pub fn price_input<'a>(value: &'a mut i32, currency: &'a Currency) -> ValidatedTextEdit<'a, i32> {
+pub fn price_input<'a>(
+    value: &'a mut Money,
+    currency: &'a Currency,
+) -> ValidatedTextEdit<'a, Money> {
     ValidatedTextEdit::new(value)
-        .display_formatter(|value: &i32| format!("{}", Price::new(*value, currency)))
-        .edit_formatter(|value: &i32| format!("{}", NumericPrice::new(*value, currency)))
-        .parser(|s: &str, _t: &i32| match s.parse::<f32>().ok() {
-            Some(raw_price) => Some((raw_price * (currency.major as f32)).round() as i32),
-            None => None,
-        })
+        .display_formatter(|value: &Money| format!("{}", Price::new(*value, currency)))
+        .edit_formatter(|value: &Money| format!("{}", NumericPrice::new(*value, currency)))
+        .parser(|s: &str, _t: &Money| parse_price(s, currency))
 }